@@ -1,14 +1,79 @@
+use std::cell::RefCell;
 use std::collections::HashSet;
+use std::fmt;
+use std::rc::Rc;
 
-use crate::ir::ast::{Environment, Expression, Function, Name, Statement, TestEnvironment};
+use crate::ir::ast::{
+    Arguments, Environment, Expression, Function, Name, Pattern, Statement, TestEnvironment, Type,
+    TypeDef,
+};
 
-type ErrorMessage = (String, Option<Expression>);
+// The message of an error, and the value carried by an error propagated with '?'
+type ErrorMessage = (String, Option<Box<EnvValue>>);
+
+// The values of the default arguments of a function, evaluated when it is defined
+type Defaults = Vec<(Name, EnvValue)>;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum EnvValue {
     Exp(Expression),
-    Func(Function),
+    Func(Function, Defaults),
+    Closure(Function, Defaults, Environment<EnvValue>), // Nested function, with the scope it was defined in
+    Cell(Rc<RefCell<Option<EnvValue>>>), // Variable shared between scopes, empty until assigned
     TestEnvironment(TestEnvironment<EnvValue>),
+    Record(Name, Vec<(Name, EnvValue)>), // Struct name, field values
+    Variant(Name, Name, Vec<EnvValue>),  // Enum name, constructor, payload
+    List(Vec<EnvValue>),
+}
+
+impl fmt::Display for EnvValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EnvValue::Exp(exp) => write_value(exp, f),
+            EnvValue::Func(func, _) | EnvValue::Closure(func, _, _) => {
+                write!(f, "<function {}>", func.name)
+            }
+            EnvValue::TestEnvironment(test_env) => write!(f, "<test {}>", test_env.name),
+            EnvValue::Cell(cell) => match &*cell.borrow() {
+                Some(value) => write!(f, "{}", value),
+                None => write!(f, "<unassigned>"),
+            },
+            EnvValue::Record(name, fields) => {
+                write!(f, "{}(", name)?;
+                for (i, (field, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}={}", field, value)?;
+                }
+                write!(f, ")")
+            }
+            EnvValue::Variant(_, constructor, payload) if payload.is_empty() => {
+                write!(f, "{}", constructor)
+            }
+            EnvValue::Variant(_, constructor, payload) => {
+                let payload: Vec<String> = payload.iter().map(|v| v.to_string()).collect();
+                write!(f, "{}({})", constructor, payload.join(", "))
+            }
+            EnvValue::List(values) => {
+                let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+                write!(f, "[{}]", values.join(", "))
+            }
+        }
+    }
+}
+
+// Write a runtime value the way it is written in source code
+fn write_value(exp: &Expression, f: &mut fmt::Formatter) -> fmt::Result {
+    match exp {
+        Expression::CTrue => write!(f, "True"),
+        Expression::CFalse => write!(f, "False"),
+        Expression::CVoid => write!(f, "None"),
+        Expression::CInt(value) => write!(f, "{}", value),
+        Expression::CReal(value) => write!(f, "{:?}", value),
+        Expression::CString(value) => write!(f, "{:?}", value),
+        _ => write!(f, "{:?}", exp),
+    }
 }
 
 pub enum ControlFlow {
@@ -30,15 +95,49 @@ pub fn eval(exp: Expression, env: &Environment<EnvValue>) -> Result<EnvValue, Er
         Expression::LT(lhs, rhs) => lt(*lhs, *rhs, env),
         Expression::GTE(lhs, rhs) => gte(*lhs, *rhs, env),
         Expression::LTE(lhs, rhs) => lte(*lhs, *rhs, env),
-        Expression::Var(name) => lookup(name, env),
+        Expression::Var(name) => eval_var(name, env),
         Expression::COk(e) => eval_ok(*e, env),
         Expression::CErr(e) => eval_err(*e, env),
         Expression::CJust(e) => eval_just(*e, env),
+        Expression::CNothing => Ok(builtin_variant("Nothing", vec![])),
         Expression::Unwrap(e) => eval_unwrap_expression(*e, env),
         Expression::Propagate(e) => eval_propagate_expression(*e, env),
         Expression::IsError(e) => eval_iserror_expression(*e, env),
         Expression::IsNothing(e) => eval_isnothing_expression(*e, env),
+        Expression::Map(e, f) => eval_combinator("map", *e, *f, env),
+        Expression::AndThen(e, f) => eval_combinator("and_then", *e, *f, env),
+        Expression::OrElse(e, f) => eval_combinator("or_else", *e, *f, env),
+        Expression::UnwrapOr(e, d) => eval_combinator("unwrap_or", *e, *d, env),
+        Expression::OkOr(e, err) => eval_combinator("ok_or", *e, *err, env),
         Expression::FuncCall(name, args) => call(name, args, env),
+        Expression::Call(callee, args) => {
+            let func = eval(*callee, env)?;
+            apply_function(func, eval_arguments(args, env)?, env)
+        }
+        Expression::KeywordArg(name, _) => Err((
+            format!("keyword argument '{}' outside of a call.", name),
+            None,
+        )),
+        Expression::Unpack(_) => Err((
+            String::from("unpacking is only allowed in the arguments of a call."),
+            None,
+        )),
+        Expression::Lambda(func) => function_value(*func, env),
+        Expression::Match(exp, arms) => {
+            let value = eval(*exp, env)?;
+
+            // Evaluate the first arm whose pattern matches
+            let arm = arms.into_iter().find_map(|(pattern, arm)| {
+                let mut arm_env = env.clone();
+                bind_pattern(&pattern, &value, &mut arm_env).then_some((arm, arm_env))
+            });
+
+            match arm {
+                Some((arm, arm_env)) => eval(arm, &arm_env),
+                None => Err((format!("no case matches the value '{}'.", value), None)),
+            }
+        }
+        Expression::FieldAccess(record, field) => eval_field_access(*record, field, env),
         _ if is_constant(exp.clone()) => Ok(EnvValue::Exp(exp)),
         _ => Err((String::from("Not implemented yet."), None)),
     }
@@ -56,11 +155,57 @@ fn execute(stmt: Statement, env: &Environment<EnvValue>) -> Result<ControlFlow,
 
     let result = match stmt {
         Statement::Assignment(name, exp, _) => {
+            if new_env.is_reassigning_val(&name) && is_assigned(&name, &new_env) {
+                return Err((
+                    format!(
+                        "[Runtime Error on '{}()'] cannot reassign '{}', declared with 'val'.",
+                        new_env.scope_name(),
+                        name
+                    ),
+                    None,
+                ));
+            }
+            let value = eval(*exp, &new_env)?;
+            assign_variable(name, value, &mut new_env);
+            Ok(ControlFlow::Continue(new_env))
+        }
+
+        Statement::FieldAssignment(record, field, exp) => {
             let value = eval(*exp, &new_env)?;
-            new_env.insert_variable(name, value); // Remove the tuple
+            assign_field(*record, field, value, &mut new_env)?;
+            Ok(ControlFlow::Continue(new_env))
+        }
+
+        Statement::StructDef(name, fields) => {
+            new_env.insert_type(name, TypeDef::Struct(fields));
+            Ok(ControlFlow::Continue(new_env))
+        }
+
+        Statement::EnumDef(name, constructors) => {
+            new_env.insert_type(name, TypeDef::Enum(constructors));
             Ok(ControlFlow::Continue(new_env))
         }
 
+        Statement::Match(exp, arms) => {
+            let value = eval(*exp, &new_env)?;
+
+            // Run the first arm whose pattern matches
+            let arm = arms.into_iter().find_map(|(pattern, stmt)| {
+                let mut arm_env = new_env.clone();
+                bind_pattern(&pattern, &value, &mut arm_env).then_some((stmt, arm_env))
+            });
+
+            match arm {
+                Some((Statement::Block(stmts), arm_env)) => execute_block(stmts, &arm_env),
+                Some((stmt, arm_env)) => execute(stmt, &arm_env),
+                None => Err((format!("no case matches the value '{}'.", value), None)),
+            }
+        }
+
+        // The initializer, if any, follows as an Assignment
+        Statement::VarDeclaration(name, kind) => declare_variable(name, true, kind, new_env),
+        Statement::ValDeclaration(name, kind) => declare_variable(name, false, kind, new_env),
+
         Statement::IfThenElse(cond, stmt_then, stmt_else) => {
             let value = eval(*cond, &new_env)?;
 
@@ -192,7 +337,12 @@ fn execute(stmt: Statement, env: &Environment<EnvValue>) -> Result<ControlFlow,
             ControlFlow::Return(value) => return Ok(ControlFlow::Return(value)),
         },
         Statement::FuncDef(func) => {
-            new_env.insert_variable(func.name.clone(), EnvValue::Func(func.clone()));
+            if let Some(body) = &func.body {
+                share_outer_variables(body, &mut new_env);
+            }
+            let name = func.name.clone();
+            let value = function_value(func, &new_env)?;
+            assign_variable(name, value, &mut new_env);
             Ok(ControlFlow::Continue(new_env))
         }
 
@@ -200,7 +350,10 @@ fn execute(stmt: Statement, env: &Environment<EnvValue>) -> Result<ControlFlow,
             let exp_value = eval(*exp, &new_env)?;
             Ok(ControlFlow::Return(exp_value))
         }
-        _ => Err((String::from("not implemented yet"), None)),
+        Statement::Global(names) => declare_outer(names, true, new_env),
+        Statement::Nonlocal(names) => declare_outer(names, false, new_env),
+        Statement::Invalid(error) => Err((error.to_string(), None)),
+        Statement::Located(_, stmt) => execute(*stmt, &new_env),
     };
 
     match result {
@@ -209,7 +362,7 @@ fn execute(stmt: Statement, env: &Environment<EnvValue>) -> Result<ControlFlow,
             if s != "Propagate".to_string() {
                 return Err((s, None));
             } else {
-                return propagate_error(opt.unwrap(), env);
+                return propagate_error(*opt.unwrap(), env);
             }
         }
     }
@@ -236,43 +389,445 @@ fn call(
     args: Vec<Expression>,
     env: &Environment<EnvValue>,
 ) -> Result<EnvValue, ErrorMessage> {
-    // Use search_frame instead of get
-    match env.search_frame(name.clone()) {
-        Some(EnvValue::Func(func)) => {
-            let mut new_env = Environment::new();
-
-            // Copy global functions
-            let mut curr_scope = env.scope_key();
-            loop {
-                let frame = env.get_frame(curr_scope.clone());
-                for (name, value) in &frame.variables {
-                    if let EnvValue::Func(_) = value {
-                        new_env.insert_variable(name.clone(), value.clone());
-                    }
-                }
-                match &frame.parent_key {
-                    Some(parent) => curr_scope = parent.clone(),
-                    None => break,
+    match lookup(name.clone(), env) {
+        Ok(func @ (EnvValue::Func(_, _) | EnvValue::Closure(_, _, _))) => {
+            apply_function(func, eval_arguments(args, env)?, env)
+        }
+        _ => {
+            if let Some(TypeDef::Struct(fields)) = env.search_type(&name) {
+                let fields = fields.iter().map(|(field, _)| field.clone()).collect();
+                return construct_record(name, fields, args, env);
+            }
+            match env.search_constructor(&name) {
+                Some((enum_name, payload)) => {
+                    let values = eval_constructor_args(&name, payload.len(), args, env)?;
+                    Ok(EnvValue::Variant(enum_name, name, values))
                 }
+                None => Err((format!("Function {} not found", name), None)),
             }
+        }
+    }
+}
 
-            // Bind arguments
-            if let Some(params) = &func.params {
-                for (param, arg) in params.iter().zip(args) {
-                    let arg_value = eval(arg, env)?;
-                    new_env.insert_variable(param.0.clone(), arg_value);
-                }
+// Variables shared through a cell are updated in place, so the change is seen
+// by every scope holding the cell
+fn assign_variable(name: Name, value: EnvValue, env: &mut Environment<EnvValue>) {
+    match env.search_binding(&name).cloned() {
+        Some(EnvValue::Cell(cell)) => *cell.borrow_mut() = Some(value),
+        _ => env.insert_variable(name, value),
+    }
+}
+
+// The cell of a local variable exists from the start of the call, but the
+// variable is bound only once it is assigned
+fn is_assigned(name: &Name, env: &Environment<EnvValue>) -> bool {
+    match env.search_binding(name) {
+        Some(EnvValue::Cell(cell)) => cell.borrow().is_some(),
+        value => value.is_some(),
+    }
+}
+
+// A declaration starts a fresh, unassigned binding, in the variable's cell if it
+// has one, so that the functions sharing the cell see the new value
+fn declare_variable(
+    name: Name,
+    mutable: bool,
+    kind: Option<Type>,
+    mut env: Environment<EnvValue>,
+) -> Result<ControlFlow, ErrorMessage> {
+    let cell = match env.search_frame(name.clone()) {
+        Some(EnvValue::Cell(cell)) => Some(cell.clone()),
+        _ => None,
+    };
+
+    env.declare_variable(name.clone(), mutable, kind);
+    if let Some(cell) = cell {
+        cell.replace(None);
+        env.insert_variable(name, EnvValue::Cell(cell));
+    }
+    Ok(ControlFlow::Continue(env))
+}
+
+// Make assignments to each name write to the binding found by 'global' or 'nonlocal'
+fn declare_outer(
+    names: Vec<Name>,
+    global: bool,
+    mut env: Environment<EnvValue>,
+) -> Result<ControlFlow, ErrorMessage> {
+    if env.recursion == 0 && global {
+        return Ok(ControlFlow::Continue(env));
+    }
+
+    for name in names {
+        match env.search_outer_frame(&name, global) {
+            Some(key) => env.declare_outer(name, key),
+            None => {
+                let kind = if global { "global" } else { "nonlocal" };
+                return Err((format!("no binding for {} '{}' found.", kind, name), None));
+            }
+        }
+    }
+    Ok(ControlFlow::Continue(env))
+}
+
+// Environments are copied into function calls, so before a function is defined
+// the variables it declares 'global' or 'nonlocal' are moved into shared cells
+fn share_outer_variables(stmt: &Statement, env: &mut Environment<EnvValue>) {
+    match stmt {
+        Statement::Global(names) | Statement::Nonlocal(names) => {
+            let global = matches!(stmt, Statement::Global(_));
+
+            for name in names {
+                let key = match env.search_outer_frame(name, global) {
+                    Some(key) => key,
+                    // The module may assign the global after the function is defined
+                    None if global => ("__main__".to_string(), 0),
+                    None => continue,
+                };
+                let frame = env.stack.get_mut(&key).unwrap();
+                let cell = match frame.variables.remove(name) {
+                    Some(EnvValue::Cell(cell)) => cell,
+                    value => Rc::new(RefCell::new(value)),
+                };
+                frame.variables.insert(name.clone(), EnvValue::Cell(cell));
+            }
+        }
+        Statement::Sequence(s1, s2) => {
+            share_outer_variables(s1, env);
+            share_outer_variables(s2, env);
+        }
+        Statement::Block(stmts) => stmts.iter().for_each(|s| share_outer_variables(s, env)),
+        Statement::Located(_, stmt) => share_outer_variables(stmt, env),
+        Statement::IfThenElse(_, then_stmt, else_stmt) => {
+            share_outer_variables(then_stmt, env);
+            if let Some(else_stmt) = else_stmt {
+                share_outer_variables(else_stmt, env);
+            }
+        }
+        Statement::While(_, body) => share_outer_variables(body, env),
+        Statement::Match(_, arms) => arms.iter().for_each(|(_, s)| share_outer_variables(s, env)),
+        // Globals of nested functions must be shared from the module's own frame
+        Statement::FuncDef(func) => {
+            if let Some(body) = &func.body {
+                share_outer_variables(body, env);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Put the parameters and local variables of a call in cells before its body
+// runs. Functions defined in the body copy the scope, but share the cells, so
+// they see the variables of the call as they are when they run.
+fn share_local_variables(body: &Statement, env: &mut Environment<EnvValue>) {
+    let mut locals = HashSet::new();
+    let mut outer = HashSet::new();
+    local_names(body, &mut locals, &mut outer);
+
+    let key = env.scope_key();
+    let frame = env.stack.get_mut(&key).unwrap();
+    for name in locals.difference(&outer) {
+        if !frame.variables.contains_key(name) {
+            frame
+                .variables
+                .insert(name.clone(), EnvValue::Cell(Rc::new(RefCell::new(None))));
+        }
+    }
+    for value in frame.variables.values_mut() {
+        if !matches!(value, EnvValue::Cell(_)) {
+            *value = EnvValue::Cell(Rc::new(RefCell::new(Some(value.clone()))));
+        }
+    }
+}
+
+// Collect the names bound in a function body, leaving out those of its nested
+// functions, and the names it declares 'global' or 'nonlocal'
+fn local_names(stmt: &Statement, locals: &mut HashSet<Name>, outer: &mut HashSet<Name>) {
+    match stmt {
+        Statement::Assignment(name, _, _)
+        | Statement::VarDeclaration(name, _)
+        | Statement::ValDeclaration(name, _) => {
+            locals.insert(name.clone());
+        }
+        Statement::FuncDef(func) => {
+            locals.insert(func.name.clone());
+        }
+        Statement::Global(names) | Statement::Nonlocal(names) => {
+            outer.extend(names.iter().cloned())
+        }
+        Statement::Sequence(s1, s2) => {
+            local_names(s1, locals, outer);
+            local_names(s2, locals, outer);
+        }
+        Statement::Block(stmts) => stmts.iter().for_each(|s| local_names(s, locals, outer)),
+        Statement::Located(_, stmt) => local_names(stmt, locals, outer),
+        Statement::IfThenElse(_, then_stmt, else_stmt) => {
+            local_names(then_stmt, locals, outer);
+            if let Some(else_stmt) = else_stmt {
+                local_names(else_stmt, locals, outer);
+            }
+        }
+        Statement::While(_, body) => local_names(body, locals, outer),
+        Statement::Match(_, arms) => {
+            for (pattern, arm) in arms {
+                pattern_names(pattern, locals);
+                local_names(arm, locals, outer);
             }
+        }
+        _ => {}
+    }
+}
+
+fn pattern_names(pattern: &Pattern, names: &mut HashSet<Name>) {
+    match pattern {
+        Pattern::Wildcard => {}
+        Pattern::Binding(name) => {
+            names.insert(name.clone());
+        }
+        Pattern::Constructor(_, args) => args.iter().for_each(|arg| pattern_names(arg, names)),
+    }
+}
+
+// Top-level functions see the globals at the time they are called. Functions
+// defined inside another function capture the scope they are defined in, and
+// share its variables through their cells. Defaults are evaluated only once,
+// in the scope the function is defined in.
+fn function_value(func: Function, env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    let mut defaults = vec![];
+    for (param, exp) in &func.defaults {
+        defaults.push((param.clone(), eval(exp.clone(), env)?));
+    }
+
+    if env.recursion == 0 {
+        Ok(EnvValue::Func(func, defaults))
+    } else {
+        Ok(EnvValue::Closure(func, defaults, env.clone()))
+    }
+}
 
-            // Execute function
-            match execute(*func.body.as_ref().unwrap().clone(), &new_env)? {
-                ControlFlow::Return(value) => Ok(value),
-                ControlFlow::Continue(_) => {
-                    Err(("Function did not return a value".to_string(), None))
+// Evaluate the arguments of a call in the caller's scope, keeping their names
+fn eval_arguments(
+    args: Vec<Expression>,
+    env: &Environment<EnvValue>,
+) -> Result<Arguments<EnvValue>, ErrorMessage> {
+    let mut values = vec![];
+
+    for arg in args {
+        match arg {
+            Expression::KeywordArg(name, exp) => values.push((Some(name), eval(*exp, env)?)),
+            // An unpacked list gives one positional argument per element
+            Expression::Unpack(exp) => match eval(*exp, env)? {
+                EnvValue::List(elements) => values.extend(positional(elements)),
+                value => {
+                    return Err((format!("cannot unpack '{}', expected a list.", value), None))
                 }
+            },
+            exp => values.push((None, eval(exp, env)?)),
+        }
+    }
+    Ok(values)
+}
+
+// Run a function body on already evaluated arguments
+fn apply_function(
+    callee: EnvValue,
+    args: Arguments<EnvValue>,
+    env: &Environment<EnvValue>,
+) -> Result<EnvValue, ErrorMessage> {
+    let (func, defaults, mut new_env) = match callee {
+        EnvValue::Func(func, defaults) => (func, defaults, global_environment(env)),
+        EnvValue::Closure(func, defaults, captured) => {
+            (func, defaults, closure_environment(captured, env))
+        }
+        value => return Err((format!("'{}' is not a function.", value), None)),
+    };
+    let (bound, extra) = func.bind_arguments(args).map_err(|e| (e, None))?;
+
+    let mut values = vec![];
+    for (param, value) in bound {
+        let value = match value {
+            Some(value) => value,
+            None => {
+                let default = defaults.iter().find(|(name, _)| *name == param);
+                default.unwrap().1.clone()
+            }
+        };
+        values.push((param, value));
+    }
+    if func.variadic {
+        let (name, _) = func.params.as_ref().unwrap().last().unwrap();
+        values.push((name.clone(), EnvValue::List(extra)));
+    }
+
+    // Bind arguments
+    new_env.insert_frame(func.clone());
+    for (param, value) in values {
+        new_env.insert_variable(param, value);
+    }
+    share_local_variables(func.body.as_ref().unwrap(), &mut new_env);
+
+    // Execute function
+    match execute(*func.body.as_ref().unwrap().clone(), &new_env)? {
+        ControlFlow::Return(value) => Ok(value),
+        ControlFlow::Continue(_) if func.returns_void() => Ok(EnvValue::Exp(Expression::CVoid)),
+        ControlFlow::Continue(_) => Err(("Function did not return a value".to_string(), None)),
+    }
+}
+
+// The caller's global variables and type definitions
+fn global_environment(env: &Environment<EnvValue>) -> Environment<EnvValue> {
+    let mut new_env = Environment::new();
+    let globals = env.get_frame(("__main__".to_string(), 0));
+
+    for (name, value) in &globals.variables {
+        new_env.insert_variable(name.clone(), value.clone());
+    }
+    for (name, def) in &globals.types {
+        new_env.insert_type(name.clone(), def.clone());
+    }
+    new_env
+}
+
+// The scope a closure captured, with the caller's module frame, so that it
+// reads the globals as they are when it runs
+fn closure_environment(
+    mut captured: Environment<EnvValue>,
+    env: &Environment<EnvValue>,
+) -> Environment<EnvValue> {
+    let key = ("__main__".to_string(), 0);
+    captured
+        .stack
+        .insert(key.clone(), env.get_frame(key).clone());
+    captured
+}
+
+// Build a record from a constructor call, e.g. 'Point(1.0, 2.0)'
+fn construct_record(
+    name: Name,
+    fields: Vec<Name>,
+    args: Vec<Expression>,
+    env: &Environment<EnvValue>,
+) -> Result<EnvValue, ErrorMessage> {
+    let values = eval_constructor_args(&name, fields.len(), args, env)?;
+    Ok(EnvValue::Record(
+        name,
+        fields.into_iter().zip(values).collect(),
+    ))
+}
+
+fn eval_constructor_args(
+    name: &Name,
+    arity: usize,
+    args: Vec<Expression>,
+    env: &Environment<EnvValue>,
+) -> Result<Vec<EnvValue>, ErrorMessage> {
+    if args.len() != arity {
+        return Err((
+            format!(
+                "'{}()' expected {} arguments, found {}.",
+                name,
+                arity,
+                args.len()
+            ),
+            None,
+        ));
+    }
+
+    args.into_iter().map(|arg| eval(arg, env)).collect()
+}
+
+// Nullary enum constructors, e.g. 'Empty', are values on their own
+fn eval_var(name: Name, env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
+    match env.search_constructor(&name) {
+        Some((enum_name, payload)) if payload.is_empty() => {
+            Ok(EnvValue::Variant(enum_name, name, vec![]))
+        }
+        _ => lookup(name, env),
+    }
+}
+
+// Maybe and Result are built-in enums: 'Just(T) | Nothing' and 'Ok(T) | Err(E)'
+fn builtin_variant(constructor: &str, payload: Vec<EnvValue>) -> EnvValue {
+    let enum_name = match constructor {
+        "Just" | "Nothing" => "Maybe",
+        _ => "Result",
+    };
+    EnvValue::Variant(enum_name.to_string(), constructor.to_string(), payload)
+}
+
+// Check whether a value matches a pattern, binding the pattern's variables
+fn bind_pattern(pattern: &Pattern, value: &EnvValue, env: &mut Environment<EnvValue>) -> bool {
+    match pattern {
+        Pattern::Wildcard => true,
+        Pattern::Binding(name) => {
+            assign_variable(name.clone(), value.clone(), env);
+            true
+        }
+        Pattern::Constructor(name, args) => match value {
+            EnvValue::Variant(_, constructor, payload)
+                if constructor == name && payload.len() == args.len() =>
+            {
+                args.iter()
+                    .zip(payload.iter())
+                    .all(|(arg, value)| bind_pattern(arg, value, env))
             }
+            _ => false,
+        },
+    }
+}
+
+fn eval_field_access(
+    record: Expression,
+    field: Name,
+    env: &Environment<EnvValue>,
+) -> Result<EnvValue, ErrorMessage> {
+    match eval(record, env)? {
+        EnvValue::Record(name, fields) => match fields.into_iter().find(|(f, _)| *f == field) {
+            Some((_, value)) => Ok(value),
+            None => Err((format!("'{}' has no field '{}'.", name, field), None)),
+        },
+        _ => Err((
+            String::from("field access is only defined for structs."),
+            None,
+        )),
+    }
+}
+
+// Update a field and write the updated record back to where it was read from
+fn assign_field(
+    record: Expression,
+    field: Name,
+    value: EnvValue,
+    env: &mut Environment<EnvValue>,
+) -> Result<(), ErrorMessage> {
+    let (name, mut fields) = match eval(record.clone(), env)? {
+        EnvValue::Record(name, fields) => (name, fields),
+        _ => {
+            return Err((
+                String::from("field assignment is only defined for structs."),
+                None,
+            ))
         }
-        _ => Err((format!("Function {} not found", name), None)),
+    };
+
+    match fields.iter_mut().find(|(f, _)| *f == field) {
+        Some(slot) => slot.1 = value,
+        None => return Err((format!("'{}' has no field '{}'.", name, field), None)),
+    }
+
+    let updated = EnvValue::Record(name, fields);
+    match record {
+        Expression::Var(var) => {
+            assign_variable(var, updated, env);
+            Ok(())
+        }
+        Expression::FieldAccess(outer, outer_field) => {
+            assign_field(*outer, outer_field, updated, env)
+        }
+        _ => Err((
+            String::from("can only assign to fields of variables."),
+            None,
+        )),
     }
 }
 
@@ -280,48 +835,29 @@ fn call(
     -> extract_error_value
     -> propagate_error
 */
-fn extract_error_value(
-    exp: Expression,
-    env: &Environment<EnvValue>,
-) -> Result<String, ErrorMessage> {
-    // Gets expression and returns the value inside (works with constants and Error types)
-    match exp {
-        Expression::COk(e) => extract_error_value(*e, env),
-        Expression::CErr(e) => extract_error_value(*e, env),
-        Expression::CJust(e) => extract_error_value(*e, env),
-        Expression::CTrue => Ok("True".to_string()),
-        Expression::CFalse => Ok("False".to_string()),
-        Expression::CInt(value) => Ok(value.to_string()),
-        Expression::CReal(value) => Ok(value.to_string()),
-        Expression::CString(value) => Ok(value.to_string()),
-        Expression::CNothing => Ok("Nothing".to_string()),
-        _ => Err((String::from("Nothing to extract from."), None)),
+fn extract_error_value(value: &EnvValue) -> String {
+    // Gets the text of an error value, with strings unquoted
+    match value {
+        EnvValue::Exp(Expression::CString(value)) => value.to_string(),
+        value => value.to_string(),
     }
 }
 
 fn propagate_error(
-    exp: Expression,
+    value: EnvValue,
     env: &Environment<EnvValue>,
 ) -> Result<ControlFlow, ErrorMessage> {
     // Checks error value and propagates it (terminates code if on highest level function)
     if env.scope_key().1 == 0 {
-        match eval(exp, &env) {
-            Ok(EnvValue::Exp(new_value)) => match extract_error_value(new_value, &env) {
-                Ok(s) => Err((
-                    String::from(format!("Program terminated with errors: {}", s)),
-                    None,
-                )),
-                _ => Err(("Program terminated with errors".to_string(), None)),
-            },
-            _ => Err((
-                "Program panicked and trying to terminate with errors".to_string(),
-                None,
-            )),
-        }
+        Err((
+            format!(
+                "Program terminated with errors: {}",
+                extract_error_value(&value)
+            ),
+            None,
+        ))
     } else {
-        return Ok(ControlFlow::Return(EnvValue::Exp(Expression::CErr(
-            Box::new(exp),
-        ))));
+        Ok(ControlFlow::Return(builtin_variant("Err", vec![value])))
     }
 }
 
@@ -424,21 +960,18 @@ fn is_constant(exp: Expression) -> bool {
         Expression::CInt(_) => true,
         Expression::CReal(_) => true,
         Expression::CString(_) => true,
-        Expression::CNothing => true,
         _ => false,
     }
 }
 
 fn lookup(name: String, env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
-    let mut curr_scope = env.scope_key();
-
-    loop {
-        let frame = env.get_frame(curr_scope.clone());
-
-        match frame.variables.get(&name) {
-            Some(value) => return Ok(value.clone()),
-            None => curr_scope = frame.parent_key.clone().unwrap(),
-        }
+    match env.search_variable(&name) {
+        Some(EnvValue::Cell(cell)) => match &*cell.borrow() {
+            Some(value) => Ok(value.clone()),
+            None => Err((format!("'{}' is not defined.", name), None)),
+        },
+        Some(value) => Ok(value.clone()),
+        None => Err((format!("'{}' is not defined.", name), None)),
     }
 }
 
@@ -645,19 +1178,47 @@ fn eq(
     rhs: Expression,
     env: &Environment<EnvValue>,
 ) -> Result<EnvValue, ErrorMessage> {
-    eval_binary_rel_op(
-        lhs,
-        rhs,
-        env,
-        |a, b| {
-            if a == b {
-                Expression::CTrue
-            } else {
-                Expression::CFalse
-            }
-        },
-        "(==) is only defined for numbers (integers and real).",
-    )
+    let v1 = eval(lhs, env)?;
+    let v2 = eval(rhs, env)?;
+
+    match values_equal(&v1, &v2) {
+        Some(true) => Ok(EnvValue::Exp(Expression::CTrue)),
+        Some(false) => Ok(EnvValue::Exp(Expression::CFalse)),
+        None => Err((String::from("(==) is not defined for functions."), None)),
+    }
+}
+
+// Numbers are equal by value, whether integer or real, and other values are
+// equal when they have the same structure. Functions cannot be compared.
+fn values_equal(v1: &EnvValue, v2: &EnvValue) -> Option<bool> {
+    match (v1, v2) {
+        (EnvValue::Exp(Expression::CInt(a)), EnvValue::Exp(Expression::CReal(b)))
+        | (EnvValue::Exp(Expression::CReal(b)), EnvValue::Exp(Expression::CInt(a))) => {
+            Some(*a as f64 == *b)
+        }
+        (EnvValue::Exp(a), EnvValue::Exp(b)) => Some(a == b),
+        (EnvValue::Record(name1, fields1), EnvValue::Record(name2, fields2)) => {
+            let (names1, values1): (Vec<_>, Vec<_>) = fields1.iter().cloned().unzip();
+            let (names2, values2): (Vec<_>, Vec<_>) = fields2.iter().cloned().unzip();
+            Some(name1 == name2 && names1 == names2 && all_equal(&values1, &values2)?)
+        }
+        (
+            EnvValue::Variant(enum1, constructor1, payload1),
+            EnvValue::Variant(enum2, constructor2, payload2),
+        ) => Some(enum1 == enum2 && constructor1 == constructor2 && all_equal(payload1, payload2)?),
+        (EnvValue::List(values1), EnvValue::List(values2)) => all_equal(values1, values2),
+        (EnvValue::Func(..) | EnvValue::Closure(..), _)
+        | (_, EnvValue::Func(..) | EnvValue::Closure(..)) => None,
+        _ => Some(false),
+    }
+}
+
+fn all_equal(values1: &[EnvValue], values2: &[EnvValue]) -> Option<bool> {
+    let mut equal = values1.len() == values2.len();
+    for (v1, v2) in values1.iter().zip(values2) {
+        equal &= values_equal(v1, v2)?;
+    }
+    Some(equal)
 }
 
 fn gt(
@@ -747,8 +1308,11 @@ fn eval_unwrap_expression(
     ////QUATRO/ FAz uteste também
     let v = eval(exp, env)?;
     match v {
-        EnvValue::Exp(Expression::CJust(e)) => Ok(EnvValue::Exp(*e)),
-        EnvValue::Exp(Expression::COk(e)) => Ok(EnvValue::Exp(*e)),
+        EnvValue::Variant(_, constructor, mut payload)
+            if constructor == "Just" || constructor == "Ok" =>
+        {
+            Ok(payload.remove(0))
+        }
         _ => Err((String::from("Program panicked trying to unwrap."), None)),
     }
 }
@@ -761,12 +1325,19 @@ fn eval_propagate_expression(
     let v = eval(exp, env)?;
     //let mut *new_env = env.clone();
     match v {
-        EnvValue::Exp(Expression::CJust(e)) => Ok(EnvValue::Exp(*e)),
-        EnvValue::Exp(Expression::COk(e)) => Ok(EnvValue::Exp(*e)),
-        EnvValue::Exp(Expression::CErr(e)) => Err(("Propagate".to_string(), Some(*e))),
-        EnvValue::Exp(Expression::CNothing) => Err((
+        EnvValue::Variant(_, constructor, mut payload)
+            if constructor == "Just" || constructor == "Ok" =>
+        {
+            Ok(payload.remove(0))
+        }
+        EnvValue::Variant(_, constructor, mut payload) if constructor == "Err" => {
+            Err(("Propagate".to_string(), Some(Box::new(payload.remove(0)))))
+        }
+        EnvValue::Variant(_, constructor, _) if constructor == "Nothing" => Err((
             "Propagate".to_string(),
-            Some(Expression::CString("Couldn't unwrap Nothing".to_string())),
+            Some(Box::new(EnvValue::Exp(Expression::CString(
+                "Couldn't unwrap Nothing".to_string(),
+            )))),
         )),
         _ => Err((String::from("'propagate' is expects a Just or Ok."), None)),
     }
@@ -778,7 +1349,9 @@ fn eval_isnothing_expression(
 ) -> Result<EnvValue, ErrorMessage> {
     let v = eval(exp, env)?;
     match v {
-        EnvValue::Exp(Expression::CNothing) => Ok(EnvValue::Exp(Expression::CTrue)),
+        EnvValue::Variant(_, constructor, _) if constructor == "Nothing" => {
+            Ok(EnvValue::Exp(Expression::CTrue))
+        }
         _ => Ok(EnvValue::Exp(Expression::CFalse)),
         //EnvValue::Exp(Expression::CJust(_)) => Ok(EnvValue::Exp(Expression::CFalse)),
         //_ => Err("Expression not recognized.".to_string()),
@@ -791,7 +1364,9 @@ fn eval_iserror_expression(
 ) -> Result<EnvValue, ErrorMessage> {
     let v = eval(exp, env)?;
     match v {
-        EnvValue::Exp(Expression::CErr(_)) => Ok(EnvValue::Exp(Expression::CTrue)),
+        EnvValue::Variant(_, constructor, _) if constructor == "Err" => {
+            Ok(EnvValue::Exp(Expression::CTrue))
+        }
         _ => Ok(EnvValue::Exp(Expression::CFalse)),
         //EnvValue::Exp(Expression::COk(_)) => Ok(EnvValue::Exp(Expression::CFalse)),
         //_ => Err(String::from("'is_error' is only defined for Ok and Err.")),
@@ -799,26 +1374,57 @@ fn eval_iserror_expression(
 }
 
 fn eval_just(exp: Expression, env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
-    let v = eval(exp, env)?;
-    match v {
-        EnvValue::Exp(e) => Ok(EnvValue::Exp(Expression::CJust(Box::new(e)))),
-        _ => Err(("Expression not recognized.".to_string(), None)),
-    }
+    Ok(builtin_variant("Just", vec![eval(exp, env)?]))
 }
 
 fn eval_ok(exp: Expression, env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
-    let v = eval(exp, env)?;
-    match v {
-        EnvValue::Exp(e) => Ok(EnvValue::Exp(Expression::COk(Box::new(e)))),
-        _ => Err(("Expression not recognized.".to_string(), None)),
-    }
+    Ok(builtin_variant("Ok", vec![eval(exp, env)?]))
 }
 
 fn eval_err(exp: Expression, env: &Environment<EnvValue>) -> Result<EnvValue, ErrorMessage> {
-    let v = eval(exp, env)?;
-    match v {
-        EnvValue::Exp(e) => Ok(EnvValue::Exp(Expression::CErr(Box::new(e)))),
-        _ => Err(("Expression not recognized.".to_string(), None)),
+    Ok(builtin_variant("Err", vec![eval(exp, env)?]))
+}
+
+fn positional(values: Vec<EnvValue>) -> Arguments<EnvValue> {
+    values.into_iter().map(|value| (None, value)).collect()
+}
+
+fn eval_combinator(
+    combinator: &str,
+    exp: Expression,
+    arg: Expression,
+    env: &Environment<EnvValue>,
+) -> Result<EnvValue, ErrorMessage> {
+    let value = eval(exp, env)?;
+    let (constructor, mut payload) = match &value {
+        EnvValue::Variant(_, constructor, payload) => (constructor.clone(), payload.clone()),
+        _ => {
+            return Err((
+                format!(
+                    "'{}' is only defined for maybe and result values.",
+                    combinator
+                ),
+                None,
+            ))
+        }
+    };
+    match (combinator, constructor.as_str()) {
+        ("map", "Just" | "Ok") => {
+            let result = apply_function(eval(arg, env)?, positional(payload), env)?;
+            Ok(builtin_variant(&constructor, vec![result]))
+        }
+        ("and_then", "Just" | "Ok") => apply_function(eval(arg, env)?, positional(payload), env),
+        ("or_else", "Nothing" | "Err") => apply_function(eval(arg, env)?, positional(payload), env),
+        ("unwrap_or", "Just" | "Ok") => Ok(payload.remove(0)),
+        ("unwrap_or", _) => eval(arg, env),
+        ("ok_or", "Just") => Ok(builtin_variant("Ok", payload)),
+        ("ok_or", "Nothing") => Ok(builtin_variant("Err", vec![eval(arg, env)?])),
+        ("ok_or", _) => Err((
+            "'ok_or' is only defined for maybe values.".to_string(),
+            None,
+        )),
+        // The remaining cases leave the value unchanged
+        _ => Ok(value),
     }
 }
 
@@ -827,10 +1433,12 @@ mod tests {
     use super::*;
     use crate::ir::ast::Expression::*;
     use crate::ir::ast::Function;
+    use crate::ir::ast::Location;
     use crate::ir::ast::Statement::*;
     use std::collections::HashMap;
     //use crate::ir::ast::Type;
     use crate::ir::ast::Type::*;
+    use crate::tc::type_checker::check_exp;
     use approx::relative_eq;
 
     #[test]
@@ -1545,8 +2153,11 @@ mod tests {
 
         let func = FuncDef(Function {
             name: "fibonacci".to_string(),
+            type_params: vec![],
             kind: Some(TInteger),
             params: Some(vec![("n".to_string(), TInteger)]),
+            variadic: false,
+            defaults: vec![],
             body: Some(Box::new(Sequence(
                 Box::new(IfThenElse(
                     Box::new(LT(Box::new(Var("n".to_string())), Box::new(CInt(1)))),
@@ -1619,11 +2230,14 @@ mod tests {
 
         let func_soma1 = FuncDef(Function {
             name: "soma1".to_string(),
+            type_params: vec![],
             kind: Some(TInteger),
             params: Some(vec![
                 ("a".to_string(), TInteger),
                 ("b".to_string(), TInteger),
             ]),
+            variadic: false,
+            defaults: vec![],
             body: Some(Box::new(Return(Box::new(Add(
                 Box::new(Var("a".to_string())),
                 Box::new(Var("b".to_string())),
@@ -1632,12 +2246,15 @@ mod tests {
 
         let func_soma_mut = FuncDef(Function {
             name: "soma_mut".to_string(),
+            type_params: vec![],
             kind: Some(TInteger),
             params: Some(vec![
                 ("a".to_string(), TInteger),
                 ("b".to_string(), TInteger),
                 ("m".to_string(), TInteger),
             ]),
+            variadic: false,
+            defaults: vec![],
             body: Some(Box::new(Return(Box::new(Mul(
                 Box::new(Add(
                     Box::new(Var("a".to_string())),
@@ -1658,8 +2275,11 @@ mod tests {
 
         let body_mod_test = Box::new(TestDef(Function {
             name: "test".to_string(),
+            type_params: vec![],
             kind: Some(TVoid),
             params: None,
+            variadic: false,
+            defaults: vec![],
             body: Some(body_test.clone()),
         }));
 
@@ -1674,8 +2294,11 @@ mod tests {
             "test".to_string(),
             Function {
                 name: "test".to_string(),
+                type_params: vec![],
                 kind: Some(TVoid),
                 params: None,
+                variadic: false,
+                defaults: vec![],
                 body: Some(Box::new(Sequence(
                     body_test,
                     Box::new(Return(Box::new(CVoid))),
@@ -1731,11 +2354,14 @@ mod tests {
 
         let func_soma1 = FuncDef(Function {
             name: "soma1".to_string(),
+            type_params: vec![],
             kind: Some(TInteger),
             params: Some(vec![
                 ("a".to_string(), TInteger),
                 ("b".to_string(), TInteger),
             ]),
+            variadic: false,
+            defaults: vec![],
             body: Some(Box::new(Return(Box::new(Add(
                 Box::new(Var("a".to_string())),
                 Box::new(Var("b".to_string())),
@@ -1744,12 +2370,15 @@ mod tests {
 
         let func_soma_mut = FuncDef(Function {
             name: "soma_mut".to_string(),
+            type_params: vec![],
             kind: Some(TInteger),
             params: Some(vec![
                 ("a".to_string(), TInteger),
                 ("b".to_string(), TInteger),
                 ("m".to_string(), TInteger),
             ]),
+            variadic: false,
+            defaults: vec![],
             body: Some(Box::new(Return(Box::new(Mul(
                 Box::new(Add(
                     Box::new(Var("a".to_string())),
@@ -1761,11 +2390,14 @@ mod tests {
 
         let func_sub = FuncDef(Function {
             name: "sub".to_string(),
+            type_params: vec![],
             kind: Some(TInteger),
             params: Some(vec![
                 ("a".to_string(), TInteger),
                 ("b".to_string(), TInteger),
             ]),
+            variadic: false,
+            defaults: vec![],
             body: Some(Box::new(Return(Box::new(Sub(
                 Box::new(Var("a".to_string())),
                 Box::new(Var("b".to_string())),
@@ -1774,12 +2406,15 @@ mod tests {
 
         let func_sub_mut = FuncDef(Function {
             name: "sub_mut".to_string(),
+            type_params: vec![],
             kind: Some(TInteger),
             params: Some(vec![
                 ("a".to_string(), TInteger),
                 ("b".to_string(), TInteger),
                 ("m".to_string(), TInteger),
             ]),
+            variadic: false,
+            defaults: vec![],
             body: Some(Box::new(Return(Box::new(Mul(
                 Box::new(Sub(
                     Box::new(Var("a".to_string())),
@@ -1809,8 +2444,11 @@ mod tests {
 
         let mut body_mod_test = Box::new(TestDef(Function {
             name: "teste".to_string(),
+            type_params: vec![],
             kind: Some(TVoid),
             params: None,
+            variadic: false,
+            defaults: vec![],
             body: Some(body_test.clone()),
         }));
 
@@ -1818,8 +2456,11 @@ mod tests {
             body_mod_test.clone(),
             Box::new(TestDef(Function {
                 name: "teste_1".to_string(),
+                type_params: vec![],
                 kind: Some(TVoid),
                 params: None,
+                variadic: false,
+                defaults: vec![],
                 body: Some(body_test_1.clone()),
             })),
         ));
@@ -1885,11 +2526,14 @@ mod tests {
 
         let func_soma1 = FuncDef(Function {
             name: "soma1".to_string(),
+            type_params: vec![],
             kind: Some(TInteger),
             params: Some(vec![
                 ("a".to_string(), TInteger),
                 ("b".to_string(), TInteger),
             ]),
+            variadic: false,
+            defaults: vec![],
             body: Some(Box::new(Return(Box::new(Add(
                 Box::new(Var("a".to_string())),
                 Box::new(Var("b".to_string())),
@@ -1898,12 +2542,15 @@ mod tests {
 
         let func_soma_mut = FuncDef(Function {
             name: "soma_mut".to_string(),
+            type_params: vec![],
             kind: Some(TInteger),
             params: Some(vec![
                 ("a".to_string(), TInteger),
                 ("b".to_string(), TInteger),
                 ("m".to_string(), TInteger),
             ]),
+            variadic: false,
+            defaults: vec![],
             body: Some(Box::new(Return(Box::new(Mul(
                 Box::new(Add(
                     Box::new(Var("a".to_string())),
@@ -1915,11 +2562,14 @@ mod tests {
 
         let func_sub = FuncDef(Function {
             name: "sub".to_string(),
+            type_params: vec![],
             kind: Some(TInteger),
             params: Some(vec![
                 ("a".to_string(), TInteger),
                 ("b".to_string(), TInteger),
             ]),
+            variadic: false,
+            defaults: vec![],
             body: Some(Box::new(Return(Box::new(Sub(
                 Box::new(Var("a".to_string())),
                 Box::new(Var("b".to_string())),
@@ -1928,12 +2578,15 @@ mod tests {
 
         let func_sub_mut = FuncDef(Function {
             name: "sub_mut".to_string(),
+            type_params: vec![],
             kind: Some(TInteger),
             params: Some(vec![
                 ("a".to_string(), TInteger),
                 ("b".to_string(), TInteger),
                 ("m".to_string(), TInteger),
             ]),
+            variadic: false,
+            defaults: vec![],
             body: Some(Box::new(Return(Box::new(Mul(
                 Box::new(Sub(
                     Box::new(Var("a".to_string())),
@@ -1964,14 +2617,20 @@ mod tests {
         let body_mod_test = Box::new(Sequence(
             Box::new(TestDef(Function {
                 name: "teste".to_string(),
+                type_params: vec![],
                 kind: Some(TVoid),
                 params: None,
+                variadic: false,
+                defaults: vec![],
                 body: Some(body_test.clone()),
             })),
             Box::new(TestDef(Function {
                 name: "teste_1".to_string(),
+                type_params: vec![],
                 kind: Some(TVoid),
                 params: None,
+                variadic: false,
+                defaults: vec![],
                 body: Some(body_test_1.clone()),
             })),
         ));
@@ -2035,11 +2694,14 @@ mod tests {
 
         let func_soma1 = FuncDef(Function {
             name: "soma1".to_string(),
+            type_params: vec![],
             kind: Some(TInteger),
             params: Some(vec![
                 ("a".to_string(), TInteger),
                 ("b".to_string(), TInteger),
             ]),
+            variadic: false,
+            defaults: vec![],
             body: Some(Box::new(Return(Box::new(Add(
                 Box::new(Var("a".to_string())),
                 Box::new(Var("b".to_string())),
@@ -2048,12 +2710,15 @@ mod tests {
 
         let func_soma_mut = FuncDef(Function {
             name: "soma_mut".to_string(),
+            type_params: vec![],
             kind: Some(TInteger),
             params: Some(vec![
                 ("a".to_string(), TInteger),
                 ("b".to_string(), TInteger),
                 ("m".to_string(), TInteger),
             ]),
+            variadic: false,
+            defaults: vec![],
             body: Some(Box::new(Return(Box::new(Mul(
                 Box::new(Add(
                     Box::new(Var("a".to_string())),
@@ -2065,11 +2730,14 @@ mod tests {
 
         let func_sub = FuncDef(Function {
             name: "sub".to_string(),
+            type_params: vec![],
             kind: Some(TInteger),
             params: Some(vec![
                 ("a".to_string(), TInteger),
                 ("b".to_string(), TInteger),
             ]),
+            variadic: false,
+            defaults: vec![],
             body: Some(Box::new(Return(Box::new(Sub(
                 Box::new(Var("a".to_string())),
                 Box::new(Var("b".to_string())),
@@ -2078,12 +2746,15 @@ mod tests {
 
         let func_sub_mut = FuncDef(Function {
             name: "sub_mut".to_string(),
+            type_params: vec![],
             kind: Some(TInteger),
             params: Some(vec![
                 ("a".to_string(), TInteger),
                 ("b".to_string(), TInteger),
                 ("m".to_string(), TInteger),
             ]),
+            variadic: false,
+            defaults: vec![],
             body: Some(Box::new(Return(Box::new(Mul(
                 Box::new(Sub(
                     Box::new(Var("a".to_string())),
@@ -2114,14 +2785,20 @@ mod tests {
         let body_mod_test = Box::new(Sequence(
             Box::new(TestDef(Function {
                 name: "teste".to_string(),
+                type_params: vec![],
                 kind: Some(TVoid),
                 params: None,
+                variadic: false,
+                defaults: vec![],
                 body: Some(body_test.clone()),
             })),
             Box::new(TestDef(Function {
                 name: "teste_1".to_string(),
+                type_params: vec![],
                 kind: Some(TVoid),
                 params: None,
+                variadic: false,
+                defaults: vec![],
                 body: Some(body_test_1.clone()),
             })),
         ));
@@ -2159,4 +2836,1374 @@ mod tests {
             Err(s) => assert!(false, "{}", s),
         }
     }
+
+    #[test]
+    fn execute_declaration() {
+        let env: Environment<EnvValue> = Environment::new();
+
+        let declaration = Sequence(
+            Box::new(VarDeclaration(String::from("x"), None)),
+            Box::new(Assignment(
+                String::from("x"),
+                Box::new(CInt(42)),
+                Some(TInteger),
+            )),
+        );
+
+        match run(declaration, &env) {
+            Ok(ControlFlow::Continue(new_env)) => assert_eq!(
+                new_env.search_frame("x".to_string()),
+                Some(&EnvValue::Exp(CInt(42)))
+            ),
+            Ok(ControlFlow::Return(_)) => assert!(false),
+            Err(s) => assert!(false, "{:?}", s),
+        }
+    }
+
+    #[test]
+    fn execute_val_reassignment() {
+        let env: Environment<EnvValue> = Environment::new();
+
+        let program = Block(vec![
+            ValDeclaration(String::from("x"), None),
+            Assignment(String::from("x"), Box::new(CInt(1)), Some(TInteger)),
+            Assignment(String::from("x"), Box::new(CInt(2)), Some(TInteger)),
+        ]);
+
+        match run(program, &env) {
+            Ok(_) => assert!(false, "'val' bindings cannot be reassigned"),
+            Err(s) => assert_eq!(
+                s,
+                "[Runtime Error on '__main__()'] cannot reassign 'x', declared with 'val'."
+            ),
+        }
+    }
+
+    #[test]
+    fn execute_val_declaration_in_loop() {
+        /*
+         * > var i = 0
+         * > while i < 3:
+         * >     val j = i
+         * >     i = i + 1
+         */
+        let env: Environment<EnvValue> = Environment::new();
+
+        let program = Block(vec![
+            VarDeclaration(String::from("i"), None),
+            Assignment(String::from("i"), Box::new(CInt(0)), Some(TInteger)),
+            While(
+                Box::new(LT(Box::new(Var(String::from("i"))), Box::new(CInt(3)))),
+                Box::new(Block(vec![
+                    ValDeclaration(String::from("j"), None),
+                    Assignment(String::from("j"), Box::new(Var(String::from("i"))), None),
+                    Assignment(
+                        String::from("i"),
+                        Box::new(Add(Box::new(Var(String::from("i"))), Box::new(CInt(1)))),
+                        None,
+                    ),
+                ])),
+            ),
+        ]);
+
+        match run(program, &env) {
+            Ok(ControlFlow::Continue(new_env)) => assert_eq!(
+                new_env.search_frame("j".to_string()),
+                Some(&EnvValue::Exp(CInt(2)))
+            ),
+            Ok(ControlFlow::Return(_)) => assert!(false),
+            Err(s) => assert!(false, "{:?}", s),
+        }
+    }
+
+    #[test]
+    fn execute_struct_fields() {
+        /*
+         * > struct Point: x: TReal, y: TReal
+         * > struct Line: start: Point, end: Point
+         * > line = Line(Point(0.0, 0.0), Point(1.0, 1.0))
+         * > line.start.y = line.end.x + 1.0
+         */
+        let env: Environment<EnvValue> = Environment::new();
+
+        let point = |x, y| FuncCall(String::from("Point"), vec![CReal(x), CReal(y)]);
+        let program = Block(vec![
+            StructDef(
+                String::from("Point"),
+                vec![(String::from("x"), TReal), (String::from("y"), TReal)],
+            ),
+            StructDef(
+                String::from("Line"),
+                vec![
+                    (String::from("start"), TUser(String::from("Point"))),
+                    (String::from("end"), TUser(String::from("Point"))),
+                ],
+            ),
+            Assignment(
+                String::from("line"),
+                Box::new(FuncCall(
+                    String::from("Line"),
+                    vec![point(0.0, 0.0), point(1.0, 1.0)],
+                )),
+                None,
+            ),
+            FieldAssignment(
+                Box::new(FieldAccess(
+                    Box::new(Var(String::from("line"))),
+                    String::from("start"),
+                )),
+                String::from("y"),
+                Box::new(Add(
+                    Box::new(FieldAccess(
+                        Box::new(FieldAccess(
+                            Box::new(Var(String::from("line"))),
+                            String::from("end"),
+                        )),
+                        String::from("x"),
+                    )),
+                    Box::new(CReal(1.0)),
+                )),
+            ),
+        ]);
+
+        match run(program, &env) {
+            Ok(ControlFlow::Continue(new_env)) => assert_eq!(
+                new_env
+                    .search_frame("line".to_string())
+                    .map(|value| value.to_string()),
+                Some(String::from(
+                    "Line(start=Point(x=0.0, y=2.0), end=Point(x=1.0, y=1.0))"
+                ))
+            ),
+            Ok(ControlFlow::Return(_)) => assert!(false),
+            Err(s) => assert!(false, "{:?}", s),
+        }
+    }
+
+    #[test]
+    fn eval_struct_constructor_arity() {
+        let mut env: Environment<EnvValue> = Environment::new();
+        env.insert_type(
+            String::from("Point"),
+            TypeDef::Struct(vec![(String::from("x"), TReal), (String::from("y"), TReal)]),
+        );
+
+        assert_eq!(
+            eval(FuncCall(String::from("Point"), vec![CReal(1.0)]), &env),
+            Err((
+                String::from("'Point()' expected 2 arguments, found 1."),
+                None
+            ))
+        );
+    }
+
+    #[test]
+    fn execute_match_on_enum_and_maybe() {
+        /*
+         * > enum Shape: Circle(TReal) | Rect(TReal, TReal) | Empty
+         * > def area(s: TMaybe[Shape]) -> TReal:
+         * >     match s:
+         * >         case Just(Circle(r)):
+         * >             return 3.0 * r * r
+         * >         case Just(Rect(w, h)):
+         * >             return w * h
+         * >         case Just(Empty):
+         * >             return 0.0
+         * >         case Nothing:
+         * >             return -1.0
+         */
+        let env: Environment<EnvValue> = Environment::new();
+
+        let case = |name: &str, args| Pattern::Constructor(name.to_string(), args);
+        let var = |name: &str| Box::new(Var(name.to_string()));
+        let area = FuncDef(Function {
+            name: String::from("area"),
+            type_params: vec![],
+            kind: Some(TReal),
+            params: Some(vec![(
+                String::from("s"),
+                TMaybe(Box::new(TUser(String::from("Shape")))),
+            )]),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(Statement::Match(
+                var("s"),
+                vec![
+                    (
+                        case(
+                            "Just",
+                            vec![case("Circle", vec![Pattern::Binding("r".to_string())])],
+                        ),
+                        Return(Box::new(Mul(
+                            Box::new(Mul(Box::new(CReal(3.0)), var("r"))),
+                            var("r"),
+                        ))),
+                    ),
+                    (
+                        case(
+                            "Just",
+                            vec![case(
+                                "Rect",
+                                vec![
+                                    Pattern::Binding("w".to_string()),
+                                    Pattern::Binding("h".to_string()),
+                                ],
+                            )],
+                        ),
+                        Return(Box::new(Mul(var("w"), var("h")))),
+                    ),
+                    (
+                        case("Just", vec![case("Empty", vec![])]),
+                        Return(Box::new(CReal(0.0))),
+                    ),
+                    (case("Nothing", vec![]), Return(Box::new(CReal(-1.0)))),
+                ],
+            ))),
+        });
+        let definitions = Block(vec![
+            EnumDef(
+                String::from("Shape"),
+                vec![
+                    (String::from("Circle"), vec![TReal]),
+                    (String::from("Rect"), vec![TReal, TReal]),
+                    (String::from("Empty"), vec![]),
+                ],
+            ),
+            area,
+        ]);
+
+        let env = match run(definitions, &env) {
+            Ok(ControlFlow::Continue(new_env)) => new_env,
+            _ => panic!("definitions should execute"),
+        };
+        let area_of = |arg: Expression| eval(FuncCall(String::from("area"), vec![arg]), &env);
+
+        assert_eq!(
+            area_of(CJust(Box::new(FuncCall(
+                String::from("Circle"),
+                vec![CReal(2.0)]
+            )))),
+            Ok(EnvValue::Exp(CReal(12.0)))
+        );
+        assert_eq!(area_of(CNothing), Ok(EnvValue::Exp(CReal(-1.0))));
+
+        let rect = eval(
+            FuncCall(String::from("Rect"), vec![CReal(2.0), CReal(3.0)]),
+            &env,
+        )
+        .unwrap();
+        assert_eq!(rect.to_string(), "Rect(2.0, 3.0)");
+        assert_eq!(
+            eval(Var(String::from("Empty")), &env).map(|v| v.to_string()),
+            Ok(String::from("Empty"))
+        );
+    }
+
+    #[test]
+    fn execute_match_on_result() {
+        /*
+         * > match r:
+         * >     case Ok(v):
+         * >         total = v + 1
+         * >     case Err(message):
+         * >         total = 0
+         */
+        let env: Environment<EnvValue> = Environment::new();
+
+        let program = |r: Expression| {
+            Statement::Match(
+                Box::new(r),
+                vec![
+                    (
+                        Pattern::Constructor(
+                            String::from("Ok"),
+                            vec![Pattern::Binding(String::from("v"))],
+                        ),
+                        Block(vec![Assignment(
+                            String::from("total"),
+                            Box::new(Add(Box::new(Var(String::from("v"))), Box::new(CInt(1)))),
+                            None,
+                        )]),
+                    ),
+                    (
+                        Pattern::Constructor(
+                            String::from("Err"),
+                            vec![Pattern::Binding(String::from("message"))],
+                        ),
+                        Block(vec![Assignment(
+                            String::from("total"),
+                            Box::new(CInt(0)),
+                            None,
+                        )]),
+                    ),
+                ],
+            )
+        };
+
+        match run(program(COk(Box::new(CInt(41)))), &env) {
+            Ok(ControlFlow::Continue(new_env)) => assert_eq!(
+                new_env.search_frame("total".to_string()),
+                Some(&EnvValue::Exp(CInt(42)))
+            ),
+            _ => assert!(false),
+        }
+        match run(program(CErr(Box::new(CString(String::from("boom"))))), &env) {
+            Ok(ControlFlow::Continue(new_env)) => {
+                assert_eq!(
+                    new_env.search_frame("total".to_string()),
+                    Some(&EnvValue::Exp(CInt(0)))
+                );
+                assert_eq!(
+                    new_env.search_frame("message".to_string()),
+                    Some(&EnvValue::Exp(CString(String::from("boom"))))
+                );
+            }
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn eval_propagate_errors() {
+        let env = Environment::new();
+        let result = || TResult(Box::new(TInteger), Box::new(TString));
+
+        // def next(r: TResult[TInteger, TString]) -> TResult[TInteger, TString]:
+        //     v = r?
+        //     return Ok(v + 1)
+        let next = FuncDef(Function {
+            name: String::from("next"),
+            type_params: vec![],
+            kind: Some(result()),
+            params: Some(vec![(String::from("r"), result())]),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(Block(vec![
+                Assignment(
+                    String::from("v"),
+                    Box::new(Propagate(Box::new(Var(String::from("r"))))),
+                    None,
+                ),
+                Return(Box::new(COk(Box::new(Add(
+                    Box::new(Var(String::from("v"))),
+                    Box::new(CInt(1)),
+                ))))),
+            ]))),
+        });
+        let env = match execute(next, &env) {
+            Ok(ControlFlow::Continue(new_env)) => new_env,
+            _ => panic!("definitions should run"),
+        };
+        let boom = || CErr(Box::new(CString(String::from("boom"))));
+        let call = |arg: Expression| eval(FuncCall(String::from("next"), vec![arg]), &env);
+
+        assert_eq!(
+            call(COk(Box::new(CInt(1)))),
+            eval(COk(Box::new(CInt(2))), &env)
+        );
+        // The error leaves the function as its result
+        assert_eq!(call(boom()), eval(boom(), &env));
+        // ... and ends the program at the top level
+        assert_eq!(
+            execute(
+                Block(vec![Assignment(
+                    String::from("x"),
+                    Box::new(Propagate(Box::new(boom()))),
+                    None
+                )]),
+                &env
+            )
+            .err(),
+            Some((String::from("Program terminated with errors: boom"), None))
+        );
+    }
+
+    #[test]
+    fn eval_equality_of_every_checked_type() {
+        let mut env = Environment::new();
+        let mut type_env = Environment::new();
+        env.insert_variable(
+            String::from("x"),
+            eval(CJust(Box::new(CInt(1))), &env).unwrap(),
+        );
+        type_env.insert_variable(String::from("x"), TMaybe(Box::new(TInteger)));
+        let string = |s: &str| Box::new(CString(String::from(s)));
+
+        let cases = vec![
+            // x != Nothing
+            (
+                Not(Box::new(EQ(
+                    Box::new(Var(String::from("x"))),
+                    Box::new(CNothing),
+                ))),
+                CTrue,
+            ),
+            (EQ(Box::new(CTrue), Box::new(CTrue)), CTrue),
+            (EQ(string("a"), string("b")), CFalse),
+            (EQ(Box::new(CInt(2)), Box::new(CReal(2.0))), CTrue),
+            (
+                EQ(
+                    Box::new(CErr(string("boom"))),
+                    Box::new(CErr(string("boom"))),
+                ),
+                CTrue,
+            ),
+            (
+                EQ(
+                    Box::new(CJust(Box::new(CJust(Box::new(CInt(1)))))),
+                    Box::new(CJust(Box::new(CNothing))),
+                ),
+                CFalse,
+            ),
+        ];
+
+        // Every comparison the checker accepts also runs
+        for (exp, expected) in cases {
+            assert_eq!(check_exp(exp.clone(), &type_env), Ok(TBool));
+            assert_eq!(eval(exp, &env), Ok(EnvValue::Exp(expected)));
+        }
+    }
+
+    #[test]
+    fn eval_match_expression() {
+        /*
+         * > match m:
+         * >     case Just(x): x + 1
+         * >     case Nothing: 0
+         */
+        let env = Environment::new();
+
+        let exp = |m: Expression| {
+            Expression::Match(
+                Box::new(m),
+                vec![
+                    (
+                        Pattern::Constructor(
+                            String::from("Just"),
+                            vec![Pattern::Binding(String::from("x"))],
+                        ),
+                        Add(Box::new(Var(String::from("x"))), Box::new(CInt(1))),
+                    ),
+                    (
+                        Pattern::Constructor(String::from("Nothing"), vec![]),
+                        CInt(0),
+                    ),
+                ],
+            )
+        };
+
+        assert_eq!(
+            eval(exp(CJust(Box::new(CInt(41)))), &env),
+            Ok(EnvValue::Exp(CInt(42)))
+        );
+        assert_eq!(eval(exp(CNothing), &env), Ok(EnvValue::Exp(CInt(0))));
+    }
+
+    #[test]
+    fn eval_combinators_on_maybe_and_result() {
+        let env = Environment::new();
+        let double = FuncDef(Function {
+            name: String::from("double"),
+            type_params: vec![],
+            kind: Some(TInteger),
+            params: Some(vec![(String::from("x"), TInteger)]),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(Return(Box::new(Mul(
+                Box::new(Var(String::from("x"))),
+                Box::new(CInt(2)),
+            ))))),
+        });
+        let fallback = FuncDef(Function {
+            name: String::from("fallback"),
+            type_params: vec![],
+            kind: Some(TMaybe(Box::new(TInteger))),
+            params: None,
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(Return(Box::new(CJust(Box::new(CInt(0))))))),
+        });
+        let env = match execute(Sequence(Box::new(double), Box::new(fallback)), &env) {
+            Ok(ControlFlow::Continue(new_env)) => new_env,
+            _ => panic!("definitions should run"),
+        };
+        let function = |name: &str| Box::new(Var(String::from(name)));
+        let just = || Box::new(CJust(Box::new(CInt(21))));
+        let err = || Box::new(CErr(Box::new(CString(String::from("boom")))));
+
+        let value = |constructor: &str, payload: Expression| {
+            builtin_variant(constructor, vec![EnvValue::Exp(payload)])
+        };
+
+        assert_eq!(
+            eval(Map(just(), function("double")), &env),
+            Ok(value("Just", CInt(42)))
+        );
+        assert_eq!(
+            eval(Map(err(), function("double")), &env),
+            Ok(value("Err", CString(String::from("boom"))))
+        );
+        assert_eq!(
+            eval(AndThen(Box::new(CNothing), function("double")), &env),
+            Ok(builtin_variant("Nothing", vec![]))
+        );
+        assert_eq!(
+            eval(OrElse(Box::new(CNothing), function("fallback")), &env),
+            Ok(value("Just", CInt(0)))
+        );
+        assert_eq!(
+            eval(UnwrapOr(err(), Box::new(CInt(7))), &env),
+            Ok(EnvValue::Exp(CInt(7)))
+        );
+        assert_eq!(
+            eval(
+                OkOr(just(), Box::new(CString(String::from("missing")))),
+                &env
+            ),
+            Ok(value("Ok", CInt(21)))
+        );
+        assert_eq!(
+            eval(
+                OkOr(
+                    Box::new(CNothing),
+                    Box::new(CString(String::from("missing")))
+                ),
+                &env
+            ),
+            Ok(value("Err", CString(String::from("missing"))))
+        );
+    }
+
+    #[test]
+    fn eval_first_class_functions() {
+        let env = Environment::new();
+        let int_to_int = TFunction(Box::new(Some(TInteger)), vec![TInteger]);
+
+        // def apply(f: TFunction[[TInteger], TInteger], x: TInteger) -> TInteger: return f(x)
+        let apply_def = FuncDef(Function {
+            name: String::from("apply"),
+            type_params: vec![],
+            kind: Some(TInteger),
+            params: Some(vec![
+                (String::from("f"), int_to_int.clone()),
+                (String::from("x"), TInteger),
+            ]),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(Return(Box::new(FuncCall(
+                String::from("f"),
+                vec![Var(String::from("x"))],
+            ))))),
+        });
+        // def doubler() -> TFunction[[TInteger], TInteger]: return lambda(y: TInteger) -> TInteger: y * 2
+        let doubler_def = FuncDef(Function {
+            name: String::from("doubler"),
+            type_params: vec![],
+            kind: Some(int_to_int),
+            params: Some(vec![]),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(Return(Box::new(Lambda(Box::new(Function {
+                name: String::from("lambda"),
+                type_params: vec![],
+                kind: Some(TInteger),
+                params: Some(vec![(String::from("y"), TInteger)]),
+                variadic: false,
+                defaults: vec![],
+                body: Some(Box::new(Return(Box::new(Mul(
+                    Box::new(Var(String::from("y"))),
+                    Box::new(CInt(2)),
+                ))))),
+            })))))),
+        });
+
+        let env = match execute(Sequence(Box::new(apply_def), Box::new(doubler_def)), &env) {
+            Ok(ControlFlow::Continue(new_env)) => new_env,
+            _ => panic!("definitions should run"),
+        };
+
+        let doubled = Call(
+            Box::new(FuncCall(String::from("doubler"), vec![])),
+            vec![CInt(21)],
+        );
+        let applied = FuncCall(
+            String::from("apply"),
+            vec![FuncCall(String::from("doubler"), vec![]), CInt(5)],
+        );
+
+        assert_eq!(eval(doubled, &env), Ok(EnvValue::Exp(CInt(42))));
+        assert_eq!(eval(applied, &env), Ok(EnvValue::Exp(CInt(10))));
+        assert_eq!(
+            eval(Call(Box::new(CInt(1)), vec![]), &env),
+            Err((String::from("'1' is not a function."), None))
+        );
+    }
+
+    #[test]
+    fn eval_arity_mismatches_and_caller_arguments() {
+        let env = Environment::new();
+
+        // def sub(x: TInteger, y: TInteger) -> TInteger: return x - y
+        // x = 5
+        // y = 1
+        let program = Sequence(
+            Box::new(FuncDef(Function {
+                name: String::from("sub"),
+                type_params: vec![],
+                kind: Some(TInteger),
+                params: Some(vec![
+                    (String::from("x"), TInteger),
+                    (String::from("y"), TInteger),
+                ]),
+                variadic: false,
+                defaults: vec![],
+                body: Some(Box::new(Return(Box::new(Sub(
+                    Box::new(Var(String::from("x"))),
+                    Box::new(Var(String::from("y"))),
+                ))))),
+            })),
+            Box::new(Sequence(
+                Box::new(Assignment(String::from("x"), Box::new(CInt(5)), None)),
+                Box::new(Assignment(String::from("y"), Box::new(CInt(1)), None)),
+            )),
+        );
+        let env = match execute(program, &env) {
+            Ok(ControlFlow::Continue(new_env)) => new_env,
+            _ => panic!("definitions should run"),
+        };
+        let call = |args: Vec<Expression>| eval(FuncCall(String::from("sub"), args), &env);
+
+        // Arguments are evaluated in the caller, before the parameters are bound
+        assert_eq!(
+            call(vec![Var(String::from("y")), Var(String::from("x"))]),
+            Ok(EnvValue::Exp(CInt(-4)))
+        );
+        assert_eq!(
+            call(vec![CInt(1)]),
+            Err((String::from("'sub()' expected 2 arguments, found 1."), None))
+        );
+        assert_eq!(
+            call(vec![CInt(1), CInt(2), CInt(3)]),
+            Err((String::from("'sub()' expected 2 arguments, found 3."), None))
+        );
+        assert_eq!(
+            call(vec![CInt(1), Var(String::from("z"))]),
+            Err((String::from("'z' is not defined."), None))
+        );
+    }
+
+    #[test]
+    fn eval_functions_read_globals_at_call_time() {
+        let env = Environment::new();
+
+        // def get() -> TInteger: return x
+        let get_def = FuncDef(Function {
+            name: String::from("get"),
+            type_params: vec![],
+            kind: Some(TInteger),
+            params: Some(vec![]),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(Return(Box::new(Var(String::from("x")))))),
+        });
+        let program = Sequence(
+            Box::new(Assignment(String::from("x"), Box::new(CInt(10)), None)),
+            Box::new(Sequence(
+                Box::new(get_def),
+                Box::new(Sequence(
+                    Box::new(Assignment(
+                        String::from("before"),
+                        Box::new(FuncCall(String::from("get"), vec![])),
+                        None,
+                    )),
+                    Box::new(Sequence(
+                        Box::new(Assignment(String::from("x"), Box::new(CInt(20)), None)),
+                        Box::new(Assignment(
+                            String::from("after"),
+                            Box::new(FuncCall(String::from("get"), vec![])),
+                            None,
+                        )),
+                    )),
+                )),
+            )),
+        );
+
+        match execute(program, &env) {
+            Ok(ControlFlow::Continue(new_env)) => {
+                assert_eq!(
+                    new_env.search_frame(String::from("before")),
+                    Some(&EnvValue::Exp(CInt(10)))
+                );
+                assert_eq!(
+                    new_env.search_frame(String::from("after")),
+                    Some(&EnvValue::Exp(CInt(20)))
+                );
+            }
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn eval_nested_functions_capture_their_scope() {
+        let env = Environment::new();
+        let int_to_int = TFunction(Box::new(Some(TInteger)), vec![TInteger]);
+
+        // def adder(n: TInteger) -> TFunction[[TInteger], TInteger]:
+        //     def add(m: TInteger) -> TInteger:
+        //         return n + m
+        //     return add
+        let adder_def = FuncDef(Function {
+            name: String::from("adder"),
+            type_params: vec![],
+            kind: Some(int_to_int),
+            params: Some(vec![(String::from("n"), TInteger)]),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(Sequence(
+                Box::new(FuncDef(Function {
+                    name: String::from("add"),
+                    type_params: vec![],
+                    kind: Some(TInteger),
+                    params: Some(vec![(String::from("m"), TInteger)]),
+                    variadic: false,
+                    defaults: vec![],
+                    body: Some(Box::new(Return(Box::new(Add(
+                        Box::new(Var(String::from("n"))),
+                        Box::new(Var(String::from("m"))),
+                    ))))),
+                })),
+                Box::new(Return(Box::new(Var(String::from("add"))))),
+            ))),
+        });
+
+        // def factorial(n: TInteger) -> TInteger:
+        //     def go(k: TInteger) -> TInteger:
+        //         if k == 0: return 1
+        //         else: return k * go(k - 1)
+        //     return go(n)
+        let factorial_def = FuncDef(Function {
+            name: String::from("factorial"),
+            type_params: vec![],
+            kind: Some(TInteger),
+            params: Some(vec![(String::from("n"), TInteger)]),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(Sequence(
+                Box::new(FuncDef(Function {
+                    name: String::from("go"),
+                    type_params: vec![],
+                    kind: Some(TInteger),
+                    params: Some(vec![(String::from("k"), TInteger)]),
+                    variadic: false,
+                    defaults: vec![],
+                    body: Some(Box::new(IfThenElse(
+                        Box::new(EQ(Box::new(Var(String::from("k"))), Box::new(CInt(0)))),
+                        Box::new(Return(Box::new(CInt(1)))),
+                        Some(Box::new(Return(Box::new(Mul(
+                            Box::new(Var(String::from("k"))),
+                            Box::new(FuncCall(
+                                String::from("go"),
+                                vec![Sub(Box::new(Var(String::from("k"))), Box::new(CInt(1)))],
+                            )),
+                        ))))),
+                    ))),
+                })),
+                Box::new(Return(Box::new(FuncCall(
+                    String::from("go"),
+                    vec![Var(String::from("n"))],
+                )))),
+            ))),
+        });
+
+        let program = Sequence(
+            Box::new(Sequence(Box::new(adder_def), Box::new(factorial_def))),
+            Box::new(Sequence(
+                Box::new(Assignment(
+                    String::from("add5"),
+                    Box::new(FuncCall(String::from("adder"), vec![CInt(5)])),
+                    None,
+                )),
+                Box::new(Sequence(
+                    Box::new(Assignment(
+                        String::from("sum"),
+                        Box::new(FuncCall(String::from("add5"), vec![CInt(2)])),
+                        None,
+                    )),
+                    Box::new(Assignment(
+                        String::from("product"),
+                        Box::new(FuncCall(String::from("factorial"), vec![CInt(5)])),
+                        None,
+                    )),
+                )),
+            )),
+        );
+
+        match execute(program, &env) {
+            Ok(ControlFlow::Continue(new_env)) => {
+                assert_eq!(
+                    new_env.search_frame(String::from("sum")),
+                    Some(&EnvValue::Exp(CInt(7)))
+                );
+                assert_eq!(
+                    new_env.search_frame(String::from("product")),
+                    Some(&EnvValue::Exp(CInt(120)))
+                );
+            }
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn eval_nested_functions_see_later_bindings() {
+        let env = Environment::new();
+        let function = |name: &str, params: Vec<(Name, Type)>, body: Statement| {
+            FuncDef(Function {
+                name: String::from(name),
+                type_params: vec![],
+                kind: Some(TInteger),
+                params: Some(params),
+                variadic: false,
+                defaults: vec![],
+                body: Some(Box::new(body)),
+            })
+        };
+
+        // def outer() -> TInteger:
+        //     def inner() -> TInteger:
+        //         return y
+        //     y = 5
+        //     return inner()
+        let outer = function(
+            "outer",
+            vec![],
+            Block(vec![
+                function("inner", vec![], Return(Box::new(Var(String::from("y"))))),
+                Assignment(String::from("y"), Box::new(CInt(5)), None),
+                Return(Box::new(FuncCall(String::from("inner"), vec![]))),
+            ]),
+        );
+
+        // def parity(n: TInteger) -> TInteger:
+        //     def even(k: TInteger) -> TInteger:
+        //         if k == 0: return 1
+        //         else: return odd(k - 1)
+        //     def odd(k: TInteger) -> TInteger:
+        //         if k == 0: return 0
+        //         else: return even(k - 1)
+        //     return even(n)
+        let step = |base: i32, other: &str| {
+            IfThenElse(
+                Box::new(EQ(Box::new(Var(String::from("k"))), Box::new(CInt(0)))),
+                Box::new(Return(Box::new(CInt(base)))),
+                Some(Box::new(Return(Box::new(FuncCall(
+                    String::from(other),
+                    vec![Sub(Box::new(Var(String::from("k"))), Box::new(CInt(1)))],
+                ))))),
+            )
+        };
+        let parity = function(
+            "parity",
+            vec![(String::from("n"), TInteger)],
+            Block(vec![
+                function("even", vec![(String::from("k"), TInteger)], step(1, "odd")),
+                function("odd", vec![(String::from("k"), TInteger)], step(0, "even")),
+                Return(Box::new(FuncCall(
+                    String::from("even"),
+                    vec![Var(String::from("n"))],
+                ))),
+            ]),
+        );
+
+        let program = Block(vec![
+            outer,
+            parity,
+            Assignment(
+                String::from("y"),
+                Box::new(FuncCall(String::from("outer"), vec![])),
+                None,
+            ),
+            Assignment(
+                String::from("even"),
+                Box::new(FuncCall(String::from("parity"), vec![CInt(4)])),
+                None,
+            ),
+            Assignment(
+                String::from("odd"),
+                Box::new(FuncCall(String::from("parity"), vec![CInt(3)])),
+                None,
+            ),
+        ]);
+
+        match execute(program, &env) {
+            Ok(ControlFlow::Continue(new_env)) => {
+                assert_eq!(
+                    new_env.search_frame(String::from("y")),
+                    Some(&EnvValue::Exp(CInt(5)))
+                );
+                assert_eq!(
+                    new_env.search_frame(String::from("even")),
+                    Some(&EnvValue::Exp(CInt(1)))
+                );
+                assert_eq!(
+                    new_env.search_frame(String::from("odd")),
+                    Some(&EnvValue::Exp(CInt(0)))
+                );
+            }
+            Ok(_) => assert!(false),
+            Err((s, _)) => panic!("{}", s),
+        }
+    }
+
+    #[test]
+    fn eval_closures_read_later_globals() {
+        let env = Environment::new();
+        let function = |name: &str, body: Statement| {
+            FuncDef(Function {
+                name: String::from(name),
+                type_params: vec![],
+                kind: Some(TInteger),
+                params: Some(vec![]),
+                variadic: false,
+                defaults: vec![],
+                body: Some(Box::new(body)),
+            })
+        };
+
+        // def outer():
+        //     def inner() -> TInteger:
+        //         return g
+        //     return inner
+        // f = outer()
+        // g = 7
+        // result = f()
+        let outer = function(
+            "outer",
+            Block(vec![
+                function("inner", Return(Box::new(Var(String::from("g"))))),
+                Return(Box::new(Var(String::from("inner")))),
+            ]),
+        );
+        let program = Block(vec![
+            outer,
+            Assignment(
+                String::from("f"),
+                Box::new(FuncCall(String::from("outer"), vec![])),
+                None,
+            ),
+            Assignment(String::from("g"), Box::new(CInt(7)), None),
+            Assignment(
+                String::from("result"),
+                Box::new(FuncCall(String::from("f"), vec![])),
+                None,
+            ),
+        ]);
+
+        match execute(program, &env) {
+            Ok(ControlFlow::Continue(new_env)) => assert_eq!(
+                new_env.search_frame(String::from("result")),
+                Some(&EnvValue::Exp(CInt(7)))
+            ),
+            Ok(_) => assert!(false),
+            Err((s, _)) => panic!("{}", s),
+        }
+    }
+
+    #[test]
+    fn eval_global_and_nonlocal_writes() {
+        let env = Environment::new();
+        let increment = |name: &str| {
+            Assignment(
+                String::from(name),
+                Box::new(Add(Box::new(Var(String::from(name))), Box::new(CInt(1)))),
+                None,
+            )
+        };
+
+        // def bump() -> TInteger:
+        //     global count
+        //     count = count + 1
+        //     return count
+        let bump = FuncDef(Function {
+            name: String::from("bump"),
+            type_params: vec![],
+            kind: Some(TInteger),
+            params: Some(vec![]),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(Block(vec![
+                Global(vec![String::from("count")]),
+                increment("count"),
+                Return(Box::new(Var(String::from("count")))),
+            ]))),
+        });
+
+        // def counter() -> TInteger:
+        //     n = 0
+        //     def step() -> TInteger:
+        //         nonlocal n
+        //         n = n + 1
+        //         return n
+        //     step()
+        //     step()
+        //     return n
+        let step = FuncDef(Function {
+            name: String::from("step"),
+            type_params: vec![],
+            kind: Some(TInteger),
+            params: Some(vec![]),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(Block(vec![
+                Nonlocal(vec![String::from("n")]),
+                increment("n"),
+                Return(Box::new(Var(String::from("n")))),
+            ]))),
+        });
+        let call_step = || {
+            Assignment(
+                String::from("ignored"),
+                Box::new(FuncCall(String::from("step"), vec![])),
+                None,
+            )
+        };
+        let counter = FuncDef(Function {
+            name: String::from("counter"),
+            type_params: vec![],
+            kind: Some(TInteger),
+            params: Some(vec![]),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(Block(vec![
+                Assignment(String::from("n"), Box::new(CInt(0)), None),
+                step,
+                call_step(),
+                call_step(),
+                Return(Box::new(Var(String::from("n")))),
+            ]))),
+        });
+
+        let program = Block(vec![
+            Assignment(String::from("count"), Box::new(CInt(0)), None),
+            bump,
+            counter,
+            Assignment(
+                String::from("first"),
+                Box::new(FuncCall(String::from("bump"), vec![])),
+                None,
+            ),
+            Assignment(
+                String::from("second"),
+                Box::new(FuncCall(String::from("bump"), vec![])),
+                None,
+            ),
+            Assignment(
+                String::from("steps"),
+                Box::new(FuncCall(String::from("counter"), vec![])),
+                None,
+            ),
+        ]);
+
+        match execute(program, &env) {
+            Ok(ControlFlow::Continue(new_env)) => {
+                assert_eq!(
+                    eval(Var(String::from("second")), &new_env),
+                    Ok(EnvValue::Exp(CInt(2)))
+                );
+                assert_eq!(
+                    eval(Var(String::from("count")), &new_env),
+                    Ok(EnvValue::Exp(CInt(2)))
+                );
+                assert_eq!(
+                    eval(Var(String::from("steps")), &new_env),
+                    Ok(EnvValue::Exp(CInt(2)))
+                );
+            }
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn eval_global_assigned_after_definition() {
+        let env = Environment::new();
+
+        // def bump():
+        //     global count
+        //     count = count + 1
+        // count = 0
+        // a = bump()
+        // b = bump()
+        let bump = FuncDef(Function {
+            name: String::from("bump"),
+            type_params: vec![],
+            kind: Some(TVoid),
+            params: Some(vec![]),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(Block(vec![
+                Global(vec![String::from("count")]),
+                Assignment(
+                    String::from("count"),
+                    Box::new(Add(Box::new(Var(String::from("count"))), Box::new(CInt(1)))),
+                    None,
+                ),
+            ]))),
+        });
+        let call_bump = |name: &str| {
+            Assignment(
+                String::from(name),
+                Box::new(FuncCall(String::from("bump"), vec![])),
+                None,
+            )
+        };
+        let program = Block(vec![
+            bump,
+            Assignment(String::from("count"), Box::new(CInt(0)), None),
+            call_bump("a"),
+            call_bump("b"),
+        ]);
+
+        match execute(program, &env) {
+            Ok(ControlFlow::Continue(new_env)) => assert_eq!(
+                eval(Var(String::from("count")), &new_env),
+                Ok(EnvValue::Exp(CInt(2)))
+            ),
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn eval_located_statements() {
+        let env = Environment::new();
+        let at =
+            |line: usize, stmt: Statement| Located(Location { line, column: 1 }, Box::new(stmt));
+
+        // The same program as above, with every statement located
+        let bump = FuncDef(Function {
+            name: String::from("bump"),
+            type_params: vec![],
+            kind: Some(TVoid),
+            params: Some(vec![]),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(Block(vec![
+                at(2, Global(vec![String::from("count")])),
+                at(
+                    3,
+                    Assignment(
+                        String::from("count"),
+                        Box::new(Add(Box::new(Var(String::from("count"))), Box::new(CInt(1)))),
+                        None,
+                    ),
+                ),
+            ]))),
+        });
+        let program = Block(vec![
+            at(1, bump),
+            at(
+                4,
+                Assignment(String::from("count"), Box::new(CInt(0)), None),
+            ),
+            at(
+                5,
+                Assignment(
+                    String::from("a"),
+                    Box::new(FuncCall(String::from("bump"), vec![])),
+                    None,
+                ),
+            ),
+        ]);
+
+        match execute(program, &env) {
+            Ok(ControlFlow::Continue(new_env)) => assert_eq!(
+                eval(Var(String::from("count")), &new_env),
+                Ok(EnvValue::Exp(CInt(1)))
+            ),
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn eval_default_and_keyword_arguments() {
+        let env = Environment::new();
+
+        // factor = 10
+        // def scale(x: TInteger, by: TInteger = factor) -> TInteger: return x * by
+        // factor = 2
+        let program = Block(vec![
+            Assignment(String::from("factor"), Box::new(CInt(10)), None),
+            FuncDef(Function {
+                name: String::from("scale"),
+                type_params: vec![],
+                kind: Some(TInteger),
+                params: Some(vec![
+                    (String::from("x"), TInteger),
+                    (String::from("by"), TInteger),
+                ]),
+                variadic: false,
+                defaults: vec![(String::from("by"), Var(String::from("factor")))],
+                body: Some(Box::new(Return(Box::new(Mul(
+                    Box::new(Var(String::from("x"))),
+                    Box::new(Var(String::from("by"))),
+                ))))),
+            }),
+            Assignment(String::from("factor"), Box::new(CInt(2)), None),
+        ]);
+        let env = match execute(program, &env) {
+            Ok(ControlFlow::Continue(new_env)) => new_env,
+            _ => panic!("definitions should run"),
+        };
+        let call = |args: Vec<Expression>| eval(FuncCall(String::from("scale"), args), &env);
+        let keyword = |name: &str, exp: Expression| KeywordArg(String::from(name), Box::new(exp));
+
+        // The default keeps the value 'factor' had when 'scale' was defined
+        assert_eq!(call(vec![CInt(2)]), Ok(EnvValue::Exp(CInt(20))));
+        assert_eq!(
+            call(vec![keyword("by", CInt(3)), keyword("x", CInt(2))]),
+            Ok(EnvValue::Exp(CInt(6)))
+        );
+        assert_eq!(
+            call(vec![keyword("by", CInt(3))]),
+            Err((String::from("'scale()' missing argument 'x'."), None))
+        );
+    }
+
+    #[test]
+    fn eval_variadic_parameters_and_unpacking() {
+        let env = Environment::new();
+        let variadic = |name: &str, params: Vec<(Name, _)>, kind, body: Expression| {
+            FuncDef(Function {
+                name: String::from(name),
+                type_params: vec![],
+                kind: Some(kind),
+                params: Some(params),
+                variadic: true,
+                defaults: vec![],
+                body: Some(Box::new(Return(Box::new(body)))),
+            })
+        };
+        let list = TList(Box::new(TInteger));
+
+        // def pack(*rest: TInteger) -> TList(TInteger): return rest
+        // def head(first: TInteger, *rest: TInteger) -> TInteger: return first
+        // def forward(*xs: TInteger) -> TInteger: return head(*xs)
+        let program = Sequence(
+            Box::new(variadic(
+                "pack",
+                vec![(String::from("rest"), list.clone())],
+                list.clone(),
+                Var(String::from("rest")),
+            )),
+            Box::new(Sequence(
+                Box::new(variadic(
+                    "head",
+                    vec![
+                        (String::from("first"), TInteger),
+                        (String::from("rest"), list.clone()),
+                    ],
+                    TInteger,
+                    Var(String::from("first")),
+                )),
+                Box::new(variadic(
+                    "forward",
+                    vec![(String::from("xs"), list)],
+                    TInteger,
+                    FuncCall(
+                        String::from("head"),
+                        vec![Unpack(Box::new(Var(String::from("xs"))))],
+                    ),
+                )),
+            )),
+        );
+        let env = match execute(program, &env) {
+            Ok(ControlFlow::Continue(new_env)) => new_env,
+            _ => panic!("definitions should run"),
+        };
+        let call =
+            |name: &str, args: Vec<Expression>| eval(FuncCall(String::from(name), args), &env);
+
+        assert_eq!(
+            call("pack", vec![CInt(1), CInt(2)]),
+            Ok(EnvValue::List(vec![
+                EnvValue::Exp(CInt(1)),
+                EnvValue::Exp(CInt(2))
+            ]))
+        );
+        assert_eq!(call("pack", vec![]), Ok(EnvValue::List(vec![])));
+        assert_eq!(
+            call("forward", vec![CInt(7), CInt(8)]),
+            Ok(EnvValue::Exp(CInt(7)))
+        );
+        assert_eq!(
+            call("forward", vec![]),
+            Err((
+                String::from("'head()' expected at least 1 argument, found 0."),
+                None
+            ))
+        );
+        assert_eq!(
+            call("head", vec![Unpack(Box::new(CInt(1)))]),
+            Err((String::from("cannot unpack '1', expected a list."), None))
+        );
+
+        // pack(0, *xs, 3), with xs = [1, 2]
+        let mut env = env.clone();
+        env.insert_variable(
+            String::from("xs"),
+            EnvValue::List(vec![EnvValue::Exp(CInt(1)), EnvValue::Exp(CInt(2))]),
+        );
+        assert_eq!(
+            eval(
+                FuncCall(
+                    String::from("pack"),
+                    vec![CInt(0), Unpack(Box::new(Var(String::from("xs")))), CInt(3)],
+                ),
+                &env
+            ),
+            Ok(EnvValue::List(vec![
+                EnvValue::Exp(CInt(0)),
+                EnvValue::Exp(CInt(1)),
+                EnvValue::Exp(CInt(2)),
+                EnvValue::Exp(CInt(3))
+            ]))
+        );
+    }
+
+    #[test]
+    fn eval_void_functions() {
+        let env = Environment::new();
+        let func = |name: &str, kind, body: Statement| {
+            FuncDef(Function {
+                name: String::from(name),
+                type_params: vec![],
+                kind,
+                params: Some(vec![]),
+                variadic: false,
+                defaults: vec![],
+                body: Some(Box::new(body)),
+            })
+        };
+        let assign = Assignment(String::from("x"), Box::new(CInt(0)), None);
+
+        // def reset() -> TVoid: x = 0
+        // def stop(): return
+        // def broken() -> TInteger: x = 0
+        let program = Sequence(
+            Box::new(func("reset", Some(TVoid), assign.clone())),
+            Box::new(Sequence(
+                Box::new(func("stop", None, Return(Box::new(CVoid)))),
+                Box::new(func("broken", Some(TInteger), assign)),
+            )),
+        );
+        let env = match execute(program, &env) {
+            Ok(ControlFlow::Continue(new_env)) => new_env,
+            _ => panic!("definitions should run"),
+        };
+        let call = |name: &str| eval(FuncCall(String::from(name), vec![]), &env);
+
+        assert_eq!(call("reset"), Ok(EnvValue::Exp(CVoid)));
+        assert_eq!(call("stop"), Ok(EnvValue::Exp(CVoid)));
+        assert_eq!(
+            call("broken"),
+            Err((String::from("Function did not return a value"), None))
+        );
+    }
 }
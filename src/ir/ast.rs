@@ -1,25 +1,51 @@
 pub type Name = String;
 
+// Arguments of a call, with the parameter name given by a keyword argument
+pub type Arguments<A> = Vec<(Option<Name>, A)>;
+
+// Arguments matched to the fixed parameters, and the extra ones for a variadic parameter
+pub type BoundArguments<A> = (Vec<(Name, Option<A>)>, Vec<A>);
+
+use nom::error::ContextError;
 use nom::IResult;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Frame<A> {
     pub parent_function: Option<Function>,
     pub parent_key: Option<(Name, i32)>,
     pub variables: HashMap<Name, A>,
+    pub immutable: HashSet<Name>,          // names declared with 'val'
+    pub declared: HashMap<Name, Type>,     // types stated on 'var' and 'val' declarations
+    pub outer: HashMap<Name, (Name, i32)>, // names declared 'global' or 'nonlocal', with the frame binding them
+    pub types: HashMap<Name, TypeDef>,
+    pub functions: HashMap<Name, Function>, // functions declared with 'def', for their parameter names and defaults
     pub tests: HashMap<Name, Function>,
+    pub assigned_later: HashMap<Name, A>, // module variables assigned further down, which functions may read
 }
 
 impl<A> Frame<A> {
     pub fn new(func: Option<Function>, key: Option<(Name, i32)>) -> Frame<A> {
         let variables: HashMap<Name, A> = HashMap::new();
+        let immutable: HashSet<Name> = HashSet::new();
+        let declared: HashMap<Name, Type> = HashMap::new();
+        let outer: HashMap<Name, (Name, i32)> = HashMap::new();
+        let types: HashMap<Name, TypeDef> = HashMap::new();
+        let functions: HashMap<Name, Function> = HashMap::new();
         let tests: HashMap<Name, Function> = HashMap::new();
+        let assigned_later: HashMap<Name, A> = HashMap::new();
         return Frame {
             parent_function: func,
             parent_key: key,
             variables,
+            immutable,
+            declared,
+            outer,
+            types,
+            functions,
             tests,
+            assigned_later,
         };
     }
 }
@@ -89,11 +115,149 @@ impl<A> Environment<A> {
     }
 
     pub fn insert_variable(&mut self, name: Name, kind: A) -> () {
-        if let Some(frame) = self.stack.get_mut(&self.scope_key()) {
+        let key = self.binding_key(&name);
+        if let Some(frame) = self.stack.get_mut(&key) {
             frame.variables.insert(name, kind);
         }
     }
 
+    // The frame a name is assigned in: the current one, unless the name was
+    // declared 'global' or 'nonlocal'
+    pub fn binding_key(&self, name: &Name) -> (Name, i32) {
+        match self.get_frame(self.scope_key()).outer.get(name) {
+            Some(key) => key.clone(),
+            None => self.scope_key(),
+        }
+    }
+
+    pub fn search_binding(&self, name: &Name) -> Option<&A> {
+        self.get_frame(self.binding_key(name)).variables.get(name)
+    }
+
+    pub fn declare_outer(&mut self, name: Name, key: (Name, i32)) {
+        if let Some(frame) = self.stack.get_mut(&self.scope_key()) {
+            frame.outer.insert(name, key);
+        }
+    }
+
+    // Find the frame 'global NAME' refers to, the module's, or the one 'nonlocal NAME'
+    // refers to, the nearest function binding it
+    pub fn search_outer_frame(&self, name: &Name, global: bool) -> Option<(Name, i32)> {
+        let mut curr_scope = Some(self.scope_key());
+
+        while let Some(key) = curr_scope {
+            let frame = self.get_frame(key.clone());
+            let is_module = frame.parent_key.is_none();
+
+            if is_module == global && frame.variables.contains_key(name) {
+                return Some(key);
+            }
+            curr_scope = frame.parent_key.clone();
+        }
+        None
+    }
+
+    // A declaration starts a fresh, unassigned binding in the current frame
+    pub fn declare_variable(&mut self, name: Name, mutable: bool, kind: Option<Type>) {
+        if let Some(frame) = self.stack.get_mut(&self.scope_key()) {
+            frame.variables.remove(&name);
+            match kind {
+                Some(kind) => frame.declared.insert(name.clone(), kind),
+                None => frame.declared.remove(&name),
+            };
+            if mutable {
+                frame.immutable.remove(&name);
+            } else {
+                frame.immutable.insert(name);
+            }
+        }
+    }
+
+    // The type stated on the declaration of a variable, if any
+    pub fn declared_type(&self, name: &Name) -> Option<&Type> {
+        self.get_frame(self.binding_key(name)).declared.get(name)
+    }
+
+    // A 'val' may only be assigned once, by its initializer
+    pub fn is_reassigning_val(&self, name: &Name) -> bool {
+        let frame = self.get_frame(self.binding_key(name));
+        frame.immutable.contains(name) && frame.variables.contains_key(name)
+    }
+
+    // Variables are visible from every enclosing scope, the nearest binding wins
+    pub fn search_variable(&self, name: &Name) -> Option<&A> {
+        let mut curr_scope = self.binding_key(name);
+
+        loop {
+            let frame = self.get_frame(curr_scope);
+
+            if let Some(value) = frame.variables.get(name) {
+                return Some(value);
+            }
+            curr_scope = frame.parent_key.clone()?;
+        }
+    }
+
+    pub fn insert_type(&mut self, name: Name, def: TypeDef) {
+        if let Some(frame) = self.stack.get_mut(&self.scope_key()) {
+            frame.types.insert(name, def);
+        }
+    }
+
+    // Type definitions are visible from every enclosing scope
+    pub fn search_type(&self, name: &Name) -> Option<&TypeDef> {
+        let mut curr_scope = self.scope_key();
+
+        loop {
+            let frame = self.get_frame(curr_scope);
+
+            if let Some(def) = frame.types.get(name) {
+                return Some(def);
+            }
+            curr_scope = frame.parent_key.clone()?;
+        }
+    }
+
+    // Find the enum declaring a constructor, along with the constructor's payload types
+    pub fn search_constructor(&self, name: &Name) -> Option<(Name, Vec<Type>)> {
+        let mut curr_scope = self.scope_key();
+
+        loop {
+            let frame = self.get_frame(curr_scope);
+
+            for (enum_name, def) in &frame.types {
+                if let TypeDef::Enum(constructors) = def {
+                    if let Some((_, payload)) = constructors.iter().find(|(c, _)| c == name) {
+                        return Some((enum_name.clone(), payload.clone()));
+                    }
+                }
+            }
+            curr_scope = frame.parent_key.clone()?;
+        }
+    }
+
+    pub fn insert_function(&mut self, func: Function) {
+        let key = self.binding_key(&func.name);
+        if let Some(frame) = self.stack.get_mut(&key) {
+            frame.functions.insert(func.name.clone(), func);
+        }
+    }
+
+    // The definition of the function a name refers to, unless the nearest
+    // binding of the name is some other variable
+    pub fn search_function(&self, name: &Name) -> Option<&Function> {
+        let mut curr_scope = self.binding_key(name);
+
+        loop {
+            let frame = self.get_frame(curr_scope);
+
+            if frame.variables.contains_key(name) {
+                return frame.functions.get(name);
+            }
+            curr_scope = frame.parent_key.clone()?;
+        }
+    }
+
     pub fn insert_test(&mut self, name: Name, test: Function) -> () {
         if let Some(frame) = self.stack.get_mut(&self.scope_key()) {
             frame.tests.insert(name, test);
@@ -104,20 +268,121 @@ impl<A> Environment<A> {
 #[derive(Clone, Debug, PartialEq)]
 pub struct Function {
     pub name: Name,
+    pub type_params: Vec<Name>, // generic type parameters, e.g. 'T' in 'def first[T](...)'
     pub kind: Option<Type>,
     pub params: Option<Vec<(Name, Type)>>,
+    pub variadic: bool, // the last parameter collects extra arguments, e.g. 'msgs' in 'def log(*msgs: TString)'
+    pub defaults: Vec<(Name, Expression)>, // default values, e.g. 'y' in 'def f(x: TInteger, y: TInteger = 1)'
     pub body: Option<Box<Statement>>,
 }
 
+// A number of arguments, e.g. '1 argument' or '2 arguments'
+pub fn count_arguments(count: usize) -> String {
+    match count {
+        1 => "1 argument".to_string(),
+        _ => format!("{} arguments", count),
+    }
+}
+
 impl Function {
     pub fn new() -> Function {
         return Function {
             name: "__main__".to_string(),
+            type_params: vec![],
             kind: None,
             params: None,
+            variadic: false,
+            defaults: vec![],
             body: None,
         };
     }
+
+    // Functions declared '-> TVoid', or without a return type, may end without a 'return'
+    pub fn returns_void(&self) -> bool {
+        matches!(self.kind, None | Some(Type::TVoid))
+    }
+
+    // Match the arguments of a call to the parameters: positional arguments in
+    // order, then keyword arguments by name. Parameters left without an
+    // argument are None, and take their default value. Extra positional
+    // arguments are returned apart, for a variadic parameter.
+    pub fn bind_arguments<A>(&self, args: Arguments<A>) -> Result<BoundArguments<A>, String> {
+        let mut params = self.params.clone().unwrap_or_default();
+        if self.variadic {
+            params.pop();
+        }
+        let mut bound: Vec<(Name, Option<A>)> =
+            params.into_iter().map(|(name, _)| (name, None)).collect();
+        let (expected, found) = (bound.len(), args.len());
+        let mut extra = vec![];
+        let mut keywords = false;
+        let mut position = 0;
+
+        for (keyword, arg) in args {
+            let slot = match keyword {
+                None if keywords => {
+                    return Err(format!(
+                        "'{}()' has a positional argument after keyword arguments.",
+                        self.name
+                    ))
+                }
+                None => {
+                    position += 1;
+                    match bound.get_mut(position - 1) {
+                        Some(slot) => slot,
+                        None if self.variadic => {
+                            extra.push(arg);
+                            continue;
+                        }
+                        None => {
+                            return Err(format!(
+                                "'{}()' expected {}, found {}.",
+                                self.name,
+                                count_arguments(expected),
+                                found
+                            ))
+                        }
+                    }
+                }
+                Some(keyword) => {
+                    keywords = true;
+                    match bound.iter_mut().find(|(name, _)| *name == keyword) {
+                        Some(slot) if slot.1.is_some() => {
+                            return Err(format!(
+                                "'{}()' got multiple values for argument '{}'.",
+                                self.name, keyword
+                            ))
+                        }
+                        Some(slot) => slot,
+                        None => {
+                            return Err(format!(
+                                "'{}()' got an unexpected keyword argument '{}'.",
+                                self.name, keyword
+                            ))
+                        }
+                    }
+                }
+            };
+            slot.1 = Some(arg);
+        }
+
+        for (name, arg) in &bound {
+            if arg.is_none() && !self.defaults.iter().any(|(param, _)| param == name) {
+                if !keywords && self.defaults.is_empty() {
+                    let bound = if self.variadic { "at least " } else { "" };
+                    return Err(format!(
+                        "'{}()' expected {}{}, found {}.",
+                        self.name,
+                        bound,
+                        count_arguments(expected),
+                        found
+                    ));
+                }
+                return Err(format!("'{}()' missing argument '{}'.", self.name, name));
+            }
+        }
+        Ok((bound, extra))
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -147,6 +412,38 @@ pub enum Type {
     TMaybe(Box<Type>),
     TResult(Box<Type>, Box<Type>), // Ok, Error
     TAny,
+    TVar(Name),  // type variable, solved by unification in the type checker
+    TUser(Name), // user-declared type, compared by name
+    TError,      // type of an expression that failed to check, compatible with any type
+}
+
+// Definition of a user-declared type
+#[derive(Clone, Debug, PartialEq)]
+pub enum TypeDef {
+    Struct(Vec<(Name, Type)>),    // Fields
+    Enum(Vec<(Name, Vec<Type>)>), // Constructors and their payload types
+}
+
+// Pattern of a 'case' arm in a 'match' statement
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pattern {
+    Constructor(Name, Vec<Pattern>), // e.g. 'Circle(r)', 'Just(_)' or 'Nothing'
+    Binding(Name),
+    Wildcard,
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pattern::Constructor(name, args) if args.is_empty() => write!(f, "{}", name),
+            Pattern::Constructor(name, args) => {
+                let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+                write!(f, "{}({})", name, args.join(", "))
+            }
+            Pattern::Binding(name) => write!(f, "{}", name),
+            Pattern::Wildcard => write!(f, "_"),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -164,6 +461,18 @@ pub enum Expression {
 
     /* function call */
     FuncCall(Name, Vec<Expression>),
+    KeywordArg(Name, Box<Expression>), // Named argument of a call, e.g. 'y=2' in 'f(y=2)'
+    Unpack(Box<Expression>),           // Spreads a list into the arguments of a call, e.g. 'f(*xs)'
+    Call(Box<Expression>, Vec<Expression>), // Any expression of function type, e.g. 'f(x)(y)'
+
+    /* anonymous function, its body is a single return statement */
+    Lambda(Box<Function>),
+
+    /* match expression, its arms are expressions */
+    Match(Box<Expression>, Vec<(Pattern, Expression)>),
+
+    /* record field access */
+    FieldAccess(Box<Expression>, Name),
 
     /* arithmetic expressions over numbers */
     Add(Box<Expression>, Box<Expression>),
@@ -194,17 +503,30 @@ pub enum Expression {
     IsError(Box<Expression>),
     IsNothing(Box<Expression>),
     Propagate(Box<Expression>),
+
+    /* combinators over maybe and result values */
+    Map(Box<Expression>, Box<Expression>), // Value, function
+    AndThen(Box<Expression>, Box<Expression>), // Value, function
+    OrElse(Box<Expression>, Box<Expression>), // Value, function
+    UnwrapOr(Box<Expression>, Box<Expression>), // Value, default
+    OkOr(Box<Expression>, Box<Expression>), // Value, error
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Statement {
-    VarDeclaration(Name),
-    ValDeclaration(Name),
+    VarDeclaration(Name, Option<Type>),
+    ValDeclaration(Name, Option<Type>),
     Assignment(Name, Box<Expression>, Option<Type>),
+    FieldAssignment(Box<Expression>, Name, Box<Expression>), // Record, field, value
+    StructDef(Name, Vec<(Name, Type)>),
+    EnumDef(Name, Vec<(Name, Vec<Type>)>),
+    Match(Box<Expression>, Vec<(Pattern, Statement)>),
     IfThenElse(Box<Expression>, Box<Statement>, Option<Box<Statement>>),
     While(Box<Expression>, Box<Statement>),
     Block(Vec<Statement>),
     Sequence(Box<Statement>, Box<Statement>),
+    Global(Vec<Name>),
+    Nonlocal(Vec<Name>),
     AssertTrue(Box<Expression>, String),
     AssertFalse(Box<Expression>, String),
     AssertEQ(Box<Expression>, Box<Expression>, String),
@@ -214,21 +536,57 @@ pub enum Statement {
     AssertFails(String),
     FuncDef(Function),
     Return(Box<Expression>),
+    Invalid(ParseError), // Placeholder for a statement that failed to parse
+    Located(Location, Box<Statement>), // A statement and where it starts in the source
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum ParseError {
     IndentationError(usize),
-    UnexpectedToken(String),
-    InvalidExpression(String),
+    UnexpectedToken(Location, String, Vec<String>), // Found token, expected one of
+    InvalidExpression(Location, String),
 }
 
-pub fn with_error_context<'a, T>(
-    parser: impl Fn(&'a str) -> IResult<&'a str, T>,
-    _context: &'a str,
-) -> impl Fn(&'a str) -> IResult<&'a str, T> {
-    move |input| {
-        parser(input)
-            .map_err(|_| nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Tag)))
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::IndentationError(line) => {
+                write!(
+                    f,
+                    "[Indentation Error] line {}: inconsistent indentation.",
+                    line
+                )
+            }
+            ParseError::UnexpectedToken(loc, found, expected) => {
+                write!(
+                    f,
+                    "[Syntax Error] line {}, column {}: unexpected {}",
+                    loc.line, loc.column, found
+                )?;
+                if !expected.is_empty() {
+                    write!(f, ", expected one of: {}", expected.join(", "))?;
+                }
+                write!(f, ".")
+            }
+            ParseError::InvalidExpression(loc, found) => write!(
+                f,
+                "[Syntax Error] line {}, column {}: expected an expression, found {}.",
+                loc.line, loc.column, found
+            ),
+        }
     }
 }
+
+// Label the errors of `parser` with what it was expected to parse
+pub fn with_error_context<'a, T, E: ContextError<&'a str>>(
+    mut parser: impl FnMut(&'a str) -> IResult<&'a str, T, E>,
+    context: &'static str,
+) -> impl FnMut(&'a str) -> IResult<&'a str, T, E> {
+    move |input| parser(input).map_err(|err| err.map(|e| E::add_context(input, context, e)))
+}
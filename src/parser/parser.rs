@@ -1,23 +1,141 @@
+use std::cmp::Ordering;
+
 use nom::{
     branch::alt,
     bytes::complete::{tag, take_while, take_while1},
-    character::complete::{char, digit1, line_ending, space0, space1},
-    combinator::{map, map_res, opt, recognize},
-    error::Error,
+    character::complete::{char, digit1, line_ending, satisfy, space0, space1},
+    combinator::{cut, map, map_res, not, opt, peek, recognize, verify},
+    error::{ContextError, ErrorKind, FromExternalError},
     multi::{many0, many1, separated_list0, separated_list1},
-    sequence::{delimited, pair, preceded, tuple},
+    sequence::{delimited, pair, preceded, separated_pair, terminated, tuple},
     IResult,
 };
 
-type ParseResult<'a, T> = IResult<&'a str, T, Error<&'a str>>;
+type ParseResult<'a, T> = IResult<&'a str, T, SyntaxError<'a>>;
+
+// Error threaded through the combinators. Indentation problems are raised as
+// `nom::Err::Failure`, so `alt` does not backtrack past them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError<'a> {
+    pub input: &'a str,
+    pub kind: SyntaxErrorKind,
+    pub expected: Vec<&'static str>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyntaxErrorKind {
+    Nom(ErrorKind),
+    Indentation,
+}
+
+impl<'a> SyntaxError<'a> {
+    fn new(input: &'a str, kind: SyntaxErrorKind) -> Self {
+        SyntaxError {
+            input,
+            kind,
+            expected: vec![],
+        }
+    }
+}
+
+impl<'a> nom::error::ParseError<&'a str> for SyntaxError<'a> {
+    fn from_error_kind(input: &'a str, kind: ErrorKind) -> Self {
+        SyntaxError::new(input, SyntaxErrorKind::Nom(kind))
+    }
+
+    fn append(_input: &'a str, _kind: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    // Keep the alternative that got furthest, merging what both expected on a tie
+    fn or(mut self, other: Self) -> Self {
+        match self.input.len().cmp(&other.input.len()) {
+            Ordering::Less => self,
+            Ordering::Greater => other,
+            Ordering::Equal => {
+                for label in other.expected {
+                    if !self.expected.contains(&label) {
+                        self.expected.push(label);
+                    }
+                }
+                self
+            }
+        }
+    }
+}
+
+impl<'a> ContextError<&'a str> for SyntaxError<'a> {
+    // Only label errors raised right where the labelled parser started
+    fn add_context(input: &'a str, ctx: &'static str, mut other: Self) -> Self {
+        if other.input.len() == input.len() && !other.expected.contains(&ctx) {
+            other.expected.push(ctx);
+        }
+        other
+    }
+}
+
+impl<'a, E> FromExternalError<&'a str, E> for SyntaxError<'a> {
+    fn from_external_error(input: &'a str, kind: ErrorKind, _e: E) -> Self {
+        SyntaxError::new(input, SyntaxErrorKind::Nom(kind))
+    }
+}
+
+impl<'a> SyntaxError<'a> {
+    pub fn to_parse_error(&self, source: &str) -> ParseError {
+        let location = location(source, self.input);
+        match self.kind {
+            SyntaxErrorKind::Indentation => ParseError::IndentationError(location.line),
+            SyntaxErrorKind::Nom(_) if self.expected.contains(&"expression") => {
+                ParseError::InvalidExpression(location, found_token(self.input))
+            }
+            SyntaxErrorKind::Nom(_) => ParseError::UnexpectedToken(
+                location,
+                found_token(self.input),
+                self.expected.iter().map(|e| e.to_string()).collect(),
+            ),
+        }
+    }
+}
+
+// Line and column (both 1-based) of `rest` within the original source
+fn location(source: &str, rest: &str) -> Location {
+    let consumed = &source[..source.len() - rest.len()];
+    Location {
+        line: consumed.matches('\n').count() + 1,
+        column: consumed.len() - consumed.rfind('\n').map_or(0, |i| i + 1) + 1,
+    }
+}
+
+// Describe the token found at the start of `rest`
+fn found_token(rest: &str) -> String {
+    let word: String = rest
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_')
+        .collect();
+
+    match rest.chars().next() {
+        None => "end of input".to_string(),
+        Some('\n') | Some('\r') => "end of line".to_string(),
+        Some(c) if word.is_empty() => format!("'{}'", c),
+        _ => format!("'{}'", word),
+    }
+}
 
 const KEYWORDS: &[&str] = &[
     "if",
+    "elif",
     "else",
     "def",
+    "lambda",
+    "struct",
+    "enum",
+    "match",
+    "case",
     "while",
     "val",
     "var",
+    "global",
+    "nonlocal",
     "return",
     "Ok",
     "Err",
@@ -36,23 +154,25 @@ const KEYWORDS: &[&str] = &[
 
 use crate::ir::ast::Function;
 use crate::ir::ast::Type;
-use crate::ir::ast::{Expression, Name, Statement};
+use crate::ir::ast::{
+    with_error_context, Expression, Location, Name, ParseError, Pattern, Statement,
+};
 
-fn identifier(input: &str) -> IResult<&str, Name> {
-    let (input, id) = take_while1(|c: char| c.is_alphanumeric() || c == '_')(input)?;
+fn identifier(input: &str) -> ParseResult<'_, Name> {
+    let (rest, id) = take_while1(|c: char| c.is_alphanumeric() || c == '_')(input)?;
 
     if KEYWORDS.contains(&id) {
-        return Err(nom::Err::Error(Error {
+        return Err(nom::Err::Error(SyntaxError::new(
             input,
-            code: nom::error::ErrorKind::Tag,
-        }));
+            SyntaxErrorKind::Nom(ErrorKind::Tag),
+        )));
     }
 
-    Ok((input, id.to_string()))
+    Ok((rest, id.to_string()))
 }
 
 // Parse integer literals
-fn integer(input: &str) -> IResult<&str, Expression> {
+fn integer(input: &str) -> ParseResult<'_, Expression> {
     map_res(
         pair(opt(preceded(space0, char('-'))), preceded(space0, digit1)),
         |(sign, digits): (Option<char>, &str)| {
@@ -68,14 +188,14 @@ fn integer(input: &str) -> IResult<&str, Expression> {
 }
 
 //term parser for arithmetic
-fn term(input: &str) -> ParseResult<Expression> {
+fn term(input: &str) -> ParseResult<'_, Expression> {
     let (mut input, mut expr) = factor(input)?;
 
     loop {
-        let op_result = delimited::<_, _, _, _, Error<&str>, _, _, _>(
-            space0::<&str, Error<&str>>,
+        let op_result = delimited::<_, _, _, _, SyntaxError, _, _, _>(
+            space0::<&str, SyntaxError>,
             alt((tag("*"), tag("/"))),
-            space0::<&str, Error<&str>>,
+            space0::<&str, SyntaxError>,
         )(input);
 
         match op_result {
@@ -95,46 +215,44 @@ fn term(input: &str) -> ParseResult<Expression> {
     Ok((input, expr))
 }
 
-//expression parser to include if statements
-fn statement(input: &str) -> IResult<&str, Statement> {
-    let (input, _) = space0(input)?;
+// The block a statement belongs to: the whole source being parsed (used to
+// locate errors), the indentation shared by the block's statements and
+// whether each statement is wrapped with its location
+#[derive(Clone, Copy, Debug)]
+struct Block<'a> {
+    source: &'a str,
+    indent: &'a str,
+    located: bool,
+}
+
+// Parse a single statement (without its indentation) belonging to `block`
+fn statement<'a>(input: &'a str, block: Block<'a>) -> ParseResult<'a, Statement> {
     alt((
-        function_def,
-        if_statement,
-        return_statement,
-        assignment,
-        declaration,
+        |i| function_def(i, block),
+        |i| if_statement(i, block),
+        |i| match_statement(i, block),
+        struct_def,
+        enum_def,
+        |i| return_statement(i, block),
+        outer_declaration,
+        field_assignment,
+        |i| assignment(i, block),
+        |i| declaration(i, block),
     ))(input)
 }
 
 // Parse basic expressions
-fn expression(input: &str) -> IResult<&str, Expression> {
-    alt((
-        boolean_expression,
-        comparison_expression,
-        arithmetic_expression,
-        real,
-        integer,
-        ok_expression,
-        err_expression,
-        just_expression,
-        nothing_expression,
-        unwrap_expression,
-        tryunwrap_expression,
-        iserror_expression,
-        isnothing_expression,
-        string,
-        map(identifier, Expression::Var),
-    ))(input)
+fn expression(input: &str) -> ParseResult<'_, Expression> {
+    boolean_expression(input)
 }
 
 // Parse arithmetic operators (unused)
-//fn operator(input: &str) -> IResult<&str, &str> {
+//fn operator(input: &str) -> ParseResult<'_, &str> {
 //alt((tag("+"), tag("-"), tag("*"), tag("/")))(input)
 //}
 
 // Add comparison operator parsing
-fn comparison_operator(input: &str) -> IResult<&str, &str> {
+fn comparison_operator(input: &str) -> ParseResult<'_, &str> {
     alt((
         tag("=="),
         tag("!="),
@@ -145,35 +263,39 @@ fn comparison_operator(input: &str) -> IResult<&str, &str> {
     ))(input)
 }
 
-// Update expression to handle comparisons
-fn comparison_expression(input: &str) -> IResult<&str, Expression> {
-    let (input, left) = term(input)?;
-    let (input, _) = space0(input)?;
-    let (input, op) = comparison_operator(input)?;
-    let (input, _) = space0(input)?;
-    let (input, right) = term(input)?;
-    Ok((
-        input,
-        match op {
+// Parse a relational expression, or fall back to a plain arithmetic one
+fn comparison_expression(input: &str) -> ParseResult<'_, Expression> {
+    let (input, left) = arithmetic_expression(input)?;
+    let (input, rest) = opt(tuple((
+        delimited(space0, comparison_operator, space0),
+        arithmetic_expression,
+    )))(input)?;
+
+    let expr = match rest {
+        None => left,
+        Some((op, right)) => match op {
             ">" => Expression::GT(Box::new(left), Box::new(right)),
             "<" => Expression::LT(Box::new(left), Box::new(right)),
             ">=" => Expression::GTE(Box::new(left), Box::new(right)),
             "<=" => Expression::LTE(Box::new(left), Box::new(right)),
             "==" => Expression::EQ(Box::new(left), Box::new(right)),
+            "!=" => Expression::Not(Box::new(Expression::EQ(Box::new(left), Box::new(right)))),
             _ => unreachable!(),
         },
-    ))
+    };
+
+    Ok((input, expr))
 }
 
 // Parse expressions with operator precedence
-fn arithmetic_expression(input: &str) -> ParseResult<Expression> {
+fn arithmetic_expression(input: &str) -> ParseResult<'_, Expression> {
     let (mut input, mut expr) = term(input)?;
 
     loop {
-        let op_result = delimited::<_, _, _, _, Error<&str>, _, _, _>(
-            space0::<&str, Error<&str>>,
+        let op_result = delimited::<_, _, _, _, SyntaxError, _, _, _>(
+            space0::<&str, SyntaxError>,
             alt((tag("+"), tag("-"))),
-            space0::<&str, Error<&str>>,
+            space0::<&str, SyntaxError>,
         )(input);
 
         match op_result {
@@ -197,7 +319,7 @@ fn arithmetic_expression(input: &str) -> ParseResult<Expression> {
 use nom::character::complete::char as char_parser;
 
 // Parse boolean literals
-fn boolean(input: &str) -> IResult<&str, Expression> {
+fn boolean(input: &str) -> ParseResult<'_, Expression> {
     alt((
         map(tag("True"), |_| Expression::CTrue),
         map(tag("False"), |_| Expression::CFalse),
@@ -205,7 +327,7 @@ fn boolean(input: &str) -> IResult<&str, Expression> {
 }
 
 // Parse real numbers
-fn real(input: &str) -> IResult<&str, Expression> {
+fn real(input: &str) -> ParseResult<'_, Expression> {
     map_res(
         recognize(tuple((opt(char('-')), digit1, char('.'), digit1))),
         |num_str: &str| num_str.parse::<f64>().map(Expression::CReal),
@@ -213,7 +335,7 @@ fn real(input: &str) -> IResult<&str, Expression> {
 }
 
 // Parse strings
-fn string(input: &str) -> IResult<&str, Expression> {
+fn string(input: &str) -> ParseResult<'_, Expression> {
     delimited(
         char_parser('"'),
         map(take_while(|c| c != '"'), |s: &str| {
@@ -223,7 +345,7 @@ fn string(input: &str) -> IResult<&str, Expression> {
     )(input)
 }
 
-fn ok_expression(input: &str) -> IResult<&str, Expression> {
+fn ok_expression(input: &str) -> ParseResult<'_, Expression> {
     let (input, _) = tag("Ok")(input)?;
     let (input, _) = space0(input)?;
     let (input, expr) = delimited(
@@ -235,7 +357,7 @@ fn ok_expression(input: &str) -> IResult<&str, Expression> {
     Ok((input, Expression::COk(Box::new(expr))))
 }
 
-fn err_expression(input: &str) -> IResult<&str, Expression> {
+fn err_expression(input: &str) -> ParseResult<'_, Expression> {
     let (input, _) = tag("Err")(input)?;
     let (input, _) = space0(input)?;
     let (input, expr) = delimited(
@@ -247,7 +369,7 @@ fn err_expression(input: &str) -> IResult<&str, Expression> {
     Ok((input, Expression::CErr(Box::new(expr))))
 }
 
-fn just_expression(input: &str) -> IResult<&str, Expression> {
+fn just_expression(input: &str) -> ParseResult<'_, Expression> {
     let (input, _) = tag("Just")(input)?;
     let (input, _) = space0(input)?;
     let (input, expr) = delimited(
@@ -258,11 +380,11 @@ fn just_expression(input: &str) -> IResult<&str, Expression> {
     Ok((input, Expression::CJust(Box::new(expr))))
 }
 
-fn nothing_expression(input: &str) -> IResult<&str, Expression> {
+fn nothing_expression(input: &str) -> ParseResult<'_, Expression> {
     map(tag("Nothing"), |_| Expression::CNothing)(input)
 }
 
-fn isnothing_expression(input: &str) -> IResult<&str, Expression> {
+fn isnothing_expression(input: &str) -> ParseResult<'_, Expression> {
     let (input, _) = tag("isNothing")(input)?;
     let (input, _) = space0(input)?;
 
@@ -275,7 +397,7 @@ fn isnothing_expression(input: &str) -> IResult<&str, Expression> {
     Ok((input, Expression::IsNothing(Box::new(expr))))
 }
 
-fn iserror_expression(input: &str) -> IResult<&str, Expression> {
+fn iserror_expression(input: &str) -> ParseResult<'_, Expression> {
     let (input, _) = tag("isError")(input)?;
     let (input, _) = space0(input)?;
     let (input, expr) = delimited(
@@ -287,7 +409,7 @@ fn iserror_expression(input: &str) -> IResult<&str, Expression> {
     Ok((input, Expression::IsError(Box::new(expr))))
 }
 
-fn unwrap_expression(input: &str) -> IResult<&str, Expression> {
+fn unwrap_expression(input: &str) -> ParseResult<'_, Expression> {
     let (input, _) = tag("unwrap")(input)?;
     let (input, _) = space0(input)?;
     let (input, expr) = delimited(
@@ -299,7 +421,30 @@ fn unwrap_expression(input: &str) -> IResult<&str, Expression> {
     Ok((input, Expression::Unwrap(Box::new(expr))))
 }
 
-fn tryunwrap_expression(input: &str) -> IResult<&str, Expression> {
+// Parse a combinator over maybe and result values, called as a method of its
+// value, e.g. '.map(double)' or '.unwrap_or(0)'. The names are not reserved.
+fn combinator_call(input: &str) -> ParseResult<'_, Suffix<'_>> {
+    let (input, name) = preceded(
+        char('.'),
+        alt((
+            keyword("map"),
+            keyword("and_then"),
+            keyword("or_else"),
+            keyword("unwrap_or"),
+            keyword("ok_or"),
+        )),
+    )(input)?;
+    let (input, _) = space0(input)?;
+    let (input, arg) = delimited(
+        pair(char('('), space0),
+        cut(expression),
+        cut(pair(space0, with_error_context(char(')'), "')'"))),
+    )(input)?;
+
+    Ok((input, Suffix::Combinator(name, arg)))
+}
+
+fn tryunwrap_expression(input: &str) -> ParseResult<'_, Expression> {
     let (input, _) = tag("tryUnwrap")(input)?;
     let (input, _) = space0(input)?;
     let (input, expr) = delimited(
@@ -311,143 +456,379 @@ fn tryunwrap_expression(input: &str) -> IResult<&str, Expression> {
     Ok((input, Expression::Propagate(Box::new(expr))))
 }
 
-// Parse boolean operations
-fn boolean_expression(input: &str) -> IResult<&str, Expression> {
-    let (input, first) = boolean_term(input)?;
-    let (input, rest) = many0(tuple((
-        delimited(space0, alt((tag("and"), tag("or"))), space0),
-        boolean_term,
-    )))(input)?;
+// Parse boolean operations ('or' binds looser than 'and')
+fn boolean_expression(input: &str) -> ParseResult<'_, Expression> {
+    let (input, first) = and_expression(input)?;
+    let (input, rest) = many0(preceded(
+        delimited(space0, keyword("or"), space0),
+        and_expression,
+    ))(input)?;
 
     Ok((
         input,
-        rest.into_iter().fold(first, |acc, (op, val)| match op {
-            "and" => Expression::And(Box::new(acc), Box::new(val)),
-            "or" => Expression::Or(Box::new(acc), Box::new(val)),
-            _ => unreachable!(),
+        rest.into_iter().fold(first, |acc, val| {
+            Expression::Or(Box::new(acc), Box::new(val))
         }),
     ))
 }
 
-fn boolean_term(input: &str) -> IResult<&str, Expression> {
-    alt((
-        map(preceded(tag("not "), boolean_factor), |expr| {
-            Expression::Not(Box::new(expr))
+fn and_expression(input: &str) -> ParseResult<'_, Expression> {
+    let (input, first) = boolean_term(input)?;
+    let (input, rest) = many0(preceded(
+        delimited(space0, keyword("and"), space0),
+        boolean_term,
+    ))(input)?;
+
+    Ok((
+        input,
+        rest.into_iter().fold(first, |acc, val| {
+            Expression::And(Box::new(acc), Box::new(val))
         }),
-        boolean_factor,
-    ))(input)
+    ))
 }
 
-fn boolean_factor(input: &str) -> IResult<&str, Expression> {
+fn boolean_term(input: &str) -> ParseResult<'_, Expression> {
     alt((
-        boolean,
-        comparison_expression,
-        unwrap_expression,
-        tryunwrap_expression,
-        iserror_expression,
-        isnothing_expression,
-        delimited(
-            tuple((char('('), space0)),
-            boolean_expression,
-            tuple((space0, char(')'))),
+        map(
+            preceded(pair(keyword("not"), space0), boolean_term),
+            |expr| Expression::Not(Box::new(expr)),
         ),
+        comparison_expression,
     ))(input)
 }
 
-fn factor(input: &str) -> IResult<&str, Expression> {
-    alt((
-        delimited(
-            tuple((char('('), space0)),
-            arithmetic_expression,
-            tuple((space0, char(')'))),
-        ),
-        function_call,
-        ok_expression,
-        err_expression,
-        just_expression,
-        nothing_expression,
-        unwrap_expression,
-        tryunwrap_expression,
-        iserror_expression,
-        isnothing_expression,
-        real,
-        integer,
-        map(tuple((char('-'), space0, factor)), |(_, _, expr)| {
-            Expression::Mul(Box::new(Expression::CInt(-1)), Box::new(expr))
-        }),
-        map(identifier, Expression::Var),
-    ))(input)
+// Match a keyword only when it is not the prefix of a longer identifier
+fn keyword<'a>(kw: &'static str) -> impl FnMut(&'a str) -> ParseResult<'a, &'a str> {
+    terminated(
+        tag(kw),
+        not(peek(satisfy(|c: char| c.is_alphanumeric() || c == '_'))),
+    )
 }
 
-//indented block parser
-fn indented_block(input: &str) -> IResult<&str, Vec<Statement>> {
-    let (input, _) = line_ending(input)?;
-    let (input, statements) = separated_list1(
-        line_ending,
-        preceded(
-            space1, // Require at least one space for indentation
-            statement,
+// What may follow a factor: a '.field' access, a call, e.g. 'f(x)(y)', or a
+// combinator such as '.map(f)'
+enum Suffix<'a> {
+    Field(Name),
+    Call(Vec<Expression>),
+    Combinator(&'a str, Expression),
+}
+
+// Parse a factor, followed by any number of '.field' accesses and calls
+fn factor(input: &str) -> ParseResult<'_, Expression> {
+    let (input, expr) = primary(input)?;
+    let (input, suffixes) = many0(alt((
+        combinator_call,
+        map(
+            preceded(char('.'), cut(with_error_context(identifier, "field name"))),
+            Suffix::Field,
         ),
-    )(input)?;
+        map(call_arguments, Suffix::Call),
+    )))(input)?;
+
+    let expr = suffixes
+        .into_iter()
+        .fold(expr, |callee, suffix| match suffix {
+            Suffix::Field(field) => Expression::FieldAccess(Box::new(callee), field),
+            Suffix::Call(args) => Expression::Call(Box::new(callee), args),
+            Suffix::Combinator(name, arg) => {
+                let (value, arg) = (Box::new(callee), Box::new(arg));
+                match name {
+                    "map" => Expression::Map(value, arg),
+                    "and_then" => Expression::AndThen(value, arg),
+                    "or_else" => Expression::OrElse(value, arg),
+                    "unwrap_or" => Expression::UnwrapOr(value, arg),
+                    "ok_or" => Expression::OkOr(value, arg),
+                    _ => unreachable!(),
+                }
+            }
+        });
+    Ok((input, expr))
+}
+
+fn primary(input: &str) -> ParseResult<'_, Expression> {
+    with_error_context(
+        alt((
+            delimited(
+                tuple((char('('), space0)),
+                expression,
+                tuple((space0, with_error_context(char(')'), "')'"))),
+            ),
+            lambda_expression,
+            function_call,
+            ok_expression,
+            err_expression,
+            just_expression,
+            nothing_expression,
+            unwrap_expression,
+            tryunwrap_expression,
+            iserror_expression,
+            isnothing_expression,
+            boolean,
+            string,
+            real,
+            integer,
+            map(tuple((char('-'), space0, factor)), |(_, _, expr)| {
+                Expression::Mul(Box::new(Expression::CInt(-1)), Box::new(expr))
+            }),
+            map(identifier, Expression::Var),
+        )),
+        "expression",
+    )(input)
+}
+
+// Parse a '#' comment up to (but not including) the line ending
+fn comment(input: &str) -> ParseResult<'_, &str> {
+    recognize(pair(char('#'), take_while(|c| c != '\n' && c != '\r')))(input)
+}
+
+// Parse the end of a line, skipping trailing whitespace and comments
+fn end_of_line(input: &str) -> ParseResult<'_, ()> {
+    map(tuple((space0, opt(comment), line_ending)), |_| ())(input)
+}
+
+// Parse one or more line endings, including blank and comment-only lines
+fn line_breaks(input: &str) -> ParseResult<'_, ()> {
+    map(many1(end_of_line), |_| ())(input)
+}
+
+fn indentation_error(input: &str) -> nom::Err<SyntaxError<'_>> {
+    nom::Err::Failure(SyntaxError::new(input, SyntaxErrorKind::Indentation))
+}
+
+// Parse the leading whitespace of a line, rejecting a mix of tabs and spaces
+fn indentation(input: &str) -> ParseResult<'_, &str> {
+    let (rest, indent) = take_while(|c| c == ' ' || c == '\t')(input)?;
+    if indent.contains(' ') && indent.contains('\t') {
+        return Err(indentation_error(input));
+    }
+    Ok((rest, indent))
+}
+
+// Match a line indented by exactly `indent`
+fn exact_indentation<'a>(input: &'a str, indent: &str) -> ParseResult<'a, ()> {
+    let (rest, found) = indentation(input)?;
+    if found != indent {
+        return Err(nom::Err::Error(SyntaxError::new(
+            input,
+            SyntaxErrorKind::Nom(ErrorKind::Space),
+        )));
+    }
+    Ok((rest, ()))
+}
+
+// Parse consecutive statements that all start exactly at the block's
+// indentation. A line indented like an enclosing block (a dedent) ends the
+// sequence. A statement that fails to parse is replaced by a
+// `Statement::Invalid` placeholder and parsing resumes at the next line that
+// is not indented deeper than the block.
+fn statement_sequence<'a>(mut input: &'a str, block: Block<'a>) -> ParseResult<'a, Vec<Statement>> {
+    let mut statements = vec![];
+
+    loop {
+        match block_statement(input, block) {
+            Ok((rest, stmt)) => {
+                statements.push(stmt);
+                input = rest;
+            }
+            Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+                statements.push(Statement::Invalid(e.to_parse_error(block.source)));
+                input = skip_statement(input, block.indent);
+            }
+            Err(e) => return Err(e),
+        }
+
+        let next = match line_breaks(input) {
+            Ok((next, _)) => next,
+            Err(_) => break,
+        };
+        // A comment left over here is the last line of the file
+        let (after, found) = leading_whitespace(next);
+        let at_end = after.is_empty() || after.starts_with('#');
+        if at_end || (found.len() < block.indent.len() && block.indent.starts_with(found)) {
+            break;
+        }
+        input = next;
+    }
+
     Ok((input, statements))
 }
 
-fn if_statement(input: &str) -> IResult<&str, Statement> {
+// Parse one line of a block: its indentation, a statement and nothing else
+// but a comment up to the end of the line
+fn block_statement<'a>(input: &'a str, block: Block<'a>) -> ParseResult<'a, Statement> {
+    let (rest, found) = indentation(input)?;
+    if found != block.indent {
+        return Err(indentation_error(input));
+    }
+    let start = rest;
+    let (rest, mut stmt) = with_error_context(|i| statement(i, block), "statement")(rest)?;
+    if block.located {
+        stmt = Statement::Located(location(block.source, start), Box::new(stmt));
+    }
+
+    let (after, _) = space0(rest)?;
+    if !(after.is_empty() || after.starts_with(['#', '\n', '\r'])) {
+        return Err(nom::Err::Error(SyntaxError {
+            input: after,
+            kind: SyntaxErrorKind::Nom(ErrorKind::Eof),
+            expected: vec!["end of line"],
+        }));
+    }
+    Ok((rest, stmt))
+}
+
+// Skip the line starting at `input` and every following line indented deeper
+// than `indent`, stopping right before the last skipped line break
+fn skip_statement<'a>(input: &'a str, indent: &str) -> &'a str {
+    let mut end = &input[input.find(['\n', '\r']).unwrap_or(input.len())..];
+
+    while let Ok((next, _)) = line_breaks(end) {
+        let (after, found) = leading_whitespace(next);
+        if after.is_empty() || after.starts_with('#') || found.len() <= indent.len() {
+            break;
+        }
+        end = &next[next.find(['\n', '\r']).unwrap_or(next.len())..];
+    }
+    end
+}
+
+fn leading_whitespace(input: &str) -> (&str, &str) {
+    let len = input.len() - input.trim_start_matches([' ', '\t']).len();
+    (&input[len..], &input[..len])
+}
+
+// Parse a block that must be indented deeper than its header line
+fn indented_block<'a>(input: &'a str, parent: Block<'a>) -> ParseResult<'a, Vec<Statement>> {
+    let (input, _) = line_breaks(input)?;
+    let (_, indent) = indentation(input)?;
+    if indent.len() <= parent.indent.len() || !indent.starts_with(parent.indent) {
+        return Err(indentation_error(input)); // Expected an indented block
+    }
+    statement_sequence(input, Block { indent, ..parent })
+}
+
+fn if_statement<'a>(input: &'a str, block: Block<'a>) -> ParseResult<'a, Statement> {
     let (input, _) = tag("if")(input)?;
     let (input, _) = space1(input)?;
-    let (input, condition) = alt((
-        comparison_expression,
-        boolean_expression,
-        map(identifier, Expression::Var),
-    ))(input)?;
+    let (input, condition) = cut(expression)(input)?;
     let (input, _) = space0(input)?;
-    let (input, _) = char(':')(input)?;
-    let (input, then_block) = indented_block(input)?;
+    let (input, _) = cut(with_error_context(char(':'), "':'"))(input)?;
+    let (input, then_block) = cut(|i| indented_block(i, block))(input)?;
+
+    let (input, elif_blocks) = many0(tuple((
+        preceded(
+            tuple((
+                line_breaks,
+                |i| exact_indentation(i, block.indent),
+                tag("elif"),
+                space1,
+            )),
+            cut(expression),
+        ),
+        cut(preceded(
+            tuple((space0, with_error_context(char(':'), "':'"))),
+            |i| indented_block(i, block),
+        )),
+    )))(input)?;
 
     let (input, else_block) = opt(preceded(
-        tuple((line_ending, space0, tag("else"), char(':'))),
-        indented_block,
+        tuple((
+            line_breaks,
+            |i| exact_indentation(i, block.indent),
+            tag("else"),
+        )),
+        cut(preceded(
+            tuple((space0, with_error_context(char(':'), "':'"))),
+            |i| indented_block(i, block),
+        )),
     ))(input)?;
 
+    // Desugar 'elif' chains into nested IfThenElse statements
+    let else_stmt = elif_blocks.into_iter().rev().fold(
+        else_block.map(|stmts| Box::new(Statement::Block(stmts))),
+        |acc, (cond, stmts)| {
+            Some(Box::new(Statement::IfThenElse(
+                Box::new(cond),
+                Box::new(Statement::Block(stmts)),
+                acc,
+            )))
+        },
+    );
+
     Ok((
         input,
         Statement::IfThenElse(
             Box::new(condition),
             Box::new(Statement::Block(then_block)),
-            else_block.map(|stmts| Box::new(Statement::Block(stmts))),
+            else_stmt,
         ),
     ))
 }
 
-fn declaration(input: &str) -> IResult<&str, Statement> {
-    let (input, keyword) = alt((tag("var"), tag("val")))(input)?;
+// Parse 'global x, y' or 'nonlocal x', making assignments write to an enclosing scope
+fn outer_declaration(input: &str) -> ParseResult<'_, Statement> {
+    let (input, kind) = alt((keyword("global"), keyword("nonlocal")))(input)?;
     let (input, _) = space1(input)?;
-    let (input, name) = identifier(input)?;
+    let (input, names) = cut(separated_list1(
+        comma,
+        with_error_context(identifier, "variable name"),
+    ))(input)?;
 
-    Ok((
-        input,
-        match keyword {
-            "var" => Statement::VarDeclaration(name), // No Box needed
-            "val" => Statement::ValDeclaration(name), // No Box needed
-            _ => unreachable!(),
-        },
-    ))
+    match kind {
+        "global" => Ok((input, Statement::Global(names))),
+        _ => Ok((input, Statement::Nonlocal(names))),
+    }
+}
+
+// Parse 'var'/'val' declarations with an optional type annotation and initializer
+fn declaration<'a>(input: &'a str, block: Block<'a>) -> ParseResult<'a, Statement> {
+    let (input, kind) = alt((keyword("var"), keyword("val")))(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = cut(with_error_context(identifier, "variable name"))(input)?;
+    let (input, annotation) = opt(preceded(
+        tuple((space0, char(':'), space0)),
+        cut(type_annotation),
+    ))(input)?;
+
+    let (input, value) = opt(preceded(
+        delimited(space0, char('='), space0),
+        cut(|i| assigned_value(i, block)),
+    ))(input)?;
+
+    let declaration = match kind {
+        "var" => Statement::VarDeclaration(name.clone(), annotation.clone()),
+        "val" => Statement::ValDeclaration(name.clone(), annotation.clone()),
+        _ => unreachable!(),
+    };
+
+    match value {
+        Some(expr) => {
+            let kind = annotation.or_else(|| literal_type(&expr));
+            Ok((
+                input,
+                Statement::Sequence(
+                    Box::new(declaration),
+                    Box::new(Statement::Assignment(name, Box::new(expr), kind)),
+                ),
+            ))
+        }
+        None => Ok((input, declaration)),
+    }
+}
+
+// Parse the value of an assignment or return, which may span several lines
+// when it is a match expression
+fn assigned_value<'a>(input: &'a str, block: Block<'a>) -> ParseResult<'a, Expression> {
+    alt((|i| match_expression(i, block), expression))(input)
 }
 
 // Parse assignment statements
-fn assignment(input: &str) -> IResult<&str, Statement> {
+fn assignment<'a>(input: &'a str, block: Block<'a>) -> ParseResult<'a, Statement> {
     let (input, name) = identifier(input)?;
-    let (input, _) = delimited(space0, char('='), space0)(input)?;
-    let (input, expr) = expression(input)?;
+    let (input, _) = delimited(space0, with_error_context(char('='), "'='"), space0)(input)?;
+    let (input, expr) = cut(|i| assigned_value(i, block))(input)?;
 
-    // Infer type from expression
-    let inferred_type = match &expr {
-        Expression::CInt(_) => Some(Type::TInteger),
-        Expression::CReal(_) => Some(Type::TReal),
-        Expression::CString(_) => Some(Type::TString),
-        Expression::CTrue | Expression::CFalse => Some(Type::TBool),
-        _ => None,
-    };
+    let inferred_type = literal_type(&expr);
 
     Ok((
         input,
@@ -455,124 +836,638 @@ fn assignment(input: &str) -> IResult<&str, Statement> {
     ))
 }
 
-fn parse_type(type_name: &str) -> Type {
-    match type_name {
-        "TInteger" => Type::TInteger,
-        "TBool" => Type::TBool,
-        "TReal" => Type::TReal,
-        _ => Type::TInteger, // Default case
-    }
-}
-
-// function definition parsing
-fn function_def(input: &str) -> IResult<&str, Statement> {
-    let (input, _) = tag("def")(input)?;
-    let (input, _) = space1(input)?;
+// Parse assignments to record fields, e.g. 'p.x = 1.0' or 'line.start.y = 0.0'
+fn field_assignment(input: &str) -> ParseResult<'_, Statement> {
     let (input, name) = identifier(input)?;
-    let (input, _) = char('(')(input)?;
-    let (input, params) = separated_list0(
-        delimited(space0, char(','), space0),
-        tuple((
-            identifier,
-            preceded(tuple((space0, char(':'), space0)), identifier),
-        )),
-    )(input)?;
-    let (input, _) = char(')')(input)?;
-    let (input, _) = space0(input)?;
-    let (input, _) = tag("->")(input)?;
-    let (input, _) = space0(input)?;
-    let (input, return_type) = identifier(input)?;
-    let (input, _) = char(':')(input)?;
-    let (input, body) = indented_block(input)?;
+    let (input, mut fields) = many1(preceded(char('.'), identifier))(input)?;
+    let (input, _) = delimited(space0, with_error_context(char('='), "'='"), space0)(input)?;
+    let (input, expr) = cut(expression)(input)?;
+
+    let field = fields.pop().unwrap();
+    let record = fields
+        .into_iter()
+        .fold(Expression::Var(name), |record, field| {
+            Expression::FieldAccess(Box::new(record), field)
+        });
 
     Ok((
         input,
-        Statement::FuncDef(Function {
-            name: name.clone(),                   // Provide the name field
-            kind: Some(parse_type(&return_type)), // Wrap in Some
-            params: Some(
-                params
-                    .into_iter()
-                    .map(|(name, type_name)| (name, parse_type(&type_name)))
-                    .collect(),
-            ),
-            body: Some(Box::new(Statement::Block(body))), // Wrap in Some
-        }),
+        Statement::FieldAssignment(Box::new(record), field, Box::new(expr)),
     ))
 }
 
-//return statement parsing
-fn return_statement(input: &str) -> IResult<&str, Statement> {
-    let (input, _) = tag("return")(input)?;
+// Parse record type declarations, e.g. 'struct Point: x: TReal, y: TReal'
+fn struct_def(input: &str) -> ParseResult<'_, Statement> {
+    let (input, _) = keyword("struct")(input)?;
     let (input, _) = space1(input)?;
-    let (input, expr) = expression(input)?;
-    Ok((input, Statement::Return(Box::new(expr))))
+    let (input, name) = cut(with_error_context(user_type_name, "type name"))(input)?;
+    let (input, _) = cut(tuple((
+        space0,
+        with_error_context(char(':'), "':'"),
+        space0,
+    )))(input)?;
+    let (input, fields) = cut(separated_list1(
+        comma,
+        separated_pair(
+            with_error_context(identifier, "field name"),
+            tuple((space0, with_error_context(char(':'), "':'"), space0)),
+            type_annotation,
+        ),
+    ))(input)?;
+
+    Ok((input, Statement::StructDef(name, fields)))
 }
 
-// Parse multiple statements
-pub fn parse_statements(input: &str) -> IResult<&str, Vec<Statement>> {
-    let (input, _) = space0(input)?; // Handle initial whitespace
-    let (input, statements) = separated_list1(
-        many1(tuple((space0, line_ending, space0))), // Require at least one newline
-        statement, // Use statement directly instead of limited alternatives
-    )(input)?;
-    let (input, _) = space0(input)?; // Handle trailing whitespace
-    Ok((input, statements))
+// Parse sum type declarations, e.g. 'enum Shape: Circle(TReal) | Rect(TReal, TReal) | Empty'
+fn enum_def(input: &str) -> ParseResult<'_, Statement> {
+    let (input, _) = keyword("enum")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = cut(with_error_context(user_type_name, "type name"))(input)?;
+    let (input, _) = cut(tuple((
+        space0,
+        with_error_context(char(':'), "':'"),
+        space0,
+    )))(input)?;
+    let (input, constructors) = cut(separated_list1(
+        delimited(space0, char('|'), space0),
+        pair(
+            with_error_context(user_constructor_name, "constructor name"),
+            map(
+                opt(delimited(
+                    pair(char('('), space0),
+                    separated_list1(comma, type_annotation),
+                    pair(space0, with_error_context(char(')'), "')'")),
+                )),
+                Option::unwrap_or_default,
+            ),
+        ),
+    ))(input)?;
+
+    Ok((input, Statement::EnumDef(name, constructors)))
 }
 
-// function call parsing
-fn function_call(input: &str) -> IResult<&str, Expression> {
-    let (input, name) = identifier(input)?;
-    let (input, _) = char('(')(input)?;
-    let (input, args) = separated_list0(delimited(space0, char(','), space0), expression)(input)?;
-    let (input, _) = char(')')(input)?;
+// Constructors are capitalized, including the built-in ones of Maybe and Result
+fn constructor_name(input: &str) -> ParseResult<'_, Name> {
+    alt((
+        map(
+            alt((
+                keyword("Just"),
+                keyword("Nothing"),
+                keyword("Ok"),
+                keyword("Err"),
+            )),
+            String::from,
+        ),
+        user_constructor_name,
+    ))(input)
+}
 
-    Ok((input, Expression::FuncCall(name, args)))
+fn user_constructor_name(input: &str) -> ParseResult<'_, Name> {
+    verify(identifier, |name: &str| {
+        name.chars().next().is_some_and(|c| c.is_uppercase())
+    })(input)
 }
 
-// Main parse function
-pub fn parse(input: &str) -> IResult<&str, Vec<Statement>> {
-    let (input, statements) = parse_statements(input)?;
-    let (input, _) = many0(line_ending)(input)?; // Consume trailing newlines
-    let (input, _) = space0(input)?; // Consume trailing whitespace
-    Ok((input, statements))
+// Parse a match statement, whose 'case' arms are indented deeper than it:
+//
+//   match shape:
+//       case Circle(r):
+//           area = 3.14 * r * r
+//       case Rect(w, h):
+//           area = w * h
+fn match_statement<'a>(input: &'a str, block: Block<'a>) -> ParseResult<'a, Statement> {
+    let (input, (exp, arms)) = match_clauses(input, block, match_arm)?;
+    Ok((input, Statement::Match(Box::new(exp), arms)))
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*; // Import everything from parent module
-    use crate::ir::ast::{Expression, Statement}; // Import AST types
-    #[test]
-    fn test_simple_assignment() {
-        let input = "x = 42";
-        let (rest, stmt) = assignment(input).unwrap();
-        assert_eq!(rest, "");
-        match stmt {
-            Statement::Assignment(name, expr, _type) => {
-                // Added _type
-                assert_eq!(name, "x");
-                match *expr {
-                    Expression::CInt(val) => assert_eq!(val, 42),
-                    _ => panic!("Expected CInt"),
-                }
-            }
-            _ => panic!("Expected Assignment"),
-        }
-    }
+// Parse a match expression, whose arms give its value:
+//
+//   name = match shape:
+//       case Circle(_): "circle"
+//       case _: "polygon"
+fn match_expression<'a>(input: &'a str, block: Block<'a>) -> ParseResult<'a, Expression> {
+    let (input, (exp, arms)) = match_clauses(input, block, match_expression_arm)?;
+    Ok((input, Expression::Match(Box::new(exp), arms)))
+}
 
-    #[test]
+// Parse 'match exp:' followed by its arms, indented deeper than `block`
+fn match_clauses<'a, A>(
+    input: &'a str,
+    block: Block<'a>,
+    arm: fn(&'a str, Block<'a>) -> ParseResult<'a, A>,
+) -> ParseResult<'a, (Expression, Vec<A>)> {
+    let (input, _) = keyword("match")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, exp) = cut(expression)(input)?;
+    let (input, _) = space0(input)?;
+    let (input, _) = cut(with_error_context(char(':'), "':'"))(input)?;
+    let (input, _) = cut(line_breaks)(input)?;
+
+    let (_, indent) = indentation(input)?;
+    if indent.len() <= block.indent.len() || !indent.starts_with(block.indent) {
+        return Err(indentation_error(input)); // Expected indented 'case' arms
+    }
+    let arms_block = Block { indent, ..block };
+
+    let (input, first) = cut(preceded(
+        |i| exact_indentation(i, indent),
+        |i| arm(i, arms_block),
+    ))(input)?;
+    let (input, mut arms) = many0(preceded(
+        pair(line_breaks, |i| exact_indentation(i, indent)),
+        |i| arm(i, arms_block),
+    ))(input)?;
+    arms.insert(0, first);
+
+    Ok((input, (exp, arms)))
+}
+
+fn match_arm<'a>(input: &'a str, block: Block<'a>) -> ParseResult<'a, (Pattern, Statement)> {
+    let (input, _) = with_error_context(keyword("case"), "'case'")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, pattern) = cut(pattern)(input)?;
+    let (input, _) = space0(input)?;
+    let (input, _) = cut(with_error_context(char(':'), "':'"))(input)?;
+    let (input, body) = cut(|i| indented_block(i, block))(input)?;
+
+    Ok((input, (pattern, Statement::Block(body))))
+}
+
+fn match_expression_arm<'a>(
+    input: &'a str,
+    _block: Block<'a>,
+) -> ParseResult<'a, (Pattern, Expression)> {
+    let (input, _) = with_error_context(keyword("case"), "'case'")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, pattern) = cut(pattern)(input)?;
+    let (input, _) = space0(input)?;
+    let (input, _) = cut(with_error_context(char(':'), "':'"))(input)?;
+    let (input, _) = space0(input)?;
+    let (input, exp) = cut(expression)(input)?;
+
+    Ok((input, (pattern, exp)))
+}
+
+// Parse a pattern: '_', a binding such as 'r', or a constructor such as 'Rect(w, _)'
+fn pattern(input: &str) -> ParseResult<'_, Pattern> {
+    with_error_context(
+        alt((
+            map(keyword("_"), |_| Pattern::Wildcard),
+            map(
+                pair(
+                    constructor_name,
+                    opt(delimited(
+                        pair(char('('), space0),
+                        separated_list1(comma, pattern),
+                        pair(space0, with_error_context(char(')'), "')'")),
+                    )),
+                ),
+                |(name, args)| Pattern::Constructor(name, args.unwrap_or_default()),
+            ),
+            // Literals are not patterns, so 'case 1:' is not a binding named '1'
+            map(
+                verify(identifier, |name: &str| {
+                    !name.starts_with(|c: char| c.is_ascii_digit())
+                }),
+                Pattern::Binding,
+            ),
+        )),
+        "pattern",
+    )(input)
+}
+
+// Names of user-declared types; 'T' followed by an uppercase letter is kept
+// for built-in types, so that misspelled ones such as 'TIntger' are reported
+fn user_type_name(input: &str) -> ParseResult<'_, Name> {
+    verify(identifier, |name: &str| {
+        let mut chars = name.chars();
+        chars.next().is_some_and(|c| c.is_uppercase())
+            && !(name.starts_with('T') && chars.next().is_some_and(|c| c.is_uppercase()))
+    })(input)
+}
+
+// Infer type from literal expressions
+fn literal_type(expr: &Expression) -> Option<Type> {
+    match expr {
+        Expression::CInt(_) => Some(Type::TInteger),
+        Expression::CReal(_) => Some(Type::TReal),
+        Expression::CString(_) => Some(Type::TString),
+        Expression::CTrue | Expression::CFalse => Some(Type::TBool),
+        _ => None,
+    }
+}
+
+// Parse a type annotation, e.g. 'TInteger', 'TList[TReal]' or
+// 'TFunction[[TInteger, TBool], TString]' (parameter types, then return type)
+fn type_annotation(input: &str) -> ParseResult<'_, Type> {
+    generic_type_annotation(input, &[])
+}
+
+// Parse a type annotation in which the given type parameters may appear
+fn generic_type_annotation<'a>(input: &'a str, type_params: &[Name]) -> ParseResult<'a, Type> {
+    let inner = |i| generic_type_annotation(i, type_params);
+
+    with_error_context(
+        alt((
+            map(keyword("TInteger"), |_| Type::TInteger),
+            map(keyword("TBool"), |_| Type::TBool),
+            map(keyword("TReal"), |_| Type::TReal),
+            map(keyword("TString"), |_| Type::TString),
+            map(keyword("TVoid"), |_| Type::TVoid),
+            map(keyword("TAny"), |_| Type::TAny),
+            map(preceded(keyword("TList"), type_arguments(inner)), |t| {
+                Type::TList(Box::new(t))
+            }),
+            map(preceded(keyword("TMaybe"), type_arguments(inner)), |t| {
+                Type::TMaybe(Box::new(t))
+            }),
+            map(
+                preceded(
+                    keyword("TResult"),
+                    type_arguments(separated_pair(inner, comma, inner)),
+                ),
+                |(ok, err)| Type::TResult(Box::new(ok), Box::new(err)),
+            ),
+            map(
+                preceded(
+                    keyword("TTuple"),
+                    type_arguments(separated_list1(comma, inner)),
+                ),
+                Type::TTuple,
+            ),
+            map(
+                preceded(
+                    keyword("TFunction"),
+                    type_arguments(separated_pair(
+                        type_arguments(separated_list0(comma, inner)),
+                        comma,
+                        inner,
+                    )),
+                ),
+                |(params, ret)| Type::TFunction(Box::new(Some(ret)), params),
+            ),
+            map(
+                verify(identifier, |name: &str| {
+                    type_params.iter().any(|param| param == name)
+                }),
+                Type::TVar,
+            ),
+            map(user_type_name, Type::TUser),
+        )),
+        "type",
+    )(input)
+}
+
+// Parse the bracketed arguments of a type constructor
+fn type_arguments<'a, T>(
+    parser: impl FnMut(&'a str) -> ParseResult<'a, T>,
+) -> impl FnMut(&'a str) -> ParseResult<'a, T> {
+    delimited(
+        pair(char('['), space0),
+        parser,
+        pair(space0, with_error_context(char(']'), "']'")),
+    )
+}
+
+fn comma(input: &str) -> ParseResult<'_, char> {
+    delimited(space0, char(','), space0)(input)
+}
+
+// function definition parsing
+fn function_def<'a>(input: &'a str, block: Block<'a>) -> ParseResult<'a, Statement> {
+    let (input, _) = tag("def")(input)?;
+    let (input, _) = space1(input)?;
+    let (input, name) = cut(with_error_context(identifier, "function name"))(input)?;
+    let (input, type_params) = opt(preceded(
+        char('['),
+        cut(terminated(
+            separated_list1(comma, with_error_context(identifier, "type parameter")),
+            with_error_context(char(']'), "']'"),
+        )),
+    ))(input)?;
+    let type_params = type_params.unwrap_or_default();
+    let type_annotation = |i| generic_type_annotation(i, &type_params);
+    let (input, _) = cut(with_error_context(char('('), "'('"))(input)?;
+    let (input, params) = cut(separated_list0(
+        delimited(space0, char(','), space0),
+        tuple((
+            with_error_context(identifier, "parameter name"),
+            preceded(
+                tuple((space0, with_error_context(char(':'), "':'"), space0)),
+                cut(type_annotation),
+            ),
+            opt(preceded(
+                delimited(space0, char('='), space0),
+                cut(expression),
+            )),
+        )),
+    ))(input)?;
+    // A variadic parameter, e.g. '*msgs: TString', comes last and collects a list
+    let (input, variadic) = opt(preceded(
+        pair(
+            |i| {
+                if params.is_empty() {
+                    Ok((i, ','))
+                } else {
+                    comma(i)
+                }
+            },
+            char('*'),
+        ),
+        cut(tuple((
+            with_error_context(identifier, "parameter name"),
+            preceded(
+                tuple((space0, with_error_context(char(':'), "':'"), space0)),
+                type_annotation,
+            ),
+        ))),
+    ))(input)?;
+    let (input, _) = cut(with_error_context(char(')'), "')'"))(input)?;
+    let (input, _) = space0(input)?;
+    // Without a return annotation the function returns nothing
+    let (input, return_type) = cut(alt((
+        map(
+            preceded(
+                pair(with_error_context(tag("->"), "'->'"), space0),
+                cut(terminated(type_annotation, space0)),
+            ),
+            Some,
+        ),
+        map(peek(with_error_context(char(':'), "':'")), |_| None),
+    )))(input)?;
+    let (input, _) = cut(with_error_context(char(':'), "':'"))(input)?;
+    let (input, body) = cut(|i| indented_block(i, block))(input)?;
+
+    let defaults = params
+        .iter()
+        .filter_map(|(name, _, default)| Some((name.clone(), default.clone()?)))
+        .collect();
+    let mut params: Vec<(Name, Type)> = params
+        .into_iter()
+        .map(|(name, kind, _)| (name, kind))
+        .collect();
+    if let Some((name, kind)) = variadic.clone() {
+        params.push((name, Type::TList(Box::new(kind))));
+    }
+
+    Ok((
+        input,
+        Statement::FuncDef(Function {
+            name: name.clone(), // Provide the name field
+            type_params,
+            kind: Some(return_type.unwrap_or(Type::TVoid)),
+            params: Some(params),
+            variadic: variadic.is_some(),
+            defaults,
+            body: Some(Box::new(Statement::Block(body))), // Wrap in Some
+        }),
+    ))
+}
+
+//return statement parsing
+fn return_statement<'a>(input: &'a str, block: Block<'a>) -> ParseResult<'a, Statement> {
+    let (input, _) = keyword("return")(input)?;
+
+    // A bare 'return' ends the line and returns nothing
+    if peek(end_of_line)(input).is_ok() || input.trim_start_matches([' ', '\t']).is_empty() {
+        return Ok((input, Statement::Return(Box::new(Expression::CVoid))));
+    }
+    let (input, _) = space1(input)?;
+    let (input, expr) = cut(|i| assigned_value(i, block))(input)?;
+    Ok((input, Statement::Return(Box::new(expr))))
+}
+
+// Parse multiple statements
+pub fn parse_statements(source: &str) -> ParseResult<'_, Vec<Statement>> {
+    statements(source, false)
+}
+
+fn statements(source: &str, located: bool) -> ParseResult<'_, Vec<Statement>> {
+    let mut statements = vec![];
+    let (mut input, _) = many0(end_of_line)(source)?; // Skip leading blank and comment lines
+
+    loop {
+        // The first statement sets the indentation of the top level
+        let (rest, indent) = leading_whitespace(input);
+        if rest.is_empty() || rest.starts_with('#') {
+            break;
+        }
+        // Any later run of statements was dedented below the top level
+        if !statements.is_empty() {
+            statements.push(Statement::Invalid(ParseError::IndentationError(
+                location(source, input).line,
+            )));
+        }
+        let (rest, mut run) = statement_sequence(
+            input,
+            Block {
+                source,
+                indent,
+                located,
+            },
+        )?;
+        statements.append(&mut run);
+        (input, _) = many0(end_of_line)(rest)?;
+    }
+
+    let (input, _) = space0(input)?; // Handle trailing whitespace
+    Ok((input, statements))
+}
+
+// function call parsing
+fn function_call(input: &str) -> ParseResult<'_, Expression> {
+    let (input, name) = identifier(input)?;
+    let (input, args) = call_arguments(input)?;
+
+    Ok((input, Expression::FuncCall(name, args)))
+}
+
+fn call_arguments(input: &str) -> ParseResult<'_, Vec<Expression>> {
+    let (input, _) = char('(')(input)?;
+    let (input, args) = separated_list0(
+        delimited(space0, char(','), space0),
+        alt((keyword_argument, unpacked_argument, expression)),
+    )(input)?;
+    let (input, _) = with_error_context(char(')'), "')'")(input)?;
+
+    Ok((input, args))
+}
+
+// Parse an unpacked argument, e.g. '*xs' in 'f(*xs)'
+fn unpacked_argument(input: &str) -> ParseResult<'_, Expression> {
+    let (input, _) = pair(char('*'), space0)(input)?;
+    let (input, value) = cut(expression)(input)?;
+
+    Ok((input, Expression::Unpack(Box::new(value))))
+}
+
+// Parse a named argument, e.g. 'y=2' in 'f(x, y=2)'
+fn keyword_argument(input: &str) -> ParseResult<'_, Expression> {
+    let (input, name) = identifier(input)?;
+    let (input, _) = tuple((space0, char('='), not(char('=')), space0))(input)?;
+    let (input, value) = cut(expression)(input)?;
+
+    Ok((input, Expression::KeywordArg(name, Box::new(value))))
+}
+
+// Parse an anonymous function, e.g. 'lambda(x: TInteger) -> TInteger: x * 2'
+fn lambda_expression(input: &str) -> ParseResult<'_, Expression> {
+    let (input, _) = keyword("lambda")(input)?;
+    let (input, _) = space0(input)?;
+    let (input, _) = cut(with_error_context(char('('), "'('"))(input)?;
+    let (input, params) = cut(separated_list0(
+        comma,
+        tuple((
+            with_error_context(identifier, "parameter name"),
+            preceded(
+                tuple((space0, with_error_context(char(':'), "':'"), space0)),
+                cut(type_annotation),
+            ),
+        )),
+    ))(input)?;
+    let (input, _) = cut(with_error_context(char(')'), "')'"))(input)?;
+    let (input, _) = space0(input)?;
+    let (input, _) = cut(with_error_context(tag("->"), "'->'"))(input)?;
+    let (input, _) = space0(input)?;
+    let (input, return_type) = cut(type_annotation)(input)?;
+    let (input, _) = cut(with_error_context(char(':'), "':'"))(input)?;
+    let (input, _) = space0(input)?;
+    let (input, body) = cut(expression)(input)?;
+
+    Ok((
+        input,
+        Expression::Lambda(Box::new(Function {
+            name: "lambda".to_string(),
+            type_params: vec![],
+            kind: Some(return_type),
+            params: Some(params),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(Statement::Return(Box::new(body)))),
+        })),
+    ))
+}
+
+// Parse a program, recovering from syntax errors: returns the partial AST,
+// with `Statement::Invalid` in place of each malformed statement, and every
+// error found
+pub fn parse_with_recovery(input: &str) -> (Vec<Statement>, Vec<ParseError>) {
+    parse_program(input, false)
+}
+
+fn parse_program(input: &str, located: bool) -> (Vec<Statement>, Vec<ParseError>) {
+    let statements = match tuple((|i| statements(i, located), opt(comment)))(input) {
+        Ok((_, (statements, _))) => statements,
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+            vec![Statement::Invalid(e.to_parse_error(input))]
+        }
+        Err(nom::Err::Incomplete(_)) => vec![Statement::Invalid(ParseError::UnexpectedToken(
+            location(input, ""),
+            found_token(""),
+            vec![],
+        ))],
+    };
+
+    let mut errors = vec![];
+    collect_parse_errors(&statements, &mut errors);
+    (statements, errors)
+}
+
+fn collect_parse_errors(statements: &[Statement], errors: &mut Vec<ParseError>) {
+    for stmt in statements {
+        match stmt {
+            Statement::Invalid(e) => errors.push(e.clone()),
+            Statement::Block(stmts) => collect_parse_errors(stmts, errors),
+            Statement::Located(_, stmt) => collect_parse_errors(std::slice::from_ref(stmt), errors),
+            Statement::IfThenElse(_, stmt_then, stmt_else) => {
+                collect_parse_errors(std::slice::from_ref(stmt_then), errors);
+                if let Some(stmt_else) = stmt_else {
+                    collect_parse_errors(std::slice::from_ref(stmt_else), errors);
+                }
+            }
+            Statement::While(_, body) | Statement::ModTestDef(_, body) => {
+                collect_parse_errors(std::slice::from_ref(body), errors)
+            }
+            Statement::Match(_, arms) => {
+                for (_, body) in arms {
+                    collect_parse_errors(std::slice::from_ref(body), errors);
+                }
+            }
+            Statement::Sequence(s1, s2) => {
+                collect_parse_errors(std::slice::from_ref(s1), errors);
+                collect_parse_errors(std::slice::from_ref(s2), errors);
+            }
+            Statement::FuncDef(func) | Statement::TestDef(func) => {
+                if let Some(body) = &func.body {
+                    collect_parse_errors(std::slice::from_ref(body), errors);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// Main parse function: fails with the first syntax error in the input
+pub fn parse(input: &str) -> Result<Vec<Statement>, ParseError> {
+    first_error(parse_with_recovery(input))
+}
+
+// Parse like `parse`, wrapping every statement, nested ones included, in a
+// `Statement::Located` with the line and column where it starts
+pub fn parse_located(input: &str) -> Result<Vec<Statement>, ParseError> {
+    first_error(parse_program(input, true))
+}
+
+fn first_error(
+    (statements, errors): (Vec<Statement>, Vec<ParseError>),
+) -> Result<Vec<Statement>, ParseError> {
+    match errors.into_iter().next() {
+        Some(error) => Err(error),
+        None => Ok(statements),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*; // Import everything from parent module
+    use crate::ir::ast::{Expression, Statement}; // Import AST types
+    #[test]
+    fn test_simple_assignment() {
+        let input = "x = 42";
+        let (rest, stmt) = assignment(
+            input,
+            Block {
+                source: input,
+                indent: "",
+                located: false,
+            },
+        )
+        .unwrap();
+        assert_eq!(rest, "");
+        match stmt {
+            Statement::Assignment(name, expr, _type) => {
+                // Added _type
+                assert_eq!(name, "x");
+                match *expr {
+                    Expression::CInt(val) => assert_eq!(val, 42),
+                    _ => panic!("Expected CInt"),
+                }
+            }
+            _ => panic!("Expected Assignment"),
+        }
+    }
+
+    #[test]
     fn test_complete_program() {
         let input = "x = 10\nif x > 5:\n    y = 1\nelse:\n    y = 2";
-        let (rest, stmts) = parse(input).unwrap();
-        assert_eq!(rest, "");
+        let stmts = parse(input).unwrap();
         assert_eq!(stmts.len(), 2); // Assignment and IfThenElse
     }
 
     #[test]
     fn test_complex_expression() {
         let input = "x = (2 * 3) + (10 - 4)";
-        let (rest, stmts) = parse(input).unwrap();
-        assert_eq!(rest, "");
+        let stmts = parse(input).unwrap();
 
         match &stmts[0] {
             Statement::Assignment(name, expr, _type) => {
@@ -590,8 +1485,7 @@ mod tests {
     #[test]
     fn test_multiline_with_if() {
         let input = "x = 10\nif x > 5:\n    y = 1\nelse:\n    y = 2";
-        let (rest, stmts) = parse(input).unwrap();
-        assert_eq!(rest, "");
+        let stmts = parse(input).unwrap();
         assert_eq!(stmts.len(), 2); // Should have assignment and if-statement
 
         // Verify first statement is assignment
@@ -650,7 +1544,15 @@ mod tests {
     #[test]
     fn test_if_else_block() {
         let input = "if x > 0:\n    y = 1\nelse:\n    y = 2";
-        let (rest, stmt) = if_statement(input).unwrap();
+        let (rest, stmt) = if_statement(
+            input,
+            Block {
+                source: input,
+                indent: "",
+                located: false,
+            },
+        )
+        .unwrap();
         assert_eq!(rest, "");
 
         match stmt {
@@ -698,7 +1600,15 @@ mod tests {
     #[test]
     fn test_if_else_statement() {
         let input = "if x > 0:\n    y = 1\nelse:\n    y = 2";
-        let (rest, stmt) = if_statement(input).unwrap();
+        let (rest, stmt) = if_statement(
+            input,
+            Block {
+                source: input,
+                indent: "",
+                located: false,
+            },
+        )
+        .unwrap();
         assert_eq!(rest, "");
 
         match stmt {
@@ -749,8 +1659,7 @@ mod tests {
     #[test]
     fn test_multiline_parse() {
         let input = "x = 42\ny = 10";
-        let (rest, stmts) = parse(input).unwrap();
-        assert_eq!(rest, "");
+        let stmts = parse(input).unwrap();
         assert_eq!(stmts.len(), 2);
 
         match &stmts[0] {
@@ -779,8 +1688,7 @@ mod tests {
     #[test]
     fn test_whitespace_handling() {
         let input = "   x    =    42   \n   y   =   10   ";
-        let (rest, stmts) = parse(input).unwrap();
-        assert_eq!(rest, "");
+        let stmts = parse(input).unwrap();
         assert_eq!(stmts.len(), 2);
     }
 
@@ -788,7 +1696,15 @@ mod tests {
     fn test_function_definition() {
         let input = r#"def add(x: TInteger, y: TInteger) -> TInteger:
         return x + y"#;
-        let (rest, stmt) = function_def(input).unwrap();
+        let (rest, stmt) = function_def(
+            input,
+            Block {
+                source: input,
+                indent: "",
+                located: false,
+            },
+        )
+        .unwrap();
         assert_eq!(rest, "");
         match stmt {
             Statement::FuncDef(func) => {
@@ -819,7 +1735,15 @@ mod tests {
     #[test]
     fn test_function_call() {
         let input = "result = add(5, 3)";
-        let (rest, stmt) = assignment(input).unwrap();
+        let (rest, stmt) = assignment(
+            input,
+            Block {
+                source: input,
+                indent: "",
+                located: false,
+            },
+        )
+        .unwrap();
         assert_eq!(rest, "");
         match stmt {
             Statement::Assignment(name, expr, _type) => {
@@ -1220,8 +2144,7 @@ mod tests {
     fn test_propagation_parsing_statements() {
         let input = "x = Ok(True)\nif unwrap(x):\n  y = 1\nif tryUnwrap(x):\n  y = 1\n";
 
-        let (rest, result) = parse(input).unwrap();
-        assert_eq!(rest, "");
+        let result = parse(input).unwrap();
         assert_eq!(
             result,
             [
@@ -1312,7 +2235,14 @@ mod tests {
     #[test]
     fn test_create_function_with_keyword_if() {
         let input = "def if(x: TInteger) -> TInteger:\n    return x";
-        let result = function_def(input);
+        let result = function_def(
+            input,
+            Block {
+                source: input,
+                indent: "",
+                located: false,
+            },
+        );
 
         assert!(result.is_err());
     }
@@ -1320,7 +2250,14 @@ mod tests {
     #[test]
     fn test_create_function_with_keyword_while() {
         let input = "def while(x: TInteger) -> TInteger:\n    return x";
-        let result = function_def(input);
+        let result = function_def(
+            input,
+            Block {
+                source: input,
+                indent: "",
+                located: false,
+            },
+        );
 
         assert!(result.is_err());
     }
@@ -1328,7 +2265,14 @@ mod tests {
     #[test]
     fn test_create_function_with_keyword_ok() {
         let input = "def Ok(x: TInteger) -> TInteger:\n    return x";
-        let result = function_def(input);
+        let result = function_def(
+            input,
+            Block {
+                source: input,
+                indent: "",
+                located: false,
+            },
+        );
 
         assert!(result.is_err());
     }
@@ -1336,7 +2280,14 @@ mod tests {
     #[test]
     fn test_var_declaration_with_keyword_if() {
         let input = "if = 10";
-        let result = assignment(input);
+        let result = assignment(
+            input,
+            Block {
+                source: input,
+                indent: "",
+                located: false,
+            },
+        );
 
         assert!(result.is_err());
     }
@@ -1344,7 +2295,14 @@ mod tests {
     #[test]
     fn test_var_declaration_with_keyword_while() {
         let input = "while = 10";
-        let result = assignment(input);
+        let result = assignment(
+            input,
+            Block {
+                source: input,
+                indent: "",
+                located: false,
+            },
+        );
 
         assert!(result.is_err());
     }
@@ -1352,8 +2310,1153 @@ mod tests {
     #[test]
     fn test_var_declaration_with_keyword_ok() {
         let input = "Ok = 10";
-        let result = assignment(input);
+        let result = assignment(
+            input,
+            Block {
+                source: input,
+                indent: "",
+                located: false,
+            },
+        );
 
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_if_elif_else_chain() {
+        let input = "if x > 0:\n    y = 1\nelif x < 0:\n    y = 2\nelif x == 0:\n    y = 3\nelse:\n    y = 4";
+        let (rest, stmt) = if_statement(
+            input,
+            Block {
+                source: input,
+                indent: "",
+                located: false,
+            },
+        )
+        .unwrap();
+        assert_eq!(rest, "");
+
+        let block = |n: i32| {
+            Box::new(Statement::Block(vec![Statement::Assignment(
+                "y".to_string(),
+                Box::new(Expression::CInt(n)),
+                Some(Type::TInteger),
+            )]))
+        };
+        let x = || Box::new(Expression::Var("x".to_string()));
+        let zero = || Box::new(Expression::CInt(0));
+
+        assert_eq!(
+            stmt,
+            Statement::IfThenElse(
+                Box::new(Expression::GT(x(), zero())),
+                block(1),
+                Some(Box::new(Statement::IfThenElse(
+                    Box::new(Expression::LT(x(), zero())),
+                    block(2),
+                    Some(Box::new(Statement::IfThenElse(
+                        Box::new(Expression::EQ(x(), zero())),
+                        block(3),
+                        Some(block(4)),
+                    ))),
+                ))),
+            )
+        );
+    }
+
+    #[test]
+    fn test_elif_without_else() {
+        let input = "if a:\n    y = 1\nelif b:\n    y = 2";
+        let (rest, stmt) = if_statement(
+            input,
+            Block {
+                source: input,
+                indent: "",
+                located: false,
+            },
+        )
+        .unwrap();
+        assert_eq!(rest, "");
+
+        match stmt {
+            Statement::IfThenElse(_, _, Some(else_stmt)) => {
+                assert!(matches!(*else_stmt, Statement::IfThenElse(_, _, None)))
+            }
+            _ => panic!("Expected IfThenElse with a nested elif"),
+        }
+    }
+
+    #[test]
+    fn test_if_arbitrary_conditions() {
+        let cases = vec![
+            (
+                "if isError(r):\n    y = 1",
+                Expression::IsError(Box::new(Expression::Var("r".to_string()))),
+            ),
+            (
+                "if not done:\n    y = 1",
+                Expression::Not(Box::new(Expression::Var("done".to_string()))),
+            ),
+            (
+                "if check(x, 1):\n    y = 1",
+                Expression::FuncCall(
+                    "check".to_string(),
+                    vec![Expression::Var("x".to_string()), Expression::CInt(1)],
+                ),
+            ),
+            (
+                "if a and not b or c:\n    y = 1",
+                Expression::Or(
+                    Box::new(Expression::And(
+                        Box::new(Expression::Var("a".to_string())),
+                        Box::new(Expression::Not(Box::new(Expression::Var("b".to_string())))),
+                    )),
+                    Box::new(Expression::Var("c".to_string())),
+                ),
+            ),
+        ];
+
+        for (input, expected) in cases {
+            let (rest, stmt) = if_statement(
+                input,
+                Block {
+                    source: input,
+                    indent: "",
+                    located: false,
+                },
+            )
+            .unwrap();
+            assert_eq!(rest, "");
+            match stmt {
+                Statement::IfThenElse(cond, _, _) => assert_eq!(*cond, expected),
+                _ => panic!("Expected IfThenElse"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_not_equal_comparison() {
+        let (rest, result) = expression("x != 1").unwrap();
+        assert_eq!(rest, "");
+        assert_eq!(
+            result,
+            Expression::Not(Box::new(Expression::EQ(
+                Box::new(Expression::Var("x".to_string())),
+                Box::new(Expression::CInt(1))
+            )))
+        );
+    }
+
+    #[test]
+    fn test_comments_and_blank_lines() {
+        let input = "# leading comment\n\nx = 10  # trailing comment\n\n   \nif x > 5:  # header comment\n    y = 1\n\n    # comment inside block\n    \n    z = 2\n# comment between branches\nelse:\n    y = 2\n# final comment";
+        let stmts = parse(input).unwrap();
+        assert_eq!(stmts.len(), 2);
+
+        match &stmts[1] {
+            Statement::IfThenElse(_, then_block, Some(_)) => match **then_block {
+                Statement::Block(ref stmts) => assert_eq!(stmts.len(), 2),
+                _ => panic!("Expected Block"),
+            },
+            _ => panic!("Expected IfThenElse with else"),
+        }
+    }
+
+    #[test]
+    fn test_comment_only_program() {
+        let stmts = parse("# nothing to see here\n\n# still nothing").unwrap();
+        assert!(stmts.is_empty());
+    }
+
+    #[test]
+    fn test_hash_inside_string_is_not_a_comment() {
+        let stmts = parse("s = \"# not a comment\" # a comment").unwrap();
+        assert_eq!(
+            stmts,
+            vec![Statement::Assignment(
+                "s".to_string(),
+                Box::new(Expression::CString("# not a comment".to_string())),
+                Some(Type::TString)
+            )]
+        );
+    }
+
+    fn indentation_error_line(input: &str) -> Option<usize> {
+        match parse(input) {
+            Err(ParseError::IndentationError(line)) => Some(line),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_nested_blocks_follow_indentation() {
+        let input = "if a:\n    if b:\n        y = 1\n    z = 2\nw = 3";
+        let stmts = parse(input).unwrap();
+        assert_eq!(stmts.len(), 2);
+
+        match &stmts[0] {
+            Statement::IfThenElse(_, then_block, None) => match **then_block {
+                Statement::Block(ref inner) => {
+                    assert_eq!(inner.len(), 2);
+                    assert!(matches!(inner[0], Statement::IfThenElse(_, _, None)));
+                    assert!(
+                        matches!(inner[1], Statement::Assignment(ref name, _, _) if name == "z")
+                    );
+                }
+                _ => panic!("Expected Block"),
+            },
+            _ => panic!("Expected IfThenElse"),
+        }
+    }
+
+    #[test]
+    fn test_else_attaches_to_matching_if() {
+        let input = "if a:\n    if b:\n        y = 1\nelse:\n    y = 2";
+        let stmts = parse(input).unwrap();
+
+        match &stmts[0] {
+            Statement::IfThenElse(_, then_block, Some(_)) => match **then_block {
+                Statement::Block(ref inner) => {
+                    assert!(matches!(inner[0], Statement::IfThenElse(_, _, None)))
+                }
+                _ => panic!("Expected Block"),
+            },
+            _ => panic!("Expected outer IfThenElse with else"),
+        }
+    }
+
+    #[test]
+    fn test_tab_indentation() {
+        let input = "if a:\n\ty = 1\n\tif b:\n\t\tz = 2";
+        let stmts = parse(input).unwrap();
+        assert_eq!(stmts.len(), 1);
+    }
+
+    #[test]
+    fn test_indentation_errors() {
+        // Block not indented deeper than its header
+        assert_eq!(indentation_error_line("if a:\ny = 1"), Some(2));
+        // Nested block at the same depth as the enclosing one
+        assert_eq!(
+            indentation_error_line("if a:\n    if b:\n    y = 1"),
+            Some(3)
+        );
+        // Unexpected indent inside a block
+        assert_eq!(
+            indentation_error_line("if a:\n    y = 1\n      z = 2"),
+            Some(3)
+        );
+        // Dedent that does not match any enclosing block
+        assert_eq!(
+            indentation_error_line("if a:\n    if b:\n        y = 1\n  z = 2"),
+            Some(4)
+        );
+        // Unexpected indent at the top level
+        assert_eq!(indentation_error_line("x = 1\n    y = 2"), Some(2));
+        // Mixed tabs and spaces
+        assert_eq!(indentation_error_line("if a:\n \ty = 1"), Some(2));
+        // Tab-indented line inside a space-indented block
+        assert_eq!(indentation_error_line("if a:\n    y = 1\n\tz = 2"), Some(3));
+    }
+
+    #[test]
+    fn test_parse_error_unexpected_token() {
+        assert_eq!(
+            parse("x = 1\ny 2"),
+            Err(ParseError::UnexpectedToken(
+                Location { line: 2, column: 3 },
+                "'2'".to_string(),
+                vec!["'='".to_string()]
+            ))
+        );
+        assert_eq!(
+            parse("if x > 1\n    y = 1"),
+            Err(ParseError::UnexpectedToken(
+                Location { line: 1, column: 9 },
+                "end of line".to_string(),
+                vec!["':'".to_string()]
+            ))
+        );
+        assert_eq!(
+            parse("def f(x: TInteger) TInteger:\n    return x"),
+            Err(ParseError::UnexpectedToken(
+                Location {
+                    line: 1,
+                    column: 20
+                },
+                "'TInteger'".to_string(),
+                vec!["'->'".to_string(), "':'".to_string()]
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_error_invalid_expression() {
+        assert_eq!(
+            parse("x = 1\ny = 2 + "),
+            Err(ParseError::InvalidExpression(
+                Location { line: 2, column: 9 },
+                "end of input".to_string()
+            ))
+        );
+        assert_eq!(
+            parse("if x > 0:\n    return )"),
+            Err(ParseError::InvalidExpression(
+                Location {
+                    line: 2,
+                    column: 12
+                },
+                "')'".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_fails_on_unconsumed_input() {
+        assert_eq!(
+            parse("x = 1 2"),
+            Err(ParseError::UnexpectedToken(
+                Location { line: 1, column: 7 },
+                "'2'".to_string(),
+                vec!["end of line".to_string()]
+            ))
+        );
+        assert_eq!(
+            parse("  x = 1\ny = 2"),
+            Err(ParseError::IndentationError(2))
+        );
+    }
+
+    #[test]
+    fn test_parse_error_display() {
+        let error = ParseError::UnexpectedToken(
+            Location { line: 2, column: 3 },
+            "'2'".to_string(),
+            vec!["'='".to_string(), "'('".to_string()],
+        );
+        assert_eq!(
+            error.to_string(),
+            "[Syntax Error] line 2, column 3: unexpected '2', expected one of: '=', '('."
+        );
+    }
+
+    #[test]
+    fn test_recovery_reports_every_error() {
+        let input = "x = 1\ny 2\nz = 3\nif z > 1\n    w = 1\n    v = 2\nu = \nt = 4";
+        let (stmts, errors) = parse_with_recovery(input);
+
+        assert_eq!(
+            errors,
+            vec![
+                ParseError::UnexpectedToken(
+                    Location { line: 2, column: 3 },
+                    "'2'".to_string(),
+                    vec!["'='".to_string()]
+                ),
+                ParseError::UnexpectedToken(
+                    Location { line: 4, column: 9 },
+                    "end of line".to_string(),
+                    vec!["':'".to_string()]
+                ),
+                ParseError::InvalidExpression(
+                    Location { line: 7, column: 5 },
+                    "end of line".to_string()
+                ),
+            ]
+        );
+
+        // The body of the broken 'if' is skipped along with its header
+        assert_eq!(stmts.len(), 6);
+        assert!(matches!(stmts[0], Statement::Assignment(ref n, _, _) if n == "x"));
+        assert_eq!(stmts[1], Statement::Invalid(errors[0].clone()));
+        assert!(matches!(stmts[2], Statement::Assignment(ref n, _, _) if n == "z"));
+        assert_eq!(stmts[3], Statement::Invalid(errors[1].clone()));
+        assert_eq!(stmts[4], Statement::Invalid(errors[2].clone()));
+        assert!(matches!(stmts[5], Statement::Assignment(ref n, _, _) if n == "t"));
+    }
+
+    #[test]
+    fn test_recovery_inside_blocks() {
+        let input = "if a:\n    x = \n    y = 1\n        z = 2\n    w = 3\nv = 4";
+        let (stmts, errors) = parse_with_recovery(input);
+
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(errors[0], ParseError::InvalidExpression(_, _)));
+        assert_eq!(errors[1], ParseError::IndentationError(4));
+        assert_eq!(stmts.len(), 2);
+
+        match &stmts[0] {
+            Statement::IfThenElse(_, then_block, None) => match **then_block {
+                Statement::Block(ref inner) => {
+                    assert_eq!(inner.len(), 4);
+                    assert!(matches!(inner[0], Statement::Invalid(_)));
+                    assert!(matches!(inner[2], Statement::Invalid(_)));
+                    assert!(matches!(inner[3], Statement::Assignment(ref n, _, _) if n == "w"));
+                }
+                _ => panic!("Expected Block"),
+            },
+            _ => panic!("Expected IfThenElse"),
+        }
+    }
+
+    #[test]
+    fn test_parse_returns_first_recovered_error() {
+        assert_eq!(
+            parse("x = \ny 2"),
+            Err(ParseError::InvalidExpression(
+                Location { line: 1, column: 5 },
+                "end of line".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_located() {
+        let at = |line: usize, column: usize, stmt: Statement| {
+            Statement::Located(Location { line, column }, Box::new(stmt))
+        };
+
+        assert_eq!(
+            parse_located("x = 1\nif x > 0:\n    y = 2"),
+            Ok(vec![
+                at(
+                    1,
+                    1,
+                    Statement::Assignment(
+                        "x".to_string(),
+                        Box::new(Expression::CInt(1)),
+                        Some(Type::TInteger)
+                    )
+                ),
+                at(
+                    2,
+                    1,
+                    Statement::IfThenElse(
+                        Box::new(Expression::GT(
+                            Box::new(Expression::Var("x".to_string())),
+                            Box::new(Expression::CInt(0))
+                        )),
+                        Box::new(Statement::Block(vec![at(
+                            3,
+                            5,
+                            Statement::Assignment(
+                                "y".to_string(),
+                                Box::new(Expression::CInt(2)),
+                                Some(Type::TInteger)
+                            )
+                        )])),
+                        None
+                    )
+                ),
+            ])
+        );
+        // Syntax errors carry their own locations
+        assert_eq!(
+            parse_located("x = \ny 2"),
+            parse("x = \ny 2").map(|_| vec![])
+        );
+    }
+
+    #[test]
+    fn test_type_annotations() {
+        let cases = vec![
+            ("TInteger", Type::TInteger),
+            ("TString", Type::TString),
+            ("TVoid", Type::TVoid),
+            ("TList[TReal]", Type::TList(Box::new(Type::TReal))),
+            ("TMaybe[ TBool ]", Type::TMaybe(Box::new(Type::TBool))),
+            (
+                "TResult[TInteger, TString]",
+                Type::TResult(Box::new(Type::TInteger), Box::new(Type::TString)),
+            ),
+            (
+                "TTuple[TInteger, TList[TBool], TReal]",
+                Type::TTuple(vec![
+                    Type::TInteger,
+                    Type::TList(Box::new(Type::TBool)),
+                    Type::TReal,
+                ]),
+            ),
+            (
+                "TFunction[[TInteger, TInteger], TBool]",
+                Type::TFunction(
+                    Box::new(Some(Type::TBool)),
+                    vec![Type::TInteger, Type::TInteger],
+                ),
+            ),
+            (
+                "TFunction[[], TMaybe[TResult[TReal, TString]]]",
+                Type::TFunction(
+                    Box::new(Some(Type::TMaybe(Box::new(Type::TResult(
+                        Box::new(Type::TReal),
+                        Box::new(Type::TString),
+                    ))))),
+                    vec![],
+                ),
+            ),
+        ];
+
+        for (input, expected) in cases {
+            let (rest, result) = type_annotation(input).unwrap();
+            assert_eq!(rest, "");
+            assert_eq!(result, expected);
+        }
+    }
+
+    #[test]
+    fn test_function_definition_with_compound_types() {
+        let input = "def f(xs: TList[TInteger], m: TMaybe[TReal]) -> TString:\n    return \"ok\"";
+        let stmts = parse(input).unwrap();
+
+        match &stmts[0] {
+            Statement::FuncDef(func) => {
+                assert_eq!(func.kind, Some(Type::TString));
+                assert_eq!(
+                    func.params,
+                    Some(vec![
+                        ("xs".to_string(), Type::TList(Box::new(Type::TInteger))),
+                        ("m".to_string(), Type::TMaybe(Box::new(Type::TReal))),
+                    ])
+                );
+            }
+            _ => panic!("Expected FuncDef"),
+        }
+    }
+
+    #[test]
+    fn test_unknown_type_names_are_rejected() {
+        assert_eq!(
+            parse("def f(x: TStrin) -> TInteger:\n    return 1"),
+            Err(ParseError::UnexpectedToken(
+                Location {
+                    line: 1,
+                    column: 10
+                },
+                "'TStrin'".to_string(),
+                vec!["type".to_string()]
+            ))
+        );
+        assert_eq!(
+            parse("def f(x: TList[TFoo]) -> TInteger:\n    return 1"),
+            Err(ParseError::UnexpectedToken(
+                Location {
+                    line: 1,
+                    column: 16
+                },
+                "'TFoo'".to_string(),
+                vec!["type".to_string()]
+            ))
+        );
+        assert!(type_annotation("TResult[TInteger]").is_err());
+    }
+
+    #[test]
+    fn test_typed_declarations() {
+        let input = "var x: TInteger = 1\nval name = \"r-python\"\nvar total: TReal = x\nvar y";
+        let stmts = parse(input).unwrap();
+
+        assert_eq!(
+            stmts,
+            vec![
+                Statement::Sequence(
+                    Box::new(Statement::VarDeclaration(
+                        "x".to_string(),
+                        Some(Type::TInteger)
+                    )),
+                    Box::new(Statement::Assignment(
+                        "x".to_string(),
+                        Box::new(Expression::CInt(1)),
+                        Some(Type::TInteger)
+                    ))
+                ),
+                Statement::Sequence(
+                    Box::new(Statement::ValDeclaration("name".to_string(), None)),
+                    Box::new(Statement::Assignment(
+                        "name".to_string(),
+                        Box::new(Expression::CString("r-python".to_string())),
+                        Some(Type::TString)
+                    ))
+                ),
+                Statement::Sequence(
+                    Box::new(Statement::VarDeclaration(
+                        "total".to_string(),
+                        Some(Type::TReal)
+                    )),
+                    Box::new(Statement::Assignment(
+                        "total".to_string(),
+                        Box::new(Expression::Var("x".to_string())),
+                        Some(Type::TReal)
+                    ))
+                ),
+                Statement::VarDeclaration("y".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_annotated_declaration_without_initializer() {
+        assert_eq!(
+            parse("val x: TInteger\nvar y: TMaybe[TReal]"),
+            Ok(vec![
+                Statement::ValDeclaration("x".to_string(), Some(Type::TInteger)),
+                Statement::VarDeclaration(
+                    "y".to_string(),
+                    Some(Type::TMaybe(Box::new(Type::TReal)))
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_generic_function_definition() {
+        let input = "def first[T](xs: TList[T]) -> TMaybe[T]:\n    return Nothing";
+        let stmts = parse(input).unwrap();
+
+        match &stmts[0] {
+            Statement::FuncDef(func) => {
+                assert_eq!(func.type_params, vec!["T".to_string()]);
+                assert_eq!(
+                    func.params,
+                    Some(vec![(
+                        "xs".to_string(),
+                        Type::TList(Box::new(Type::TVar("T".to_string())))
+                    )])
+                );
+                assert_eq!(
+                    func.kind,
+                    Some(Type::TMaybe(Box::new(Type::TVar("T".to_string()))))
+                );
+            }
+            _ => panic!("Expected FuncDef"),
+        }
+    }
+
+    #[test]
+    fn test_type_parameters_are_scoped_to_their_function() {
+        // Outside of 'def f[T]', 'T' names a user-declared type instead
+        let stmts = parse("def f(x: T) -> T:\n    return x").unwrap();
+
+        match &stmts[0] {
+            Statement::FuncDef(func) => {
+                assert_eq!(func.kind, Some(Type::TUser("T".to_string())));
+            }
+            _ => panic!("Expected FuncDef"),
+        }
+    }
+
+    #[test]
+    fn test_struct_definition_and_field_access() {
+        let input = "struct Point: x: TReal, y: TReal\np = Point(1.0, 2.0)\np.x = p.y + 1.0\nline.start.y = 0.0";
+        let stmts = parse(input).unwrap();
+
+        assert_eq!(
+            stmts[0],
+            Statement::StructDef(
+                "Point".to_string(),
+                vec![
+                    ("x".to_string(), Type::TReal),
+                    ("y".to_string(), Type::TReal)
+                ]
+            )
+        );
+        assert_eq!(
+            stmts[1],
+            Statement::Assignment(
+                "p".to_string(),
+                Box::new(Expression::FuncCall(
+                    "Point".to_string(),
+                    vec![Expression::CReal(1.0), Expression::CReal(2.0)]
+                )),
+                None
+            )
+        );
+        assert_eq!(
+            stmts[2],
+            Statement::FieldAssignment(
+                Box::new(Expression::Var("p".to_string())),
+                "x".to_string(),
+                Box::new(Expression::Add(
+                    Box::new(Expression::FieldAccess(
+                        Box::new(Expression::Var("p".to_string())),
+                        "y".to_string()
+                    )),
+                    Box::new(Expression::CReal(1.0))
+                ))
+            )
+        );
+        assert_eq!(
+            stmts[3],
+            Statement::FieldAssignment(
+                Box::new(Expression::FieldAccess(
+                    Box::new(Expression::Var("line".to_string())),
+                    "start".to_string()
+                )),
+                "y".to_string(),
+                Box::new(Expression::CReal(0.0))
+            )
+        );
+    }
+
+    #[test]
+    fn test_user_type_annotations() {
+        let input = "def norm(p: Point) -> TMaybe[Point]:\n    return Just(p)";
+        let stmts = parse(input).unwrap();
+
+        match &stmts[0] {
+            Statement::FuncDef(func) => {
+                assert_eq!(
+                    func.params,
+                    Some(vec![("p".to_string(), Type::TUser("Point".to_string()))])
+                );
+                assert_eq!(
+                    func.kind,
+                    Some(Type::TMaybe(Box::new(Type::TUser("Point".to_string()))))
+                );
+            }
+            _ => panic!("Expected FuncDef"),
+        }
+    }
+
+    #[test]
+    fn test_enum_definition() {
+        let input = "enum Shape: Circle(TReal) | Rect(TReal, TReal) | Empty";
+        let stmts = parse(input).unwrap();
+
+        assert_eq!(
+            stmts,
+            vec![Statement::EnumDef(
+                "Shape".to_string(),
+                vec![
+                    ("Circle".to_string(), vec![Type::TReal]),
+                    ("Rect".to_string(), vec![Type::TReal, Type::TReal]),
+                    ("Empty".to_string(), vec![]),
+                ]
+            )]
+        );
+    }
+
+    #[test]
+    fn test_match_statement() {
+        let input = "def area(s: TMaybe[Shape]) -> TReal:\n    match s:\n        case Just(Circle(r)):\n            return 3.14 * r * r\n        case Just(_):\n            return 0.0\n        case Nothing:\n            return -1.0\n";
+        let stmts = parse(input).unwrap();
+
+        let body = match &stmts[0] {
+            Statement::FuncDef(func) => func.body.clone().unwrap(),
+            _ => panic!("Expected FuncDef"),
+        };
+        match *body {
+            Statement::Block(stmts) => match &stmts[0] {
+                Statement::Match(exp, arms) => {
+                    assert_eq!(**exp, Expression::Var("s".to_string()));
+                    let patterns: Vec<Pattern> = arms.iter().map(|(p, _)| p.clone()).collect();
+                    assert_eq!(
+                        patterns,
+                        vec![
+                            Pattern::Constructor(
+                                "Just".to_string(),
+                                vec![Pattern::Constructor(
+                                    "Circle".to_string(),
+                                    vec![Pattern::Binding("r".to_string())]
+                                )]
+                            ),
+                            Pattern::Constructor("Just".to_string(), vec![Pattern::Wildcard]),
+                            Pattern::Constructor("Nothing".to_string(), vec![]),
+                        ]
+                    );
+                    assert_eq!(
+                        arms[1].1,
+                        Statement::Block(vec![Statement::Return(Box::new(Expression::CReal(0.0)))])
+                    );
+                }
+                _ => panic!("Expected Match"),
+            },
+            _ => panic!("Expected Block"),
+        }
+    }
+
+    #[test]
+    fn test_match_expression() {
+        let input = "y = match m:\n    case Just(x): x + 1\n    case Nothing: 0\nz = y\n";
+        let stmts = parse(input).unwrap();
+
+        assert_eq!(
+            stmts,
+            vec![
+                Statement::Assignment(
+                    "y".to_string(),
+                    Box::new(Expression::Match(
+                        Box::new(Expression::Var("m".to_string())),
+                        vec![
+                            (
+                                Pattern::Constructor(
+                                    "Just".to_string(),
+                                    vec![Pattern::Binding("x".to_string())]
+                                ),
+                                Expression::Add(
+                                    Box::new(Expression::Var("x".to_string())),
+                                    Box::new(Expression::CInt(1))
+                                )
+                            ),
+                            (
+                                Pattern::Constructor("Nothing".to_string(), vec![]),
+                                Expression::CInt(0)
+                            ),
+                        ]
+                    )),
+                    None
+                ),
+                Statement::Assignment(
+                    "z".to_string(),
+                    Box::new(Expression::Var("y".to_string())),
+                    None
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_match_requires_case_arms() {
+        assert_eq!(
+            parse("match s:\n    x = 1"),
+            Err(ParseError::UnexpectedToken(
+                Location { line: 2, column: 5 },
+                "'x'".to_string(),
+                vec!["'case'".to_string()]
+            ))
+        );
+        assert_eq!(
+            parse("match n:\n    case 1:\n        x = 1"),
+            Err(ParseError::UnexpectedToken(
+                Location {
+                    line: 2,
+                    column: 10
+                },
+                "'1'".to_string(),
+                vec!["pattern".to_string()]
+            ))
+        );
+    }
+
+    #[test]
+    fn test_match_on_result() {
+        let input = "match divide(a, b):\n    case Ok(v):\n        total = total + v\n    case Err(message):\n        errors = errors + 1\n";
+        let stmts = parse(input).unwrap();
+
+        assert_eq!(
+            stmts,
+            vec![Statement::Match(
+                Box::new(Expression::FuncCall(
+                    "divide".to_string(),
+                    vec![
+                        Expression::Var("a".to_string()),
+                        Expression::Var("b".to_string())
+                    ]
+                )),
+                vec![
+                    (
+                        Pattern::Constructor(
+                            "Ok".to_string(),
+                            vec![Pattern::Binding("v".to_string())]
+                        ),
+                        Statement::Block(vec![Statement::Assignment(
+                            "total".to_string(),
+                            Box::new(Expression::Add(
+                                Box::new(Expression::Var("total".to_string())),
+                                Box::new(Expression::Var("v".to_string()))
+                            )),
+                            None
+                        )])
+                    ),
+                    (
+                        Pattern::Constructor(
+                            "Err".to_string(),
+                            vec![Pattern::Binding("message".to_string())]
+                        ),
+                        Statement::Block(vec![Statement::Assignment(
+                            "errors".to_string(),
+                            Box::new(Expression::Add(
+                                Box::new(Expression::Var("errors".to_string())),
+                                Box::new(Expression::CInt(1))
+                            )),
+                            None
+                        )])
+                    ),
+                ]
+            )]
+        );
+    }
+
+    #[test]
+    fn test_combinators() {
+        assert_eq!(
+            expression("m.map(double)"),
+            Ok((
+                "",
+                Expression::Map(
+                    Box::new(Expression::Var("m".to_string())),
+                    Box::new(Expression::Var("double".to_string()))
+                )
+            ))
+        );
+        assert_eq!(
+            expression("divide(a, b).and_then(half).unwrap_or(0)"),
+            Ok((
+                "",
+                Expression::UnwrapOr(
+                    Box::new(Expression::AndThen(
+                        Box::new(Expression::FuncCall(
+                            "divide".to_string(),
+                            vec![
+                                Expression::Var("a".to_string()),
+                                Expression::Var("b".to_string())
+                            ]
+                        )),
+                        Box::new(Expression::Var("half".to_string()))
+                    )),
+                    Box::new(Expression::CInt(0))
+                )
+            ))
+        );
+        assert!(parse("x = m.map()").is_err());
+    }
+
+    #[test]
+    fn test_combinator_names_are_not_reserved() {
+        let input = "def map(f: TFunction[[TInteger], TInteger], x: TInteger) -> TInteger:\n    return f(x)\nunwrap_or = 1\ny = p.map\n";
+        let stmts = parse(input).unwrap();
+
+        assert!(matches!(&stmts[0], Statement::FuncDef(func) if func.name == "map"));
+        assert_eq!(
+            stmts[1],
+            Statement::Assignment(
+                "unwrap_or".to_string(),
+                Box::new(Expression::CInt(1)),
+                Some(Type::TInteger)
+            )
+        );
+        assert_eq!(
+            stmts[2],
+            Statement::Assignment(
+                "y".to_string(),
+                Box::new(Expression::FieldAccess(
+                    Box::new(Expression::Var("p".to_string())),
+                    "map".to_string()
+                )),
+                None
+            )
+        );
+    }
+
+    #[test]
+    fn test_lambda_expression() {
+        let input = "twice = lambda(x: TInteger) -> TInteger: x * 2";
+        let stmts = parse(input).unwrap();
+
+        assert_eq!(
+            stmts,
+            vec![Statement::Assignment(
+                "twice".to_string(),
+                Box::new(Expression::Lambda(Box::new(Function {
+                    name: "lambda".to_string(),
+                    type_params: vec![],
+                    kind: Some(Type::TInteger),
+                    params: Some(vec![("x".to_string(), Type::TInteger)]),
+                    variadic: false,
+                    defaults: vec![],
+                    body: Some(Box::new(Statement::Return(Box::new(Expression::Mul(
+                        Box::new(Expression::Var("x".to_string())),
+                        Box::new(Expression::CInt(2))
+                    )))))
+                }))),
+                None
+            )]
+        );
+        assert!(parse("f = lambda(x: TInteger): x").is_err());
+    }
+
+    #[test]
+    fn test_call_of_any_expression() {
+        assert_eq!(
+            expression("adder(1)(2)"),
+            Ok((
+                "",
+                Expression::Call(
+                    Box::new(Expression::FuncCall(
+                        "adder".to_string(),
+                        vec![Expression::CInt(1)]
+                    )),
+                    vec![Expression::CInt(2)]
+                )
+            ))
+        );
+        assert_eq!(
+            expression("handlers.on_error(e)"),
+            Ok((
+                "",
+                Expression::Call(
+                    Box::new(Expression::FieldAccess(
+                        Box::new(Expression::Var("handlers".to_string())),
+                        "on_error".to_string()
+                    )),
+                    vec![Expression::Var("e".to_string())]
+                )
+            ))
+        );
+    }
+
+    #[test]
+    fn test_global_and_nonlocal_declarations() {
+        let input =
+            "def bump() -> TInteger:\n    global count, total\n    nonlocal step\n    return count";
+        let stmts = parse(input).unwrap();
+
+        assert_eq!(
+            stmts,
+            vec![Statement::FuncDef(Function {
+                name: "bump".to_string(),
+                type_params: vec![],
+                kind: Some(Type::TInteger),
+                params: Some(vec![]),
+                variadic: false,
+                defaults: vec![],
+                body: Some(Box::new(Statement::Block(vec![
+                    Statement::Global(vec!["count".to_string(), "total".to_string()]),
+                    Statement::Nonlocal(vec!["step".to_string()]),
+                    Statement::Return(Box::new(Expression::Var("count".to_string())))
+                ])))
+            })]
+        );
+        assert!(parse("global").is_err());
+    }
+
+    #[test]
+    fn test_default_and_keyword_arguments() {
+        let input = "def scale(x: TInteger, by: TInteger = 2) -> TInteger:\n    return x * by\ny = scale(by=3, x=1)";
+        let stmts = parse(input).unwrap();
+
+        assert_eq!(
+            stmts,
+            vec![
+                Statement::FuncDef(Function {
+                    name: "scale".to_string(),
+                    type_params: vec![],
+                    kind: Some(Type::TInteger),
+                    params: Some(vec![
+                        ("x".to_string(), Type::TInteger),
+                        ("by".to_string(), Type::TInteger)
+                    ]),
+                    variadic: false,
+                    defaults: vec![("by".to_string(), Expression::CInt(2))],
+                    body: Some(Box::new(Statement::Block(vec![Statement::Return(
+                        Box::new(Expression::Mul(
+                            Box::new(Expression::Var("x".to_string())),
+                            Box::new(Expression::Var("by".to_string()))
+                        ))
+                    )])))
+                }),
+                Statement::Assignment(
+                    "y".to_string(),
+                    Box::new(Expression::FuncCall(
+                        "scale".to_string(),
+                        vec![
+                            Expression::KeywordArg("by".to_string(), Box::new(Expression::CInt(3))),
+                            Expression::KeywordArg("x".to_string(), Box::new(Expression::CInt(1)))
+                        ]
+                    )),
+                    None
+                )
+            ]
+        );
+        assert_eq!(
+            expression("f(x == 1)"),
+            Ok((
+                "",
+                Expression::FuncCall(
+                    "f".to_string(),
+                    vec![Expression::EQ(
+                        Box::new(Expression::Var("x".to_string())),
+                        Box::new(Expression::CInt(1))
+                    )]
+                )
+            ))
+        );
+    }
+
+    #[test]
+    fn test_variadic_parameters_and_unpacking() {
+        let input = "def count(first: TInteger, *rest: TInteger) -> TInteger:\n    return first";
+        let stmts = parse(input).unwrap();
+
+        assert_eq!(
+            stmts,
+            vec![Statement::FuncDef(Function {
+                name: "count".to_string(),
+                type_params: vec![],
+                kind: Some(Type::TInteger),
+                params: Some(vec![
+                    ("first".to_string(), Type::TInteger),
+                    ("rest".to_string(), Type::TList(Box::new(Type::TInteger)))
+                ]),
+                variadic: true,
+                defaults: vec![],
+                body: Some(Box::new(Statement::Block(vec![Statement::Return(
+                    Box::new(Expression::Var("first".to_string()))
+                )])))
+            })]
+        );
+        assert_eq!(
+            expression("f(a, *xs)"),
+            Ok((
+                "",
+                Expression::FuncCall(
+                    "f".to_string(),
+                    vec![
+                        Expression::Var("a".to_string()),
+                        Expression::Unpack(Box::new(Expression::Var("xs".to_string())))
+                    ]
+                )
+            ))
+        );
+        // The variadic parameter must come last
+        assert!(parse("def f(*xs: TInteger, y: TInteger) -> TInteger:\n    return y").is_err());
+    }
+
+    #[test]
+    fn test_void_functions_and_bare_return() {
+        let input = "def reset():\n    x = 0\n    return # done\n";
+        let stmts = parse(input).unwrap();
+
+        assert_eq!(
+            stmts,
+            vec![Statement::FuncDef(Function {
+                name: "reset".to_string(),
+                type_params: vec![],
+                kind: Some(Type::TVoid),
+                params: Some(vec![]),
+                variadic: false,
+                defaults: vec![],
+                body: Some(Box::new(Statement::Block(vec![
+                    Statement::Assignment(
+                        "x".to_string(),
+                        Box::new(Expression::CInt(0)),
+                        Some(Type::TInteger)
+                    ),
+                    Statement::Return(Box::new(Expression::CVoid))
+                ])))
+            })]
+        );
+        assert_eq!(
+            parse("def f() -> TVoid:\n    return"),
+            parse("def f():\n    return")
+        );
+        // 'returned' is a name, not a bare return
+        assert_eq!(
+            parse("returned = 1"),
+            Ok(vec![Statement::Assignment(
+                "returned".to_string(),
+                Box::new(Expression::CInt(1)),
+                Some(Type::TInteger)
+            )])
+        );
+    }
 }
@@ -1,13 +1,125 @@
-use crate::ir::ast::{Environment, Expression, Name, Statement, Type};
+use crate::ir::ast::{
+    count_arguments, Arguments, Environment, Expression, Function, Location, Name, Pattern,
+    Statement, Type, TypeDef,
+};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-type ErrorMessage = String;
+// The code of the problem, e.g. 'TypeError', and its message
+type ErrorMessage = (&'static str, String);
+
+// Maps solved type variables to their types
+type Substitution = HashMap<Name, Type>;
+
+static NEXT_TYPE_VAR: AtomicUsize = AtomicUsize::new(0);
 
 pub enum ControlFlow {
     Continue(Environment<Type>),
     Return(Type),
 }
 
-pub fn check_exp(exp: Expression, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+#[derive(Debug, Clone, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+// A problem found by the checker. The span is where the statement with the
+// problem starts, if the program was parsed with locations.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: String,
+    pub message: String,
+    pub span: Option<Location>,
+}
+
+impl Diagnostic {
+    fn error((code, message): ErrorMessage, span: Option<Location>) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            code: code.to_string(),
+            message,
+            span,
+        }
+    }
+
+    fn warning(code: &str, message: String, span: Option<Location>) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            code: code.to_string(),
+            message,
+            span,
+        }
+    }
+}
+
+// Where problems in statements go. A strict check stops at the first error,
+// while collecting records it and goes on with the next statement.
+struct Diagnostics {
+    strict: bool,
+    found: Vec<Diagnostic>,
+    span: Option<Location>, // Location of the statement being checked
+}
+
+impl Diagnostics {
+    fn strict() -> Diagnostics {
+        Diagnostics {
+            strict: true,
+            found: vec![],
+            span: None,
+        }
+    }
+
+    fn collecting() -> Diagnostics {
+        Diagnostics {
+            strict: false,
+            found: vec![],
+            span: None,
+        }
+    }
+
+    fn report(&mut self, message: ErrorMessage) -> Result<(), ErrorMessage> {
+        if self.strict {
+            return Err(message);
+        }
+        self.found.push(Diagnostic::error(message, self.span));
+        Ok(())
+    }
+
+    fn warn(&mut self, code: &str, message: String) {
+        if !self.strict {
+            self.found
+                .push(Diagnostic::warning(code, message, self.span));
+        }
+    }
+}
+
+pub fn check_exp(exp: Expression, env: &Environment<Type>) -> Result<Type, String> {
+    check_expression(exp, env, &mut Diagnostics::strict()).map_err(|(_, message)| message)
+}
+
+// While collecting, an ill-typed expression is recorded and given the error
+// type, so that the expressions around it are still checked
+fn check_expression(
+    exp: Expression,
+    env: &Environment<Type>,
+    diagnostics: &mut Diagnostics,
+) -> Result<Type, ErrorMessage> {
+    match check_expression_node(exp, env, diagnostics) {
+        Err(error) if !diagnostics.strict => {
+            diagnostics.report(error)?;
+            Ok(Type::TError)
+        }
+        result => result,
+    }
+}
+
+fn check_expression_node(
+    exp: Expression,
+    env: &Environment<Type>,
+    diagnostics: &mut Diagnostics,
+) -> Result<Type, ErrorMessage> {
     match exp {
         Expression::CTrue => Ok(Type::TBool),
         Expression::CFalse => Ok(Type::TBool),
@@ -15,1057 +127,4423 @@ pub fn check_exp(exp: Expression, env: &Environment<Type>) -> Result<Type, Error
         Expression::CInt(_) => Ok(Type::TInteger),
         Expression::CReal(_) => Ok(Type::TReal),
         Expression::CString(_) => Ok(Type::TString),
-        Expression::Add(l, r) => check_bin_arithmetic_expression(*l, *r, env),
-        Expression::Sub(l, r) => check_bin_arithmetic_expression(*l, *r, env),
-        Expression::Mul(l, r) => check_bin_arithmetic_expression(*l, *r, env),
-        Expression::Div(l, r) => check_bin_arithmetic_expression(*l, *r, env),
-        Expression::And(l, r) => check_bin_boolean_expression(*l, *r, env),
-        Expression::Or(l, r) => check_bin_boolean_expression(*l, *r, env),
-        Expression::Not(e) => check_not_expression(*e, env),
-        Expression::EQ(l, r) => check_bin_relational_expression(*l, *r, env),
-        Expression::GT(l, r) => check_bin_relational_expression(*l, *r, env),
-        Expression::LT(l, r) => check_bin_relational_expression(*l, *r, env),
-        Expression::GTE(l, r) => check_bin_relational_expression(*l, *r, env),
-        Expression::LTE(l, r) => check_bin_relational_expression(*l, *r, env),
-        Expression::Var(name) => check_var_name(name, env, false),
-
-        Expression::COk(e) => check_result_ok(*e, env),
-        Expression::CErr(e) => check_result_err(*e, env),
-        Expression::CJust(e) => check_maybe_just(*e, env),
-        Expression::CNothing => Ok(Type::TMaybe(Box::new(Type::TAny))),
-        Expression::IsError(e) => check_iserror_type(*e, env),
-        Expression::IsNothing(e) => check_isnothing_type(*e, env),
-        Expression::Unwrap(e) => check_unwrap_type(*e, env),
-        Expression::Propagate(e) => check_propagate_type(*e, env),
-        Expression::FuncCall(name, args) => check_func_call(name, args, env),
-        //_ => Err(String::from("not implemented yet")),
-    }
-}
-
-pub fn check_stmt(stmt: Statement, env: &Environment<Type>) -> Result<ControlFlow, ErrorMessage> {
+        Expression::Add(l, r) => check_bin_arithmetic_expression(*l, *r, env, diagnostics),
+        Expression::Sub(l, r) => check_bin_arithmetic_expression(*l, *r, env, diagnostics),
+        Expression::Mul(l, r) => check_bin_arithmetic_expression(*l, *r, env, diagnostics),
+        Expression::Div(l, r) => check_bin_arithmetic_expression(*l, *r, env, diagnostics),
+        Expression::And(l, r) => check_bin_boolean_expression(*l, *r, env, diagnostics),
+        Expression::Or(l, r) => check_bin_boolean_expression(*l, *r, env, diagnostics),
+        Expression::Not(e) => check_not_expression(*e, env, diagnostics),
+        Expression::EQ(l, r) => check_equality_expression(*l, *r, env, diagnostics),
+        Expression::GT(l, r) => check_bin_relational_expression(*l, *r, env, diagnostics),
+        Expression::LT(l, r) => check_bin_relational_expression(*l, *r, env, diagnostics),
+        Expression::GTE(l, r) => check_bin_relational_expression(*l, *r, env, diagnostics),
+        Expression::LTE(l, r) => check_bin_relational_expression(*l, *r, env, diagnostics),
+        Expression::Var(name) => check_var(name, env),
+
+        Expression::COk(e) => check_result_ok(*e, env, diagnostics),
+        Expression::CErr(e) => check_result_err(*e, env, diagnostics),
+        Expression::CJust(e) => check_maybe_just(*e, env, diagnostics),
+        Expression::CNothing => Ok(Type::TMaybe(Box::new(fresh_type_var()))),
+        Expression::IsError(e) => check_iserror_type(*e, env, diagnostics),
+        Expression::IsNothing(e) => check_isnothing_type(*e, env, diagnostics),
+        Expression::Unwrap(e) => check_unwrap_type(*e, env, diagnostics),
+        Expression::Propagate(e) => check_propagate_type(*e, env, diagnostics),
+        Expression::Map(e, f) => check_combinator("map", *e, *f, env, diagnostics),
+        Expression::AndThen(e, f) => check_combinator("and_then", *e, *f, env, diagnostics),
+        Expression::OrElse(e, f) => check_combinator("or_else", *e, *f, env, diagnostics),
+        Expression::UnwrapOr(e, d) => check_combinator("unwrap_or", *e, *d, env, diagnostics),
+        Expression::OkOr(e, err) => check_combinator("ok_or", *e, *err, env, diagnostics),
+        Expression::FuncCall(name, args) => check_func_call(name, args, env, diagnostics),
+        Expression::Call(callee, args) => match check_expression(*callee, env, diagnostics)? {
+            Type::TFunction(kind, params) => {
+                check_application("the function", *kind, params, None, args, env, diagnostics)
+            }
+            Type::TError => Ok(Type::TError),
+            kind => Err((
+                "TypeError",
+                format!(
+                    "[Type Error on '{}()'] '{:?}' is not a function.",
+                    env.scope_name(),
+                    kind
+                ),
+            )),
+        },
+        Expression::Lambda(func) => check_function(&func, env, diagnostics),
+        Expression::Match(exp, arms) => check_match_exp(*exp, arms, env, diagnostics),
+        Expression::KeywordArg(name, _) => Err((
+            "TypeError",
+            format!(
+                "[Type Error on '{}()'] keyword argument '{}' outside of a call.",
+                env.scope_name(),
+                name
+            ),
+        )),
+        Expression::Unpack(_) => Err((
+            "TypeError",
+            format!(
+                "[Type Error on '{}()'] unpacking is only allowed in the arguments of a call.",
+                env.scope_name()
+            ),
+        )),
+        Expression::FieldAccess(record, field) => {
+            check_field_access(*record, field, env, diagnostics)
+        } //_ => Err(String::from("not implemented yet")),
+    }
+}
+
+pub fn check_stmt(stmt: Statement, env: &Environment<Type>) -> Result<ControlFlow, String> {
+    let env = with_module_variables(std::slice::from_ref(&stmt), env);
+    check_statement(stmt, &env, &mut Diagnostics::strict()).map_err(|(_, message)| message)
+}
+
+// Check a whole program, reporting every error instead of stopping at the first
+pub fn check_program(stmts: Vec<Statement>, env: &Environment<Type>) -> Vec<Diagnostic> {
+    let env = with_module_variables(&stmts, env);
+    let mut diagnostics = Diagnostics::collecting();
+
+    // Errors are recorded while collecting, so the check itself cannot fail
+    let _ = check_block(stmts, &env, &mut diagnostics);
+    diagnostics.found
+}
+
+// Functions may read module variables assigned after them, so the module is
+// checked once beforehand to find the types its variables end up with
+fn with_module_variables(stmts: &[Statement], env: &Environment<Type>) -> Environment<Type> {
+    let mut new_env = env.clone();
+    if env.recursion != 0 {
+        return new_env;
+    }
+
+    let key = ("__main__".to_string(), 0);
+    if let Ok(ControlFlow::Continue(final_env)) =
+        check_block(stmts.to_vec(), env, &mut Diagnostics::collecting())
+    {
+        let variables = final_env.get_frame(key.clone()).variables.clone();
+        new_env.stack.get_mut(&key).unwrap().assigned_later = variables;
+    }
+    new_env
+}
+
+fn check_statement(
+    stmt: Statement,
+    env: &Environment<Type>,
+    diagnostics: &mut Diagnostics,
+) -> Result<ControlFlow, ErrorMessage> {
     let mut new_env = env.clone();
 
     match stmt {
         Statement::Assignment(name, exp, kind) => {
-            let exp_type = check_exp(*exp, &new_env)?;
+            if new_env.is_reassigning_val(&name) {
+                let (declaring_scope, _) = new_env.binding_key(&name);
+                return Err((
+                    "TypeError",
+                    format!(
+                        "[Type Error on '{}()'] cannot reassign '{}', declared with 'val' in '{}()'.",
+                        new_env.scope_name(),
+                        name,
+                        declaring_scope
+                    ),
+                ));
+            }
 
+            let exp_type = check_expression(*exp, &new_env, diagnostics)?;
+
+            let mut subst = Substitution::new();
+
+            // The parser-inferred type is only an annotation on the expression
             if let Some(state_type) = kind {
-                if exp_type != state_type {
-                    return Err(format!("[Type Error on '{}()'] '{}' has mismatched types: expected '{:?}', found '{:?}'.", new_env.scope_name(), name, state_type, exp_type));
+                check_type_defined(&state_type, &new_env)?;
+
+                if !unify(&state_type, &exp_type, &mut subst) {
+                    return Err(("TypeError", format!("[Type Error on '{}()'] '{}' has mismatched types: expected '{:?}', found '{:?}'.", new_env.scope_name(), name, apply(&subst, &state_type), apply(&subst, &exp_type))));
                 }
-            } else {
-                let stated_type = check_var_name(name.clone(), &new_env, true)?;
+            }
+
+            // A type stated on the declaration holds for every assignment
+            if let Some(declared_type) = new_env.declared_type(&name).cloned() {
+                if !unify(&declared_type, &exp_type, &mut subst) {
+                    return Err(("TypeError", format!("[Type Error on '{}()'] '{}' has mismatched types: expected '{:?}', found '{:?}'.", new_env.scope_name(), name, apply(&subst, &declared_type), apply(&subst, &exp_type))));
+                }
+            }
 
-                if exp_type != stated_type {
-                    return Err(format!("[Type Error on '{}()'] '{}' has mismatched types: expected '{:?}', found '{:?}'.", new_env.scope_name(), name, stated_type, exp_type));
+            // A first assignment infers the variable's type; reassignments must keep it.
+            // Assignments bind local names, unless declared 'global' or 'nonlocal'.
+            if new_env.search_binding(&name).is_some() {
+                let stated_type = check_var_name(name.clone(), &new_env, true)?;
+                if !unify(&stated_type, &exp_type, &mut subst) {
+                    return Err(("TypeError", format!("[Type Error on '{}()'] '{}' has mismatched types: expected '{:?}', found '{:?}'.", new_env.scope_name(), name, apply(&subst, &stated_type), apply(&subst, &exp_type))));
                 }
             }
 
+            apply_env(&subst, &mut new_env);
+            let exp_type = apply(&subst, &exp_type);
             new_env.insert_variable(name, exp_type);
 
             Ok(ControlFlow::Continue(new_env))
         }
-        Statement::IfThenElse(exp, stmt_then, option) => {
-            let exp_type = check_exp(*exp, &new_env)?;
-
-            if exp_type != Type::TBool {
-                return Err(format!(
-                    "[Type Error on '{}()'] if expression must be boolean.",
-                    new_env.scope_name()
+        Statement::FieldAssignment(record, field, exp) => {
+            if !is_field_of_variable(&record) {
+                return Err((
+                    "TypeError",
+                    format!(
+                        "[Type Error on '{}()'] can only assign to fields of variables.",
+                        new_env.scope_name()
+                    ),
                 ));
             }
+            let field_type = check_field_access(*record, field.clone(), &new_env, diagnostics)?;
+            let exp_type = check_expression(*exp, &new_env, diagnostics)?;
+            let mut subst = Substitution::new();
 
-            let stmt_then_result = check_stmt(*stmt_then, &new_env)?;
-            let stmt_else_result = match option {
-                Some(stmt_else) => check_stmt(*stmt_else, &new_env)?,
-                None => return Ok(ControlFlow::Continue(new_env)),
-            };
-
-            match (stmt_then_result, stmt_else_result) {
-                (ControlFlow::Return(kind), ControlFlow::Continue(_)) => {
-                    Ok(ControlFlow::Return(kind))
-                }
-                (ControlFlow::Continue(_), ControlFlow::Return(kind)) => {
-                    Ok(ControlFlow::Return(kind))
-                }
-                (ControlFlow::Return(kind1), ControlFlow::Return(_)) => {
-                    Ok(ControlFlow::Return(kind1))
-                }
-                _ => Ok(ControlFlow::Continue(new_env)),
+            if !unify(&field_type, &exp_type, &mut subst) {
+                return Err(("TypeError", format!("[Type Error on '{}()'] field '{}' has mismatched types: expected '{:?}', found '{:?}'.", new_env.scope_name(), field, field_type, apply(&subst, &exp_type))));
             }
-        }
-        Statement::While(exp, stmt_while) => {
-            let exp_type = check_exp(*exp, &new_env)?;
 
-            if exp_type != Type::TBool {
-                return Err(format!(
-                    "[Type Error on '{}()'] while expression must be boolean.",
-                    new_env.scope_name()
+            Ok(ControlFlow::Continue(new_env))
+        }
+        Statement::StructDef(name, fields) => {
+            if new_env.search_type(&name).is_some() {
+                return Err((
+                    "TypeError",
+                    format!(
+                        "[Type Error on '{}()'] type '{}' is already defined.",
+                        new_env.scope_name(),
+                        name
+                    ),
                 ));
             }
 
-            match check_stmt(*stmt_while, &new_env)? {
-                ControlFlow::Continue(_) => Ok(ControlFlow::Continue(new_env)),
-                ControlFlow::Return(kind) => Ok(ControlFlow::Return(kind)),
-            }
-        }
-        Statement::Sequence(stmt1, stmt2) => {
-            if let ControlFlow::Continue(control_env) = check_stmt(*stmt1, &new_env)? {
-                new_env = control_env;
+            // Fields may refer to the struct being defined
+            new_env.insert_type(name.clone(), TypeDef::Struct(fields.clone()));
+
+            let mut seen_fields = std::collections::HashSet::new();
+            for (field, kind) in &fields {
+                if !seen_fields.insert(field) {
+                    return Err((
+                        "TypeError",
+                        format!(
+                            "[Type Error on '{}()'] '{}' has duplicate field '{}'.",
+                            new_env.scope_name(),
+                            name,
+                            field
+                        ),
+                    ));
+                }
+                check_type_defined(kind, &new_env)?;
             }
-            check_stmt(*stmt2, &new_env)
-        }
-        Statement::FuncDef(func) => {
-            new_env.insert_frame(func.clone());
 
-            let mut type_vec = vec![];
+            // The constructor takes the fields in declaration order
+            let field_types = fields.into_iter().map(|(_, kind)| kind).collect();
+            new_env.insert_variable(
+                name.clone(),
+                Type::TFunction(Box::new(Some(Type::TUser(name))), field_types),
+            );
 
-            if let Some(params) = func.params.clone() {
-                // Adicionamos a verificação de parâmetros duplicados
-                check_duplicate_params(&params)?;
+            Ok(ControlFlow::Continue(new_env))
+        }
+        Statement::EnumDef(name, constructors) => {
+            if new_env.search_type(&name).is_some() {
+                return Err((
+                    "TypeError",
+                    format!(
+                        "[Type Error on '{}()'] type '{}' is already defined.",
+                        new_env.scope_name(),
+                        name
+                    ),
+                ));
+            }
 
-                for (param_name, param_kind) in params {
-                    new_env.insert_variable(param_name, param_kind.clone());
-                    type_vec.push(param_kind);
+            for (constructor, _) in &constructors {
+                if new_env.search_constructor(constructor).is_some()
+                    || constructors
+                        .iter()
+                        .filter(|(c, _)| c == constructor)
+                        .count()
+                        > 1
+                {
+                    return Err((
+                        "TypeError",
+                        format!(
+                            "[Type Error on '{}()'] constructor '{}' is already defined.",
+                            new_env.scope_name(),
+                            constructor
+                        ),
+                    ));
                 }
             }
 
-            let func_type = Type::TFunction(Box::new(func.kind), type_vec);
+            // Payloads may refer to the enum being defined
+            new_env.insert_type(name.clone(), TypeDef::Enum(constructors.clone()));
+
+            // Constructors with a payload are functions, the others are values
+            for (constructor, payload) in constructors {
+                payload
+                    .iter()
+                    .try_for_each(|kind| check_type_defined(kind, &new_env))?;
 
-            if let None = new_env.search_frame(func.name.clone()) {
-                new_env.insert_variable(func.name.clone(), func_type.clone());
+                let enum_type = Type::TUser(name.clone());
+                let constructor_type = if payload.is_empty() {
+                    enum_type
+                } else {
+                    Type::TFunction(Box::new(Some(enum_type)), payload)
+                };
+                new_env.insert_variable(constructor, constructor_type);
             }
 
-            match check_stmt(*func.body.unwrap(), &new_env)? {
-                ControlFlow::Continue(_) => Err(format!(
-                    "[Syntax Error] '{}()' does not have a return statement.",
-                    func.name
-                )),
-                ControlFlow::Return(_) => {
-                    new_env.remove_frame();
-                    new_env.insert_variable(func.name, func_type);
-                    Ok(ControlFlow::Continue(new_env))
+            Ok(ControlFlow::Continue(new_env))
+        }
+        Statement::Match(exp, arms) => check_match(*exp, arms, new_env, diagnostics),
+        Statement::IfThenElse(exp, stmt_then, option) => {
+            check_condition("if", *exp, &new_env, diagnostics)?;
+
+            let stmt_then_result = check_nested(*stmt_then, &new_env, diagnostics)?;
+            let stmt_else_result = match option {
+                Some(stmt_else) => check_nested(*stmt_else, &new_env, diagnostics)?,
+                // Without an else, the variables may be left as they were
+                None => ControlFlow::Continue(new_env.clone()),
+            };
+
+            // The if returns only if both of its branches do
+            match (stmt_then_result, stmt_else_result) {
+                (ControlFlow::Return(kind), ControlFlow::Return(_)) => {
+                    Ok(ControlFlow::Return(kind))
+                }
+                (then_result, else_result) => {
+                    let branches = continuing_envs(vec![then_result, else_result]);
+                    Ok(ControlFlow::Continue(merge_branches(new_env, branches)?))
                 }
             }
         }
+        Statement::While(exp, stmt_while) => {
+            check_condition("while", *exp, &new_env, diagnostics)?;
+
+            // The body may run zero times, so a return inside it does not count
+            check_nested(*stmt_while, &new_env, diagnostics)?;
+            Ok(ControlFlow::Continue(new_env))
+        }
+        Statement::Sequence(stmt1, stmt2) => {
+            check_block(vec![*stmt1, *stmt2], &new_env, diagnostics)
+        }
+        Statement::Block(stmts) => check_block(stmts, &new_env, diagnostics),
+        Statement::FuncDef(func) => {
+            let func_type = check_function(&func, &new_env, diagnostics)?;
+            new_env.insert_variable(func.name.clone(), func_type);
+            new_env.insert_function(func);
+            Ok(ControlFlow::Continue(new_env))
+        }
         Statement::Return(exp) => {
-            let exp_type = check_exp(*exp, &new_env)?;
+            let exp_type = check_expression(*exp, &new_env, diagnostics)?;
 
             if let Some(Type::TFunction(func_type, _)) = new_env.scope_return() {
-                if exp_type != func_type.clone().unwrap() {
-                    return Err(format!(
-                        "[Type Error] '{}()' has mismatched types: expected '{:?}', found '{:?}'.",
-                        new_env.scope_name(),
-                        func_type.clone().unwrap(),
-                        exp_type
+                let func_type = func_type.clone().unwrap_or(Type::TVoid);
+                let mut subst = Substitution::new();
+
+                if !unify(&func_type, &exp_type, &mut subst) {
+                    return Err((
+                        "TypeError",
+                        format!(
+                            "[Type Error] '{}()' has mismatched types: expected '{:?}', found '{:?}'.",
+                            new_env.scope_name(),
+                            func_type,
+                            apply(&subst, &exp_type)
+                        ),
                     ));
                 }
 
-                Ok(ControlFlow::Return(exp_type))
+                Ok(ControlFlow::Return(apply(&subst, &exp_type)))
             } else {
-                Err(format!("[Syntax Error] return statement outside function."))
+                Err((
+                    "SyntaxError",
+                    "[Syntax Error] return statement outside function.".to_string(),
+                ))
             }
         }
-        _ => Err(String::from("not implemented yet.")),
+        Statement::Global(names) => check_outer_declaration(names, true, new_env),
+        Statement::Nonlocal(names) => check_outer_declaration(names, false, new_env),
+        Statement::VarDeclaration(name, kind) => check_declaration(name, kind, true, new_env),
+        Statement::ValDeclaration(name, kind) => check_declaration(name, kind, false, new_env),
+        // Syntax errors were already reported by the parser; keep checking the rest
+        Statement::Invalid(_) => Ok(ControlFlow::Continue(new_env)),
+        // Problems found in the statement are reported at its location
+        Statement::Located(location, stmt) => {
+            let enclosing = diagnostics.span.replace(location);
+            let result = check_nested(*stmt, &new_env, diagnostics);
+            diagnostics.span = enclosing;
+            result
+        }
+        Statement::AssertTrue(exp, _) | Statement::AssertFalse(exp, _) => {
+            check_condition("assert", *exp, &new_env, diagnostics)?;
+            Ok(ControlFlow::Continue(new_env))
+        }
+        Statement::AssertEQ(left, right, _) | Statement::AssertNEQ(left, right, _) => {
+            check_equality_expression(*left, *right, &new_env, diagnostics)?;
+            Ok(ControlFlow::Continue(new_env))
+        }
+        Statement::AssertFails(_) => Ok(ControlFlow::Continue(new_env)),
+        Statement::TestDef(mut test) => {
+            // Tests are run without arguments and their result is ignored
+            if test
+                .params
+                .as_ref()
+                .is_some_and(|params| !params.is_empty())
+            {
+                return Err((
+                    "TypeError",
+                    format!(
+                        "[Type Error on '{}()'] test '{}' cannot take parameters.",
+                        new_env.scope_name(),
+                        test.name
+                    ),
+                ));
+            }
+            test.kind = Some(Type::TVoid);
+
+            check_function(&test, &new_env, diagnostics)?;
+            new_env.insert_test(test.name.clone(), test);
+            Ok(ControlFlow::Continue(new_env))
+        }
+        // Definitions inside a test module are not seen outside of it
+        Statement::ModTestDef(_, stmt) => match check_nested(*stmt, &new_env, diagnostics)? {
+            ControlFlow::Continue(_) => Ok(ControlFlow::Continue(new_env)),
+            ControlFlow::Return(kind) => Ok(ControlFlow::Return(kind)),
+        },
     }
 }
 
-fn check_func_call(
-    name: String,
-    args: Vec<Expression>,
+// Check a statement nested in another. When collecting, a statement with an
+// error is recorded and skipped, with the names it binds given the error type
+// so that their later uses are not reported again.
+fn check_nested(
+    stmt: Statement,
     env: &Environment<Type>,
-) -> Result<Type, ErrorMessage> {
-    match check_var_name(name.clone(), env, false) {
-        Ok(Type::TFunction(kind, type_vec)) => {
-            if args.len() != type_vec.len() {
-                return Err(format!(
-                    "[Type Error on '{}()'] '{}()' expected {} arguments, found {}.",
-                    env.scope_name(),
-                    name,
-                    type_vec.len(),
-                    args.len()
-                ));
-            }
-
-            for (arg, param_type) in args.iter().zip(type_vec) {
-                let arg_type = check_exp(arg.clone(), env)?;
-                if arg_type != param_type {
-                    return Err(format!("[Type Error on '{}()'] '{}()' has mismatched arguments: expected '{:?}', found '{:?}'.", env.scope_name(), name, param_type, arg_type));
-                }
-            }
+    diagnostics: &mut Diagnostics,
+) -> Result<ControlFlow, ErrorMessage> {
+    if diagnostics.strict {
+        return check_statement(stmt, env, diagnostics);
+    }
 
-            Ok(kind.unwrap())
+    match check_statement(stmt.clone(), env, diagnostics) {
+        Err(message) => {
+            diagnostics.report(message)?;
+            Ok(recover(&stmt, env))
         }
-        _ => Err(format!(
-            "[Name Error on '{}()'] '{}()' is not defined.",
-            env.scope_name(),
-            name
-        )),
+        result => result,
     }
 }
 
-fn check_duplicate_params(params: &Vec<(Name, Type)>) -> Result<(), ErrorMessage> {
-    let mut seen_params = std::collections::HashSet::new();
+fn recover(stmt: &Statement, env: &Environment<Type>) -> ControlFlow {
+    let mut new_env = env.clone();
 
-    for (name, _) in params {
-        if !seen_params.insert(name.clone()) {
-            return Err(format!(
-                "[Parameter Error] Duplicate parameter name '{}'",
-                name
-            ));
+    match stmt {
+        Statement::Assignment(name, _, _) if new_env.search_binding(name).is_none() => {
+            new_env.insert_variable(name.clone(), Type::TError)
         }
+        // Calls are still checked against the declared signature
+        Statement::FuncDef(func) => {
+            let params = func.params.iter().flatten().map(|(_, kind)| kind.clone());
+            let func_type = Type::TFunction(Box::new(func.kind.clone()), params.collect());
+            new_env.insert_variable(func.name.clone(), func_type);
+            new_env.insert_function(func.clone());
+        }
+        Statement::Return(_) => return ControlFlow::Return(Type::TError),
+        _ => (),
     }
+    ControlFlow::Continue(new_env)
+}
 
-    Ok(())
+// Conditions of 'if' and 'while' must be boolean
+fn check_condition(
+    statement: &str,
+    exp: Expression,
+    env: &Environment<Type>,
+    diagnostics: &mut Diagnostics,
+) -> Result<(), ErrorMessage> {
+    match check_expression(exp, env, diagnostics) {
+        Ok(Type::TBool | Type::TError) => Ok(()),
+        Ok(_) => diagnostics.report((
+            "TypeError",
+            format!(
+                "[Type Error on '{}()'] {} expression must be boolean.",
+                env.scope_name(),
+                statement
+            ),
+        )),
+        Err(message) => diagnostics.report(message),
+    }
 }
 
-fn check_var_name(name: Name, env: &Environment<Type>, scoped: bool) -> Result<Type, ErrorMessage> {
-    let mut curr_scope = env.scope_key();
+// The initializer, if any, is checked by the Assignment that follows
+fn check_declaration(
+    name: Name,
+    kind: Option<Type>,
+    mutable: bool,
+    mut env: Environment<Type>,
+) -> Result<ControlFlow, ErrorMessage> {
+    if env.search_frame(name.clone()).is_some() {
+        return Err((
+            "NameError",
+            format!(
+                "[Name Error on '{}()'] '{}' is already declared.",
+                env.scope_name(),
+                name
+            ),
+        ));
+    }
 
-    loop {
-        let frame = env.get_frame(curr_scope.clone());
+    if let Some(kind) = &kind {
+        check_type_defined(kind, &env)?;
+    }
+    env.declare_variable(name, mutable, kind);
+    Ok(ControlFlow::Continue(env))
+}
 
-        match frame.variables.get(&name) {
-            Some(kind) => {
-                if scoped && curr_scope != env.scope_key() {
-                    return Err(format!(
-                        "[Local Name Error on '{}'] cannot access local variable '{}'.",
-                        env.scope_name(),
-                        name
-                    ));
-                } else {
-                    return Ok(kind.clone());
+// 'global' and 'nonlocal' make a function's assignments to a name write to
+// the module's binding, or to the nearest enclosing function's
+fn check_outer_declaration(
+    names: Vec<Name>,
+    global: bool,
+    mut env: Environment<Type>,
+) -> Result<ControlFlow, ErrorMessage> {
+    let kind = if global { "global" } else { "nonlocal" };
+
+    if env.recursion == 0 {
+        if global {
+            return Ok(ControlFlow::Continue(env));
+        }
+        return Err((
+            "SyntaxError",
+            String::from("[Syntax Error] nonlocal declaration not allowed at module level."),
+        ));
+    }
+
+    for name in names {
+        if env.search_frame(name.clone()).is_some() {
+            return Err((
+                "NameError",
+                format!(
+                    "[Name Error on '{}()'] '{}' is assigned to before {} declaration.",
+                    env.scope_name(),
+                    name,
+                    kind
+                ),
+            ));
+        }
+        match env.search_outer_frame(&name, global) {
+            Some(key) => env.declare_outer(name, key),
+            // The module may assign a global after the function is defined, so the
+            // name has the type it ends up with there
+            None if global => {
+                let key = ("__main__".to_string(), 0);
+                let module = env.stack.get_mut(&key).unwrap();
+                if let Some(kind) = module.assigned_later.get(&name).cloned() {
+                    module.variables.insert(name.clone(), kind);
                 }
+                env.declare_outer(name, key)
             }
-            None => match &frame.parent_key {
-                Some(parent) => curr_scope = parent.clone(),
-                None => {
-                    return Err(format!(
-                        "[Name Error on '{}'] '{}' is not defined.",
+            None => {
+                return Err((
+                    "NameError",
+                    format!(
+                        "[Name Error on '{}()'] no binding for {} '{}' found.",
                         env.scope_name(),
+                        kind,
                         name
-                    ))
-                }
-            },
+                    ),
+                ))
+            }
         }
     }
+    Ok(ControlFlow::Continue(env))
 }
 
-fn check_bin_arithmetic_expression(
-    left: Expression,
-    right: Expression,
+fn check_func_call(
+    name: String,
+    args: Vec<Expression>,
     env: &Environment<Type>,
+    diagnostics: &mut Diagnostics,
 ) -> Result<Type, ErrorMessage> {
-    let left_type = check_exp(left, env)?;
-    let right_type = check_exp(right, env)?;
-
-    match (left_type, right_type) {
-        (Type::TInteger, Type::TInteger) => Ok(Type::TInteger),
-        (Type::TInteger, Type::TReal) => Ok(Type::TReal),
-        (Type::TReal, Type::TInteger) => Ok(Type::TReal),
-        (Type::TReal, Type::TReal) => Ok(Type::TReal),
-        _ => Err(String::from("[Type Error] expecting numeric type values.")),
+    match check_var(name.clone(), env) {
+        Ok(Type::TFunction(kind, params)) => {
+            let callee = format!("'{}()'", name);
+            let signature = env.search_function(&name);
+            check_application(&callee, *kind, params, signature, args, env, diagnostics)
+        }
+        Ok(Type::TError) => Ok(Type::TError),
+        _ => Err((
+            "NameError",
+            format!(
+                "[Name Error on '{}()'] '{}()' is not defined.",
+                env.scope_name(),
+                name
+            ),
+        )),
     }
 }
 
-fn check_bin_boolean_expression(
-    left: Expression,
-    right: Expression,
+// Check the arguments of a call against the parameters of the callee's type.
+// Keyword arguments, defaults and variadic parameters need the callee's
+// definition; calls of other function values only take positional arguments.
+fn check_application(
+    callee: &str,
+    kind: Option<Type>,
+    params: Vec<Type>,
+    signature: Option<&Function>,
+    args: Vec<Expression>,
     env: &Environment<Type>,
+    diagnostics: &mut Diagnostics,
 ) -> Result<Type, ErrorMessage> {
-    let left_type = check_exp(left, env)?;
-    let right_type = check_exp(right, env)?;
-    match (left_type, right_type) {
-        (Type::TBool, Type::TBool) => Ok(Type::TBool),
-        _ => Err(String::from("[Type Error] expecting boolean type values.")),
+    let (args, spread) = check_arguments(args, env, diagnostics)?;
+    let variadic = signature
+        .filter(|func| func.variadic)
+        .map(|func| func.name.clone());
+
+    // A list has no known length, so it may only be unpacked into a variadic parameter
+    if !spread.is_empty() {
+        let positional = args.iter().filter(|(name, _)| name.is_none()).count();
+        if variadic.is_none() || positional + 1 < params.len() {
+            return Err(("TypeError", format!(
+                "[Type Error on '{}()'] {} can only take an unpacked list as its variadic parameter.",
+                env.scope_name(),
+                callee
+            )));
+        }
+    }
+
+    let (bound, extra) = match signature {
+        Some(func) => func.bind_arguments(args).map_err(|e| {
+            (
+                "TypeError",
+                format!("[Type Error on '{}()'] {}", env.scope_name(), e),
+            )
+        })?,
+        None => {
+            if let Some((Some(name), _)) = args.iter().find(|(name, _)| name.is_some()) {
+                return Err(("TypeError", format!(
+                    "[Type Error on '{}()'] {} has no parameter names, found keyword argument '{}'.",
+                    env.scope_name(),
+                    callee,
+                    name
+                )));
+            }
+            if args.len() != params.len() {
+                return Err((
+                    "TypeError",
+                    format!(
+                        "[Type Error on '{}()'] {} expected {}, found {}.",
+                        env.scope_name(),
+                        callee,
+                        count_arguments(params.len()),
+                        args.len()
+                    ),
+                ));
+            }
+            let bound = args.into_iter().map(|(_, arg)| (String::new(), Some(arg)));
+            (bound.collect(), vec![])
+        }
+    };
+
+    // Extra arguments, and those from an unpacked list on, go to the variadic parameter
+    let mut found: Vec<(Type, Type)> = vec![];
+    if variadic.is_some() {
+        if let Some(Type::TList(element)) = params.last() {
+            found.extend(extra.into_iter().map(|arg| (*element.clone(), arg)));
+            found.extend(spread.into_iter().map(|arg| (*element.clone(), arg)));
+        }
+    }
+    // Parameters without an argument take their default, checked with the definition
+    for ((_, arg), param_type) in bound.into_iter().zip(params) {
+        if let Some(arg) = arg {
+            found.push((param_type, arg));
+        }
     }
-}
 
-fn check_not_expression(exp: Expression, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
-    let exp_type = check_exp(exp, env)?;
+    let mut subst = Substitution::new();
 
-    match exp_type {
-        Type::TBool => Ok(Type::TBool),
-        _ => Err(String::from("[Type Error] expecting a boolean type value.")),
+    for (param_type, arg_type) in found {
+        if !unify(&param_type, &arg_type, &mut subst) {
+            return Err(("TypeError", format!("[Type Error on '{}()'] {} has mismatched arguments: expected '{:?}', found '{:?}'.", env.scope_name(), callee, apply(&subst, &param_type), apply(&subst, &arg_type))));
+        }
     }
+
+    Ok(apply(&subst, &kind.unwrap()))
 }
 
-fn check_bin_relational_expression(
-    left: Expression,
-    right: Expression,
+// Type the arguments of a call. The element type of an unpacked list, and the
+// positional arguments after it, are returned apart: a list has no known
+// length, so they can only go to a variadic parameter.
+fn check_arguments(
+    args: Vec<Expression>,
     env: &Environment<Type>,
-) -> Result<Type, ErrorMessage> {
-    let left_type = check_exp(left, env)?;
-    let right_type = check_exp(right, env)?;
-
-    match (left_type, right_type) {
-        (Type::TInteger, Type::TInteger) => Ok(Type::TBool),
-        (Type::TInteger, Type::TReal) => Ok(Type::TBool),
-        (Type::TReal, Type::TInteger) => Ok(Type::TBool),
-        (Type::TReal, Type::TReal) => Ok(Type::TBool),
-        _ => Err(String::from("[Type Error] expecting numeric type values.")),
+    diagnostics: &mut Diagnostics,
+) -> Result<(Arguments<Type>, Vec<Type>), ErrorMessage> {
+    let mut typed = vec![];
+    let mut spread = vec![];
+
+    for arg in args {
+        match arg {
+            Expression::KeywordArg(name, exp) => {
+                typed.push((Some(name), check_expression(*exp, env, diagnostics)?))
+            }
+            Expression::Unpack(exp) => match check_expression(*exp, env, diagnostics)? {
+                Type::TList(element) => spread.push(*element),
+                Type::TError => spread.push(Type::TError),
+                kind => {
+                    return Err((
+                        "TypeError",
+                        format!(
+                            "[Type Error on '{}()'] cannot unpack '{:?}', expected a list.",
+                            env.scope_name(),
+                            kind
+                        ),
+                    ))
+                }
+            },
+            exp if !spread.is_empty() => spread.push(check_expression(exp, env, diagnostics)?),
+            exp => typed.push((None, check_expression(exp, env, diagnostics)?)),
+        }
     }
+    Ok((typed, spread))
 }
 
-fn check_result_ok(exp: Expression, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
-    let exp_type = check_exp(exp, env)?;
-    return Ok(Type::TResult(Box::new(exp_type), Box::new(Type::TAny)));
-}
+// Check a function definition or lambda, giving its type. The function's own
+// name is bound in its frame, for recursion and to check return statements.
+fn check_function(
+    func: &Function,
+    env: &Environment<Type>,
+    diagnostics: &mut Diagnostics,
+) -> Result<Type, ErrorMessage> {
+    let mut new_env = env.clone();
+    new_env.insert_frame(func.clone());
 
-fn check_result_err(exp: Expression, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
-    let exp_type = check_exp(exp, env)?;
-    return Ok(Type::TResult(Box::new(Type::TAny), Box::new(exp_type)));
-}
+    let mut type_vec = vec![];
 
-fn check_unwrap_type(exp: Expression, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
-    let exp_type = check_exp(exp, env)?;
+    if let Some(kind) = &func.kind {
+        check_type_defined(kind, &new_env)?;
+    }
 
-    match exp_type {
-        Type::TMaybe(t) => Ok(*t),
-        Type::TResult(tl, _) => Ok(*tl),
-        _ => Err(String::from(
-            "[Type Error] expecting a maybe or result type value.",
-        )),
+    if let Some(params) = func.params.clone() {
+        // Adicionamos a verificação de parâmetros duplicados
+        check_duplicate_params(&params, &func.defaults)?;
+
+        for (param_name, param_kind) in params {
+            check_type_defined(&param_kind, &new_env)?;
+            check_default(func, &param_name, &param_kind, env, diagnostics)?;
+            new_env.insert_variable(param_name, param_kind.clone());
+            type_vec.push(param_kind);
+        }
     }
-}
 
-fn check_propagate_type(exp: Expression, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
-    let exp_type = check_exp(exp, env)?;
+    let func_type = Type::TFunction(Box::new(func.kind.clone()), type_vec);
 
-    match exp_type {
-        Type::TMaybe(t) => Ok(*t),
-        Type::TResult(tl, _) => Ok(*tl),
-        _ => Err(String::from(
-            "[Type Error] expecting a maybe or result type value.",
-        )),
+    if let None = new_env.search_frame(func.name.clone()) {
+        new_env.insert_variable(func.name.clone(), func_type.clone());
+        new_env.insert_function(func.clone());
     }
-}
 
-fn check_maybe_just(exp: Expression, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
-    let exp_type = check_exp(exp, env)?;
-    Ok(Type::TMaybe(Box::new(exp_type)))
+    match check_nested(*func.body.clone().unwrap(), &new_env, diagnostics)? {
+        ControlFlow::Continue(_) if func.returns_void() => (),
+        ControlFlow::Continue(_) if has_return(func.body.as_ref().unwrap()) => {
+            diagnostics.report((
+                "TypeError",
+                format!(
+                    "[Type Error on '{}()'] not every path returns a value of type '{:?}'.",
+                    func.name,
+                    func.kind.clone().unwrap()
+                ),
+            ))?
+        }
+        ControlFlow::Continue(_) => diagnostics.report((
+            "SyntaxError",
+            format!(
+                "[Syntax Error] '{}()' does not have a return statement.",
+                func.name
+            ),
+        ))?,
+        ControlFlow::Return(_) => (),
+    }
+    Ok(func_type)
 }
 
-fn check_iserror_type(exp: Expression, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
-    let v = check_exp(exp, env)?;
+// Whether a statement has a return anywhere, outside of nested functions
+fn has_return(stmt: &Statement) -> bool {
+    match stmt {
+        Statement::Return(_) => true,
+        Statement::Sequence(stmt1, stmt2) => has_return(stmt1) || has_return(stmt2),
+        Statement::IfThenElse(_, stmt_then, stmt_else) => {
+            has_return(stmt_then) || stmt_else.as_deref().is_some_and(has_return)
+        }
+        Statement::While(_, stmt) => has_return(stmt),
+        Statement::Block(stmts) => stmts.iter().any(has_return),
+        Statement::Located(_, stmt) => has_return(stmt),
+        Statement::Match(_, arms) => arms.iter().any(|(_, stmt)| has_return(stmt)),
+        _ => false,
+    }
+}
 
-    match v {
-        Type::TResult(_, _) => Ok(Type::TBool),
-        _ => Err(String::from("[Type Error] expecting a result type value.")),
+// Check statements in order, stopping at the first one that returns
+fn check_block(
+    stmts: Vec<Statement>,
+    env: &Environment<Type>,
+    diagnostics: &mut Diagnostics,
+) -> Result<ControlFlow, ErrorMessage> {
+    let mut current_env = env.clone();
+    let mut stmts = stmts.into_iter();
+
+    while let Some(stmt) = stmts.next() {
+        match check_nested(stmt, &current_env, diagnostics)? {
+            ControlFlow::Continue(new_env) => current_env = new_env,
+            ControlFlow::Return(kind) => {
+                if let Some(unreachable) = stmts.next() {
+                    // Reported where the first statement never run starts
+                    let enclosing = diagnostics.span;
+                    if let Statement::Located(location, _) = unreachable {
+                        diagnostics.span = Some(location);
+                    }
+                    diagnostics.warn(
+                        "UnreachableCode",
+                        format!(
+                            "[Warning on '{}()'] code after a return statement is never run.",
+                            current_env.scope_name()
+                        ),
+                    );
+                    diagnostics.span = enclosing;
+                }
+                return Ok(ControlFlow::Return(kind));
+            }
+        }
     }
+    Ok(ControlFlow::Continue(current_env))
 }
 
-fn check_isnothing_type(exp: Expression, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
-    let exp_type = check_exp(exp, env)?;
+fn check_match(
+    exp: Expression,
+    arms: Vec<(Pattern, Statement)>,
+    env: Environment<Type>,
+    diagnostics: &mut Diagnostics,
+) -> Result<ControlFlow, ErrorMessage> {
+    let (patterns, stmts): (Vec<_>, Vec<_>) = arms.into_iter().unzip();
+    let arm_envs = check_patterns(exp, &patterns, &env, diagnostics)?;
+
+    let mut returns = vec![];
+    let mut results = vec![];
+    for (stmt, arm_env) in stmts.into_iter().zip(arm_envs) {
+        match check_nested(stmt, &arm_env, diagnostics)? {
+            ControlFlow::Return(kind) => returns.push(kind),
+            result => results.push(result),
+        }
+    }
 
-    match exp_type {
-        Type::TMaybe(_) => Ok(Type::TBool),
-        _ => Err(String::from("[Type Error] expecting a maybe type value.")),
+    // The match returns only if all of its arms do
+    if !returns.is_empty() && returns.len() == patterns.len() {
+        Ok(ControlFlow::Return(unify_arms(returns, &env)?))
+    } else {
+        let arms = continuing_envs(results);
+        Ok(ControlFlow::Continue(merge_branches(env, arms)?))
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    use crate::ir::ast::Environment;
-    use crate::ir::ast::Expression::*;
-    use crate::ir::ast::Function;
-    use crate::ir::ast::Statement::*;
-    use crate::ir::ast::Type::*;
+fn continuing_envs(results: Vec<ControlFlow>) -> Vec<Environment<Type>> {
+    results
+        .into_iter()
+        .filter_map(|result| match result {
+            ControlFlow::Continue(env) => Some(env),
+            ControlFlow::Return(_) => None,
+        })
+        .collect()
+}
 
-    #[test]
-    fn check_tlist_comparison() {
-        let t_list1 = TList(Box::new(TInteger));
-        let t_list2 = TList(Box::new(TInteger));
+// After a branching statement, the variables bound by every branch that goes
+// on are bound, with the types the branches give them unified
+fn merge_branches(
+    env: Environment<Type>,
+    branches: Vec<Environment<Type>>,
+) -> Result<Environment<Type>, ErrorMessage> {
+    let Some(first) = branches.first() else {
+        return Ok(env);
+    };
+    let key = env.scope_key();
+    let mut names: Vec<&Name> = first.get_frame(key.clone()).variables.keys().collect();
+    names.sort();
 
-        assert_eq!(t_list1 == t_list2, true);
+    let mut new_env = env.clone();
+    for name in names {
+        let types: Option<Vec<&Type>> = branches
+            .iter()
+            .map(|branch| branch.get_frame(key.clone()).variables.get(name))
+            .collect();
+        // Not bound on every branch
+        let Some(types) = types else {
+            continue;
+        };
+
+        let mut subst = Substitution::new();
+        for other in &types[1..] {
+            if !unify(types[0], other, &mut subst) {
+                return Err(("TypeError", format!("[Type Error on '{}()'] '{}' has mismatched types: expected '{:?}', found '{:?}'.", env.scope_name(), name, apply(&subst, types[0]), apply(&subst, other))));
+            }
+        }
+        new_env.insert_variable(name.clone(), apply(&subst, types[0]));
     }
+    Ok(new_env)
+}
 
-    #[test]
-    fn check_tlist_comparison_different_types() {
-        let t_list1 = TList(Box::new(TInteger));
-        let t_list2 = TList(Box::new(TBool));
-
-        assert_eq!(t_list1 == t_list2, false);
-    }
+// A match expression has the type shared by all of its arms
+fn check_match_exp(
+    exp: Expression,
+    arms: Vec<(Pattern, Expression)>,
+    env: &Environment<Type>,
+    diagnostics: &mut Diagnostics,
+) -> Result<Type, ErrorMessage> {
+    let (patterns, exps): (Vec<_>, Vec<_>) = arms.into_iter().unzip();
+    let arm_envs = check_patterns(exp, &patterns, env, diagnostics)?;
 
-    #[test]
-    fn check_ttuple_comparison() {
-        let t_tuple1 = TTuple(vec![TInteger, TBool]);
-        let t_tuple2 = TTuple(vec![TInteger, TBool]);
+    let types = exps
+        .into_iter()
+        .zip(arm_envs)
+        .map(|(arm, arm_env)| check_expression(arm, &arm_env, diagnostics))
+        .collect::<Result<Vec<_>, _>>()?;
 
-        assert_eq!(t_tuple1 == t_tuple2, true);
-    }
+    unify_arms(types, env)
+}
 
-    #[test]
-    fn check_ttuple_comparison_different_types() {
-        let t_tuple1 = TTuple(vec![TInteger, TBool]);
-        let t_tuple2 = TTuple(vec![TBool, TInteger]);
+// Check the patterns of a match against its scrutinee, rejecting unreachable
+// and missing cases, and give the environment in which each arm is checked
+fn check_patterns(
+    exp: Expression,
+    patterns: &[Pattern],
+    env: &Environment<Type>,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<Environment<Type>>, ErrorMessage> {
+    let exp_type = check_expression(exp, env, diagnostics)?;
+    let types = vec![exp_type.clone()];
+
+    let mut rows: Vec<Vec<Pattern>> = vec![];
+    let mut arm_envs = vec![];
+
+    for pattern in patterns {
+        let mut arm_env = env.clone();
+        check_pattern(pattern, &exp_type, &mut arm_env)?;
+        arm_envs.push(arm_env);
+        if exp_type == Type::TError {
+            continue;
+        }
 
-        assert_eq!(t_tuple1 == t_tuple2, false);
+        let row = vec![pattern.clone()];
+        if !is_useful(&rows, &row, &types, env) {
+            return Err((
+                "TypeError",
+                format!(
+                    "[Type Error on '{}()'] unreachable case '{}'.",
+                    env.scope_name(),
+                    row[0]
+                ),
+            ));
+        }
+        rows.push(row);
     }
 
-    #[test]
-    fn check_constant() {
-        let env = Environment::new();
+    if exp_type == Type::TError {
+        return Ok(arm_envs);
+    }
+    if let Some(missing) = missing_patterns(&rows, &types, env) {
+        return Err((
+            "TypeError",
+            format!(
+                "[Type Error on '{}()'] match is not exhaustive: case '{}' is not covered.",
+                env.scope_name(),
+                missing[0]
+            ),
+        ));
+    }
 
-        let c10 = CInt(10);
+    Ok(arm_envs)
+}
 
-        assert_eq!(check_exp(c10, &env), Ok(TInteger));
+// Unify the types given by the arms of a match
+fn unify_arms(types: Vec<Type>, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    let mut subst = Substitution::new();
+    let mut types = types.into_iter();
+    let first = types.next().unwrap_or(Type::TVoid);
+
+    for kind in types {
+        if !unify(&first, &kind, &mut subst) {
+            return Err(("TypeError", format!(
+                "[Type Error on '{}()'] match arms have mismatched types: expected '{:?}', found '{:?}'.",
+                env.scope_name(),
+                apply(&subst, &first),
+                apply(&subst, &kind)
+            )));
+        }
     }
 
-    #[test]
-    fn check_add_integers() {
-        let env = Environment::new();
+    Ok(apply(&subst, &first))
+}
 
-        let c10 = CInt(10);
-        let c20 = CInt(20);
-        let add = Add(Box::new(c10), Box::new(c20));
+// Check that a pattern fits the matched type and bind its variables
+fn check_pattern(
+    pattern: &Pattern,
+    kind: &Type,
+    env: &mut Environment<Type>,
+) -> Result<(), ErrorMessage> {
+    match pattern {
+        Pattern::Wildcard => Ok(()),
+        Pattern::Binding(name) => {
+            env.insert_variable(name.clone(), kind.clone());
+            Ok(())
+        }
+        // A scrutinee that failed to check has already been reported
+        Pattern::Constructor(_, args) if *kind == Type::TError => args
+            .iter()
+            .try_for_each(|arg| check_pattern(arg, kind, env)),
+        Pattern::Constructor(name, args) => {
+            let payload = constructors_of(kind, env)
+                .and_then(|constructors| {
+                    constructors
+                        .into_iter()
+                        .find(|(constructor, _)| constructor == name)
+                })
+                .map(|(_, payload)| payload)
+                .ok_or_else(|| {
+                    (
+                        "TypeError",
+                        format!(
+                            "[Type Error on '{}()'] '{}' is not a constructor of '{:?}'.",
+                            env.scope_name(),
+                            name,
+                            kind
+                        ),
+                    )
+                })?;
+
+            if payload.len() != args.len() {
+                return Err((
+                    "TypeError",
+                    format!(
+                        "[Type Error on '{}()'] '{}' expects {} fields, found {}.",
+                        env.scope_name(),
+                        name,
+                        payload.len(),
+                        args.len()
+                    ),
+                ));
+            }
 
-        assert_eq!(check_exp(add, &env), Ok(TInteger));
+            for (arg, arg_type) in args.iter().zip(payload.iter()) {
+                check_pattern(arg, arg_type, env)?;
+            }
+            Ok(())
+        }
     }
+}
 
-    #[test]
-    fn check_add_reals() {
-        let env = Environment::new();
+// Constructors of a type that can be matched on. Maybe and Result are
+// built-in enums: 'Just(T) | Nothing' and 'Ok(T) | Err(E)'.
+fn constructors_of(kind: &Type, env: &Environment<Type>) -> Option<Vec<(Name, Vec<Type>)>> {
+    match kind {
+        Type::TMaybe(t) => Some(vec![
+            ("Just".to_string(), vec![*t.clone()]),
+            ("Nothing".to_string(), vec![]),
+        ]),
+        Type::TResult(ok, err) => Some(vec![
+            ("Ok".to_string(), vec![*ok.clone()]),
+            ("Err".to_string(), vec![*err.clone()]),
+        ]),
+        Type::TUser(name) => match env.search_type(name) {
+            Some(TypeDef::Enum(constructors)) => Some(constructors.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
 
-        let c10 = CReal(10.5);
-        let c20 = CReal(20.3);
-        let add = Add(Box::new(c10), Box::new(c20));
+// Rows whose first pattern matches the constructor, with that pattern
+// replaced by its arguments
+fn specialize(rows: &[Vec<Pattern>], constructor: &Name, arity: usize) -> Vec<Vec<Pattern>> {
+    rows.iter()
+        .filter_map(|row| {
+            let head: Vec<Pattern> = match &row[0] {
+                Pattern::Constructor(name, args) if name == constructor => args.clone(),
+                Pattern::Constructor(_, _) => return None,
+                _ => vec![Pattern::Wildcard; arity],
+            };
+            Some([head, row[1..].to_vec()].concat())
+        })
+        .collect()
+}
 
-        assert_eq!(check_exp(add, &env), Ok(TReal));
-    }
+// Rows whose first pattern matches anything, without that pattern
+fn default_rows(rows: &[Vec<Pattern>]) -> Vec<Vec<Pattern>> {
+    rows.iter()
+        .filter(|row| !matches!(row[0], Pattern::Constructor(_, _)))
+        .map(|row| row[1..].to_vec())
+        .collect()
+}
 
-    #[test]
-    fn check_add_real_and_integer() {
-        let env = Environment::new();
+// Constructors of the type that do not head any of the rows
+fn unused_constructors(
+    rows: &[Vec<Pattern>],
+    constructors: &[(Name, Vec<Type>)],
+) -> Vec<(Name, Vec<Type>)> {
+    constructors
+        .iter()
+        .filter(|(constructor, _)| {
+            !rows
+                .iter()
+                .any(|row| matches!(&row[0], Pattern::Constructor(name, _) if name == constructor))
+        })
+        .cloned()
+        .collect()
+}
 
-        let c10 = CInt(10);
-        let c20 = CReal(20.3);
-        let add = Add(Box::new(c10), Box::new(c20));
+// Whether some value matched by `row` is matched by none of `rows`
+fn is_useful(
+    rows: &[Vec<Pattern>],
+    row: &[Pattern],
+    types: &[Type],
+    env: &Environment<Type>,
+) -> bool {
+    if row.is_empty() {
+        return rows.is_empty();
+    }
+    let constructors = constructors_of(&types[0], env).unwrap_or_default();
+
+    let specialized = |name: &Name, payload: &Vec<Type>, args: Vec<Pattern>| {
+        is_useful(
+            &specialize(rows, name, payload.len()),
+            &[args, row[1..].to_vec()].concat(),
+            &[payload.clone(), types[1..].to_vec()].concat(),
+            env,
+        )
+    };
+
+    match &row[0] {
+        Pattern::Constructor(name, args) => match constructors.iter().find(|(c, _)| c == name) {
+            Some((_, payload)) => specialized(name, payload, args.clone()),
+            None => true,
+        },
+        _ if !constructors.is_empty() && unused_constructors(rows, &constructors).is_empty() => {
+            constructors.iter().any(|(name, payload)| {
+                specialized(name, payload, vec![Pattern::Wildcard; payload.len()])
+            })
+        }
+        _ => is_useful(&default_rows(rows), &row[1..], &types[1..], env),
+    }
+}
 
-        assert_eq!(check_exp(add, &env), Ok(TReal));
+// Patterns for values that none of the rows match, if there are any
+fn missing_patterns(
+    rows: &[Vec<Pattern>],
+    types: &[Type],
+    env: &Environment<Type>,
+) -> Option<Vec<Pattern>> {
+    if types.is_empty() {
+        return rows.is_empty().then(Vec::new);
+    }
+    let constructors = constructors_of(&types[0], env).unwrap_or_default();
+    let unused = unused_constructors(rows, &constructors);
+
+    if !constructors.is_empty() && unused.is_empty() {
+        return constructors.iter().find_map(|(name, payload)| {
+            let mut missing = missing_patterns(
+                &specialize(rows, name, payload.len()),
+                &[payload.clone(), types[1..].to_vec()].concat(),
+                env,
+            )?;
+            let args = missing.drain(..payload.len()).collect();
+            Some([vec![Pattern::Constructor(name.clone(), args)], missing].concat())
+        });
     }
 
-    #[test]
-    fn check_add_integer_and_real() {
-        let env = Environment::new();
+    let missing = missing_patterns(&default_rows(rows), &types[1..], env)?;
+    let head = match (rows.is_empty(), unused.first()) {
+        (false, Some((name, payload))) => {
+            Pattern::Constructor(name.clone(), vec![Pattern::Wildcard; payload.len()])
+        }
+        _ => Pattern::Wildcard,
+    };
+    Some([vec![head], missing].concat())
+}
 
-        let c10 = CReal(10.5);
-        let c20 = CInt(20);
-        let add = Add(Box::new(c10), Box::new(c20));
+// Field assignments update a variable, e.g. 'p.x' or 'line.start.x'
+fn is_field_of_variable(record: &Expression) -> bool {
+    match record {
+        Expression::Var(_) => true,
+        Expression::FieldAccess(outer, _) => is_field_of_variable(outer),
+        _ => false,
+    }
+}
 
-        assert_eq!(check_exp(add, &env), Ok(TReal));
+fn check_field_access(
+    record: Expression,
+    field: Name,
+    env: &Environment<Type>,
+    diagnostics: &mut Diagnostics,
+) -> Result<Type, ErrorMessage> {
+    let record_type = check_expression(record, env, diagnostics)?;
+    if record_type == Type::TError {
+        return Ok(Type::TError);
     }
 
-    #[test]
-    fn check_type_error_arithmetic_expression() {
-        let env = Environment::new();
+    if let Type::TUser(name) = &record_type {
+        if let Some(TypeDef::Struct(fields)) = env.search_type(name) {
+            return match fields.iter().find(|(name, _)| *name == field) {
+                Some((_, kind)) => Ok(kind.clone()),
+                None => Err((
+                    "TypeError",
+                    format!(
+                        "[Type Error on '{}()'] '{}' has no field '{}'.",
+                        env.scope_name(),
+                        name,
+                        field
+                    ),
+                )),
+            };
+        }
+    }
 
-        let c10 = CInt(10);
-        let bool = CFalse;
-        let add = Add(Box::new(c10), Box::new(bool));
+    Err((
+        "TypeError",
+        format!(
+            "[Type Error on '{}()'] expecting a struct value, found '{:?}'.",
+            env.scope_name(),
+            record_type
+        ),
+    ))
+}
 
-        assert_eq!(
-            check_exp(add, &env),
-            Err(String::from("[Type Error] expecting numeric type values."))
-        );
+// User-declared types must be defined before they are used
+fn check_type_defined(kind: &Type, env: &Environment<Type>) -> Result<(), ErrorMessage> {
+    match kind {
+        Type::TUser(name) => match env.search_type(name) {
+            Some(_) => Ok(()),
+            None => Err((
+                "TypeError",
+                format!(
+                    "[Type Error on '{}()'] type '{}' is not defined.",
+                    env.scope_name(),
+                    name
+                ),
+            )),
+        },
+        Type::TList(t) | Type::TMaybe(t) => check_type_defined(t, env),
+        Type::TResult(ok, err) => {
+            check_type_defined(ok, env)?;
+            check_type_defined(err, env)
+        }
+        Type::TTuple(types) => types.iter().try_for_each(|t| check_type_defined(t, env)),
+        Type::TFunction(ret, params) => {
+            if let Some(ret) = ret.as_ref() {
+                check_type_defined(ret, env)?;
+            }
+            params.iter().try_for_each(|t| check_type_defined(t, env))
+        }
+        _ => Ok(()),
     }
+}
 
-    #[test]
-    fn check_type_error_not_expression() {
-        let env = Environment::new();
+fn check_duplicate_params(
+    params: &Vec<(Name, Type)>,
+    defaults: &[(Name, Expression)],
+) -> Result<(), ErrorMessage> {
+    let mut seen_params = std::collections::HashSet::new();
+    let mut seen_default = false;
 
-        let c10 = CInt(10);
-        let not = Not(Box::new(c10));
+    for (name, _) in params {
+        if !seen_params.insert(name.clone()) {
+            return Err((
+                "ParameterError",
+                format!("[Parameter Error] Duplicate parameter name '{}'", name),
+            ));
+        }
 
-        assert_eq!(
-            check_exp(not, &env),
-            Err(String::from("[Type Error] expecting a boolean type value."))
-        );
+        // Positional arguments fill parameters in order, so defaults come last
+        let has_default = defaults.iter().any(|(param, _)| param == name);
+        if seen_default && !has_default {
+            return Err((
+                "ParameterError",
+                format!(
+                    "[Parameter Error] Parameter '{}' without a default follows a parameter with one",
+                    name
+                ),
+            ));
+        }
+        seen_default |= has_default;
     }
 
-    #[test]
-    fn check_type_error_and_expression() {
-        let env = Environment::new();
-
-        let c10 = CInt(10);
-        let bool = CTrue;
-        let and = And(Box::new(c10), Box::new(bool));
+    Ok(())
+}
 
-        assert_eq!(
-            check_exp(and, &env),
-            Err(String::from("[Type Error] expecting boolean type values."))
-        );
+// Default values are checked in the scope the function is defined in
+fn check_default(
+    func: &Function,
+    param: &Name,
+    param_type: &Type,
+    env: &Environment<Type>,
+    diagnostics: &mut Diagnostics,
+) -> Result<(), ErrorMessage> {
+    if let Some((_, default)) = func.defaults.iter().find(|(name, _)| name == param) {
+        let default_type = check_expression(default.clone(), env, diagnostics)?;
+        let mut subst = Substitution::new();
+
+        if !unify(param_type, &default_type, &mut subst) {
+            return Err(("TypeError", format!("[Type Error on '{}()'] default value of '{}' has mismatched types: expected '{:?}', found '{:?}'.", func.name, param, param_type, apply(&subst, &default_type))));
+        }
     }
+    Ok(())
+}
 
-    #[test]
-    fn check_type_error_or_expression() {
-        let env = Environment::new();
-
-        let c10 = CInt(10);
-        let bool = CTrue;
-        let or = Or(Box::new(c10), Box::new(bool));
+// A generic function is instantiated each time its name is used
+fn check_var(name: Name, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    let kind = check_var_name(name.clone(), env, false)?;
 
-        assert_eq!(
-            check_exp(or, &env),
-            Err(String::from("[Type Error] expecting boolean type values."))
-        );
+    match env.search_function(&name) {
+        Some(func) => Ok(instantiate(&kind, &func.type_params)),
+        None => Ok(kind),
     }
+}
 
-    #[test]
-    fn check_ok_result() {
-        let env = Environment::new();
-        let f10 = CReal(10.0);
-        let ok = COk(Box::new(f10));
+fn check_var_name(name: Name, env: &Environment<Type>, scoped: bool) -> Result<Type, ErrorMessage> {
+    // Names declared 'global' or 'nonlocal' belong to an enclosing frame
+    let binding_key = env.binding_key(&name);
+    let mut curr_scope = binding_key.clone();
 
-        assert_eq!(
-            check_exp(ok, &env),
-            Ok(TResult(Box::new(TReal), Box::new(TAny)))
-        );
+    loop {
+        let frame = env.get_frame(curr_scope.clone());
+
+        match frame.variables.get(&name) {
+            Some(kind) => {
+                if scoped && curr_scope != binding_key {
+                    return Err((
+                        "LocalNameError",
+                        format!(
+                            "[Local Name Error on '{}'] cannot access local variable '{}'.",
+                            env.scope_name(),
+                            name
+                        ),
+                    ));
+                } else {
+                    return Ok(kind.clone());
+                }
+            }
+            None => match &frame.parent_key {
+                Some(parent) => curr_scope = parent.clone(),
+                None => {
+                    // A function may run after the module assigns the name
+                    if let Some(kind) = frame.assigned_later.get(&name) {
+                        if env.recursion > 0 {
+                            return Ok(kind.clone());
+                        }
+                    }
+                    return Err((
+                        "NameError",
+                        format!(
+                            "[Name Error on '{}'] '{}' is not defined.",
+                            env.scope_name(),
+                            name
+                        ),
+                    ));
+                }
+            },
+        }
     }
+}
 
-    #[test]
-    fn check_err_result() {
-        let env = Environment::new();
-        let ecode = CInt(1);
-        let err = CErr(Box::new(ecode));
+fn check_bin_arithmetic_expression(
+    left: Expression,
+    right: Expression,
+    env: &Environment<Type>,
+    diagnostics: &mut Diagnostics,
+) -> Result<Type, ErrorMessage> {
+    let left_type = check_expression(left, env, diagnostics)?;
+    let right_type = check_expression(right, env, diagnostics)?;
 
-        assert_eq!(
-            check_exp(err, &env),
-            Ok(TResult(Box::new(TAny), Box::new(TInteger)))
-        );
+    match (left_type, right_type) {
+        (Type::TError, _) | (_, Type::TError) => Ok(Type::TError),
+        (Type::TInteger, Type::TInteger) => Ok(Type::TInteger),
+        (Type::TInteger, Type::TReal) => Ok(Type::TReal),
+        (Type::TReal, Type::TInteger) => Ok(Type::TReal),
+        (Type::TReal, Type::TReal) => Ok(Type::TReal),
+        _ => Err((
+            "TypeError",
+            String::from("[Type Error] expecting numeric type values."),
+        )),
     }
+}
 
-    #[test]
-    fn check_just_integer() {
-        let env = Environment::new();
+fn check_bin_boolean_expression(
+    left: Expression,
+    right: Expression,
+    env: &Environment<Type>,
+    diagnostics: &mut Diagnostics,
+) -> Result<Type, ErrorMessage> {
+    let left_type = check_expression(left, env, diagnostics)?;
+    let right_type = check_expression(right, env, diagnostics)?;
+    match (left_type, right_type) {
+        (Type::TBool | Type::TError, Type::TBool | Type::TError) => Ok(Type::TBool),
+        _ => Err((
+            "TypeError",
+            String::from("[Type Error] expecting boolean type values."),
+        )),
+    }
+}
+
+fn check_not_expression(
+    exp: Expression,
+    env: &Environment<Type>,
+    diagnostics: &mut Diagnostics,
+) -> Result<Type, ErrorMessage> {
+    let exp_type = check_expression(exp, env, diagnostics)?;
+
+    match exp_type {
+        Type::TBool | Type::TError => Ok(Type::TBool),
+        _ => Err((
+            "TypeError",
+            String::from("[Type Error] expecting a boolean type value."),
+        )),
+    }
+}
+
+fn check_bin_relational_expression(
+    left: Expression,
+    right: Expression,
+    env: &Environment<Type>,
+    diagnostics: &mut Diagnostics,
+) -> Result<Type, ErrorMessage> {
+    let left_type = check_expression(left, env, diagnostics)?;
+    let right_type = check_expression(right, env, diagnostics)?;
+
+    match (left_type, right_type) {
+        (Type::TError, _) | (_, Type::TError) => Ok(Type::TBool),
+        (Type::TInteger, Type::TInteger) => Ok(Type::TBool),
+        (Type::TInteger, Type::TReal) => Ok(Type::TBool),
+        (Type::TReal, Type::TInteger) => Ok(Type::TBool),
+        (Type::TReal, Type::TReal) => Ok(Type::TBool),
+        _ => Err((
+            "TypeError",
+            String::from("[Type Error] expecting numeric type values."),
+        )),
+    }
+}
+
+// Numeric values compare across integers and reals; anything else must unify
+fn check_equality_expression(
+    left: Expression,
+    right: Expression,
+    env: &Environment<Type>,
+    diagnostics: &mut Diagnostics,
+) -> Result<Type, ErrorMessage> {
+    let left_type = check_expression(left, env, diagnostics)?;
+    let right_type = check_expression(right, env, diagnostics)?;
+
+    match (&left_type, &right_type) {
+        (Type::TInteger | Type::TReal, Type::TInteger | Type::TReal) => Ok(Type::TBool),
+        // Functions have no equality at runtime
+        _ if has_function_type(&left_type) || has_function_type(&right_type) => Err((
+            "TypeError",
+            format!(
+                "[Type Error] cannot compare '{:?}' with '{:?}', functions have no equality.",
+                left_type, right_type
+            ),
+        )),
+        _ if unify(&left_type, &right_type, &mut Substitution::new()) => Ok(Type::TBool),
+        _ => Err((
+            "TypeError",
+            format!(
+                "[Type Error] cannot compare '{:?}' with '{:?}'.",
+                left_type, right_type
+            ),
+        )),
+    }
+}
+
+fn check_result_ok(
+    exp: Expression,
+    env: &Environment<Type>,
+    diagnostics: &mut Diagnostics,
+) -> Result<Type, ErrorMessage> {
+    let exp_type = check_expression(exp, env, diagnostics)?;
+    Ok(Type::TResult(
+        Box::new(exp_type),
+        Box::new(fresh_type_var()),
+    ))
+}
+
+fn check_result_err(
+    exp: Expression,
+    env: &Environment<Type>,
+    diagnostics: &mut Diagnostics,
+) -> Result<Type, ErrorMessage> {
+    let exp_type = check_expression(exp, env, diagnostics)?;
+    Ok(Type::TResult(
+        Box::new(fresh_type_var()),
+        Box::new(exp_type),
+    ))
+}
+
+// Combinators take a maybe or result value and either a function over its
+// payload or a fallback value. The argument must unify with the type the
+// combinator expects, for 'map(m, f)' on 'TMaybe[A]' that is 'TFunction[[A], B]'.
+fn check_combinator(
+    combinator: &str,
+    exp: Expression,
+    arg: Expression,
+    env: &Environment<Type>,
+    diagnostics: &mut Diagnostics,
+) -> Result<Type, ErrorMessage> {
+    let exp_type = check_expression(exp, env, diagnostics)?;
+    let arg_type = check_expression(arg, env, diagnostics)?;
+
+    // The payload type, and how to rebuild the value around another payload
+    let (payload, wrap): (Type, Box<dyn Fn(Type) -> Type>) = match &exp_type {
+        Type::TMaybe(t) => (*t.clone(), Box::new(|t| Type::TMaybe(Box::new(t)))),
+        Type::TResult(ok, err) => {
+            let err = *err.clone();
+            (
+                *ok.clone(),
+                Box::new(move |t| Type::TResult(Box::new(t), Box::new(err.clone()))),
+            )
+        }
+        Type::TError => return Ok(Type::TError),
+        _ => {
+            return Err((
+                "TypeError",
+                format!(
+                    "[Type Error] '{}' is only defined for maybe and result values.",
+                    combinator
+                ),
+            ))
+        }
+    };
+    let function = |ret: Type, params: Vec<Type>| Type::TFunction(Box::new(Some(ret)), params);
+    let result = fresh_type_var();
+
+    let (expected_arg, kind) = match (combinator, &exp_type) {
+        ("map", _) => (function(result.clone(), vec![payload]), wrap(result)),
+        ("and_then", _) => (function(wrap(result.clone()), vec![payload]), wrap(result)),
+        ("or_else", Type::TMaybe(_)) => (function(exp_type.clone(), vec![]), exp_type.clone()),
+        ("or_else", Type::TResult(_, err)) => {
+            let recovered = Type::TResult(Box::new(payload), Box::new(result));
+            (function(recovered.clone(), vec![*err.clone()]), recovered)
+        }
+        ("unwrap_or", _) => (payload.clone(), payload),
+        ("ok_or", Type::TMaybe(_)) => (
+            result.clone(),
+            Type::TResult(Box::new(payload), Box::new(result)),
+        ),
+        _ => {
+            return Err((
+                "TypeError",
+                format!(
+                    "[Type Error] '{}' is only defined for maybe values.",
+                    combinator
+                ),
+            ))
+        }
+    };
+
+    let mut subst = Substitution::new();
+    if !unify(&expected_arg, &arg_type, &mut subst) {
+        return Err((
+            "TypeError",
+            format!(
+                "[Type Error] '{}' has mismatched arguments: expected '{:?}', found '{:?}'.",
+                combinator,
+                apply(&subst, &expected_arg),
+                apply(&subst, &arg_type)
+            ),
+        ));
+    }
+    Ok(apply(&subst, &kind))
+}
+
+fn check_unwrap_type(
+    exp: Expression,
+    env: &Environment<Type>,
+    diagnostics: &mut Diagnostics,
+) -> Result<Type, ErrorMessage> {
+    let exp_type = check_expression(exp, env, diagnostics)?;
+
+    match exp_type {
+        Type::TMaybe(t) => Ok(*t),
+        Type::TResult(tl, _) => Ok(*tl),
+        Type::TError => Ok(Type::TError),
+        _ => Err((
+            "TypeError",
+            String::from("[Type Error] expecting a maybe or result type value."),
+        )),
+    }
+}
+
+fn check_propagate_type(
+    exp: Expression,
+    env: &Environment<Type>,
+    diagnostics: &mut Diagnostics,
+) -> Result<Type, ErrorMessage> {
+    let exp_type = check_expression(exp, env, diagnostics)?;
+
+    match exp_type {
+        Type::TMaybe(t) => Ok(*t),
+        Type::TResult(tl, _) => Ok(*tl),
+        Type::TError => Ok(Type::TError),
+        _ => Err((
+            "TypeError",
+            String::from("[Type Error] expecting a maybe or result type value."),
+        )),
+    }
+}
+
+fn check_maybe_just(
+    exp: Expression,
+    env: &Environment<Type>,
+    diagnostics: &mut Diagnostics,
+) -> Result<Type, ErrorMessage> {
+    let exp_type = check_expression(exp, env, diagnostics)?;
+    Ok(Type::TMaybe(Box::new(exp_type)))
+}
+
+fn check_iserror_type(
+    exp: Expression,
+    env: &Environment<Type>,
+    diagnostics: &mut Diagnostics,
+) -> Result<Type, ErrorMessage> {
+    let v = check_expression(exp, env, diagnostics)?;
+
+    match v {
+        Type::TResult(_, _) | Type::TError => Ok(Type::TBool),
+        _ => Err((
+            "TypeError",
+            String::from("[Type Error] expecting a result type value."),
+        )),
+    }
+}
+
+fn check_isnothing_type(
+    exp: Expression,
+    env: &Environment<Type>,
+    diagnostics: &mut Diagnostics,
+) -> Result<Type, ErrorMessage> {
+    let exp_type = check_expression(exp, env, diagnostics)?;
+
+    match exp_type {
+        Type::TMaybe(_) | Type::TError => Ok(Type::TBool),
+        _ => Err((
+            "TypeError",
+            String::from("[Type Error] expecting a maybe type value."),
+        )),
+    }
+}
+
+fn fresh_type_var() -> Type {
+    let id = NEXT_TYPE_VAR.fetch_add(1, Ordering::Relaxed);
+    Type::TVar(format!("?{}", id))
+}
+
+// Type variables created during inference ('?n') are solved by unification, while
+// the type parameters of a generic function are rigid inside its body
+fn is_flexible(name: &Name) -> bool {
+    name.starts_with('?')
+}
+
+// Rebuild a type, replacing each of its type variables
+fn map_type_vars(kind: &Type, f: &mut impl FnMut(&Name) -> Type) -> Type {
+    match kind {
+        Type::TVar(name) => f(name),
+        Type::TList(t) => Type::TList(Box::new(map_type_vars(t, f))),
+        Type::TMaybe(t) => Type::TMaybe(Box::new(map_type_vars(t, f))),
+        Type::TResult(ok, err) => Type::TResult(
+            Box::new(map_type_vars(ok, f)),
+            Box::new(map_type_vars(err, f)),
+        ),
+        Type::TTuple(types) => Type::TTuple(types.iter().map(|t| map_type_vars(t, f)).collect()),
+        Type::TFunction(ret, params) => Type::TFunction(
+            Box::new(ret.as_ref().as_ref().map(|t| map_type_vars(t, f))),
+            params.iter().map(|t| map_type_vars(t, f)).collect(),
+        ),
+        _ => kind.clone(),
+    }
+}
+
+// Resolve the type variables of a type through the substitution
+fn apply(subst: &Substitution, kind: &Type) -> Type {
+    map_type_vars(kind, &mut |name| match subst.get(name) {
+        Some(solved) => apply(subst, solved),
+        None => Type::TVar(name.clone()),
+    })
+}
+
+// Give each of the given type parameters a fresh type variable. Other type
+// variables, e.g. those of an enclosing generic function, stay rigid.
+fn instantiate(kind: &Type, type_params: &[Name]) -> Type {
+    let mut fresh = Substitution::new();
+
+    map_type_vars(kind, &mut |name| {
+        if type_params.contains(name) {
+            fresh
+                .entry(name.clone())
+                .or_insert_with(fresh_type_var)
+                .clone()
+        } else {
+            Type::TVar(name.clone())
+        }
+    })
+}
+
+// Keep the variables of every frame up to date with the solved type variables
+fn apply_env(subst: &Substitution, env: &mut Environment<Type>) {
+    for frame in env.stack.values_mut() {
+        for kind in frame.variables.values_mut() {
+            *kind = apply(subst, kind);
+        }
+    }
+}
+
+fn occurs(name: &Name, kind: &Type) -> bool {
+    match kind {
+        Type::TVar(other) => name == other,
+        Type::TList(t) | Type::TMaybe(t) => occurs(name, t),
+        Type::TResult(ok, err) => occurs(name, ok) || occurs(name, err),
+        Type::TTuple(types) => types.iter().any(|t| occurs(name, t)),
+        Type::TFunction(ret, params) => {
+            ret.as_ref().as_ref().is_some_and(|t| occurs(name, t))
+                || params.iter().any(|t| occurs(name, t))
+        }
+        _ => false,
+    }
+}
+
+fn has_function_type(kind: &Type) -> bool {
+    match kind {
+        Type::TFunction(_, _) => true,
+        Type::TList(t) | Type::TMaybe(t) => has_function_type(t),
+        Type::TResult(ok, err) => has_function_type(ok) || has_function_type(err),
+        Type::TTuple(types) => types.iter().any(has_function_type),
+        _ => false,
+    }
+}
+
+// Extend the substitution so that both types become equal, if possible
+fn unify(expected: &Type, found: &Type, subst: &mut Substitution) -> bool {
+    match (apply(subst, expected), apply(subst, found)) {
+        // An earlier error was already reported
+        (Type::TError, _) | (_, Type::TError) => true,
+        (Type::TVar(a), Type::TVar(b)) if a == b => true,
+        (Type::TVar(name), kind) | (kind, Type::TVar(name)) if is_flexible(&name) => {
+            if occurs(&name, &kind) {
+                return false;
+            }
+            subst.insert(name, kind);
+            true
+        }
+        (Type::TList(t1), Type::TList(t2)) | (Type::TMaybe(t1), Type::TMaybe(t2)) => {
+            unify(&t1, &t2, subst)
+        }
+        (Type::TResult(ok1, err1), Type::TResult(ok2, err2)) => {
+            unify(&ok1, &ok2, subst) && unify(&err1, &err2, subst)
+        }
+        (Type::TTuple(types1), Type::TTuple(types2)) => {
+            types1.len() == types2.len()
+                && types1
+                    .iter()
+                    .zip(types2.iter())
+                    .all(|(t1, t2)| unify(t1, t2, subst))
+        }
+        (Type::TFunction(ret1, params1), Type::TFunction(ret2, params2)) => {
+            let returns = match (*ret1, *ret2) {
+                (Some(r1), Some(r2)) => unify(&r1, &r2, subst),
+                (r1, r2) => r1 == r2,
+            };
+            returns
+                && params1.len() == params2.len()
+                && params1
+                    .iter()
+                    .zip(params2.iter())
+                    .all(|(t1, t2)| unify(t1, t2, subst))
+        }
+        (t1, t2) => t1 == t2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::ir::ast::Environment;
+    use crate::ir::ast::Expression::*;
+    use crate::ir::ast::Function;
+    use crate::ir::ast::Statement::*;
+    use crate::ir::ast::Type::*;
+
+    #[test]
+    fn check_tlist_comparison() {
+        let t_list1 = TList(Box::new(TInteger));
+        let t_list2 = TList(Box::new(TInteger));
+
+        assert_eq!(t_list1 == t_list2, true);
+    }
+
+    #[test]
+    fn check_tlist_comparison_different_types() {
+        let t_list1 = TList(Box::new(TInteger));
+        let t_list2 = TList(Box::new(TBool));
+
+        assert_eq!(t_list1 == t_list2, false);
+    }
+
+    #[test]
+    fn check_ttuple_comparison() {
+        let t_tuple1 = TTuple(vec![TInteger, TBool]);
+        let t_tuple2 = TTuple(vec![TInteger, TBool]);
+
+        assert_eq!(t_tuple1 == t_tuple2, true);
+    }
+
+    #[test]
+    fn check_ttuple_comparison_different_types() {
+        let t_tuple1 = TTuple(vec![TInteger, TBool]);
+        let t_tuple2 = TTuple(vec![TBool, TInteger]);
+
+        assert_eq!(t_tuple1 == t_tuple2, false);
+    }
+
+    #[test]
+    fn check_constant() {
+        let env = Environment::new();
+
+        let c10 = CInt(10);
+
+        assert_eq!(check_exp(c10, &env), Ok(TInteger));
+    }
+
+    #[test]
+    fn check_add_integers() {
+        let env = Environment::new();
+
+        let c10 = CInt(10);
+        let c20 = CInt(20);
+        let add = Add(Box::new(c10), Box::new(c20));
+
+        assert_eq!(check_exp(add, &env), Ok(TInteger));
+    }
+
+    #[test]
+    fn check_add_reals() {
+        let env = Environment::new();
+
+        let c10 = CReal(10.5);
+        let c20 = CReal(20.3);
+        let add = Add(Box::new(c10), Box::new(c20));
+
+        assert_eq!(check_exp(add, &env), Ok(TReal));
+    }
+
+    #[test]
+    fn check_add_real_and_integer() {
+        let env = Environment::new();
+
+        let c10 = CInt(10);
+        let c20 = CReal(20.3);
+        let add = Add(Box::new(c10), Box::new(c20));
+
+        assert_eq!(check_exp(add, &env), Ok(TReal));
+    }
+
+    #[test]
+    fn check_add_integer_and_real() {
+        let env = Environment::new();
+
+        let c10 = CReal(10.5);
+        let c20 = CInt(20);
+        let add = Add(Box::new(c10), Box::new(c20));
+
+        assert_eq!(check_exp(add, &env), Ok(TReal));
+    }
+
+    #[test]
+    fn check_type_error_arithmetic_expression() {
+        let env = Environment::new();
+
+        let c10 = CInt(10);
+        let bool = CFalse;
+        let add = Add(Box::new(c10), Box::new(bool));
+
+        assert_eq!(
+            check_exp(add, &env),
+            Err(String::from("[Type Error] expecting numeric type values."))
+        );
+    }
+
+    #[test]
+    fn check_type_error_not_expression() {
+        let env = Environment::new();
+
+        let c10 = CInt(10);
+        let not = Not(Box::new(c10));
+
+        assert_eq!(
+            check_exp(not, &env),
+            Err(String::from("[Type Error] expecting a boolean type value."))
+        );
+    }
+
+    #[test]
+    fn check_type_error_and_expression() {
+        let env = Environment::new();
+
+        let c10 = CInt(10);
+        let bool = CTrue;
+        let and = And(Box::new(c10), Box::new(bool));
+
+        assert_eq!(
+            check_exp(and, &env),
+            Err(String::from("[Type Error] expecting boolean type values."))
+        );
+    }
+
+    #[test]
+    fn check_type_error_or_expression() {
+        let env = Environment::new();
+
+        let c10 = CInt(10);
+        let bool = CTrue;
+        let or = Or(Box::new(c10), Box::new(bool));
+
+        assert_eq!(
+            check_exp(or, &env),
+            Err(String::from("[Type Error] expecting boolean type values."))
+        );
+    }
+
+    #[test]
+    fn check_ok_result() {
+        let env = Environment::new();
+        let f10 = CReal(10.0);
+        let ok = COk(Box::new(f10));
+
+        match check_exp(ok, &env) {
+            Ok(TResult(ok_type, err_type)) => {
+                assert_eq!(*ok_type, TReal);
+                assert!(matches!(*err_type, TVar(_)));
+            }
+            other => assert!(false, "{:?}", other),
+        }
+    }
+
+    #[test]
+    fn check_err_result() {
+        let env = Environment::new();
+        let ecode = CInt(1);
+        let err = CErr(Box::new(ecode));
+
+        match check_exp(err, &env) {
+            Ok(TResult(ok_type, err_type)) => {
+                assert!(matches!(*ok_type, TVar(_)));
+                assert_eq!(*err_type, TInteger);
+            }
+            other => assert!(false, "{:?}", other),
+        }
+    }
+
+    #[test]
+    fn check_just_integer() {
+        let env = Environment::new();
+        let c5 = CInt(5);
+        let just = CJust(Box::new(c5));
+
+        assert_eq!(check_exp(just, &env), Ok(TMaybe(Box::new(TInteger))))
+    }
+
+    #[test]
+    fn check_is_error_result_positive() {
+        let env = Environment::new();
+        let bool = CTrue;
+        let ok = COk(Box::new(bool));
+        let ie = IsError(Box::new(ok));
+
+        assert_eq!(check_exp(ie, &env), Ok(TBool));
+    }
+
+    #[test]
+    fn check_is_error_result_error() {
+        let env = Environment::new();
+        let bool = CTrue;
+        let ie = IsError(Box::new(bool));
+
+        assert_eq!(
+            check_exp(ie, &env),
+            Err(String::from("[Type Error] expecting a result type value."))
+        );
+    }
+
+    #[test]
+    fn check_nothing() {
+        let env = Environment::new();
+
+        match check_exp(CNothing, &env) {
+            Ok(TMaybe(t)) => assert!(matches!(*t, TVar(_))),
+            other => assert!(false, "{:?}", other),
+        }
+    }
+
+    #[test]
+    fn check_is_nothing_on_maybe() {
+        let env = Environment::new();
         let c5 = CInt(5);
         let just = CJust(Box::new(c5));
+        let is_nothing = IsNothing(Box::new(just));
+
+        assert_eq!(check_exp(is_nothing, &env), Ok(TBool));
+    }
+
+    #[test]
+    fn check_is_nothing_type_error() {
+        let env = Environment::new();
+        let c5 = CInt(5);
+        let is_nothing = IsNothing(Box::new(c5));
+
+        assert_eq!(
+            check_exp(is_nothing, &env),
+            Err(String::from("[Type Error] expecting a maybe type value."))
+        );
+    }
+
+    #[test]
+    fn check_unwrap_maybe() {
+        let env = Environment::new();
+        let c5 = CInt(5);
+        let some = CJust(Box::new(c5));
+        let u = Unwrap(Box::new(some));
+
+        assert_eq!(check_exp(u, &env), Ok(TInteger));
+    }
+
+    #[test]
+    fn check_unwrap_maybe_type_error() {
+        let env = Environment::new();
+        let c5 = CInt(5);
+        let u = Unwrap(Box::new(c5));
+
+        assert_eq!(
+            check_exp(u, &env),
+            Err(String::from(
+                "[Type Error] expecting a maybe or result type value."
+            ))
+        );
+    }
+
+    #[test]
+    fn check_unwrap_result() {
+        let env = Environment::new();
+        let bool = CTrue;
+        let ok = COk(Box::new(bool));
+        let u = Unwrap(Box::new(ok));
+
+        assert_eq!(check_exp(u, &env), Ok(TBool));
+    }
+
+    #[test]
+    fn check_propagate_maybe() {
+        let env = Environment::new();
+        let c5 = CInt(5);
+        let some = CJust(Box::new(c5));
+        let u = Propagate(Box::new(some));
+
+        assert_eq!(check_exp(u, &env), Ok(TInteger));
+    }
+
+    #[test]
+    fn check_propagate_maybe_type_error() {
+        let env = Environment::new();
+        let c5 = CInt(5);
+        let u = Propagate(Box::new(c5));
+
+        assert_eq!(
+            check_exp(u, &env),
+            Err(String::from(
+                "[Type Error] expecting a maybe or result type value."
+            ))
+        );
+    }
+
+    #[test]
+    fn check_propagate_result() {
+        let env = Environment::new();
+        let bool = CTrue;
+        let ok = COk(Box::new(bool));
+        let u = Propagate(Box::new(ok));
+
+        assert_eq!(check_exp(u, &env), Ok(TBool));
+    }
+
+    #[test]
+    fn check_assignment() {
+        let env: Environment<Type> = Environment::new();
+
+        let assignment = Assignment("a".to_string(), Box::new(CTrue), Some(TBool));
+
+        match check_stmt(assignment, &env) {
+            Ok(ControlFlow::Continue(new_env)) => {
+                assert_eq!(new_env.search_frame("a".to_string()), Some(TBool).as_ref());
+            }
+            Ok(_) => assert!(false),
+            Err(s) => assert!(false, "{}", s),
+        }
+    }
+
+    #[test]
+    fn check_assignment_error1() {
+        let env: Environment<Type> = Environment::new();
+
+        let assignment = Assignment("a".to_string(), Box::new(CTrue), Some(TInteger));
+
+        match check_stmt(assignment, &env) {
+            Ok(_) => assert!(false),
+            Err(s) => assert_eq!(
+                s,
+                "[Type Error on '__main__()'] 'a' has mismatched types: expected 'TInteger', found 'TBool'."
+            ),
+        }
+    }
+
+    #[test]
+    fn check_assignment_error2() {
+        let env: Environment<Type> = Environment::new();
+
+        let assignment1 = Assignment("a".to_string(), Box::new(CTrue), Some(TBool));
+        let assignment2 = Assignment("a".to_string(), Box::new(CInt(1)), None);
+        let program = Sequence(Box::new(assignment1), Box::new(assignment2));
+
+        match check_stmt(program, &env) {
+            Ok(_) => assert!(false),
+            Err(s) => assert_eq!(
+                s,
+                "[Type Error on '__main__()'] 'a' has mismatched types: expected 'TBool', found 'TInteger'."
+            ),
+        }
+    }
+
+    #[test]
+    fn check_if_then_else_error() {
+        let env: Environment<Type> = Environment::new();
+
+        let ifthenelse = IfThenElse(
+            Box::new(CInt(1)),
+            Box::new(Assignment(
+                "a".to_string(),
+                Box::new(CInt(1)),
+                Some(TInteger),
+            )),
+            Some(Box::new(Assignment(
+                "b".to_string(),
+                Box::new(CReal(2.0)),
+                Some(TReal),
+            ))),
+        );
+
+        match check_stmt(ifthenelse, &env) {
+            Ok(_) => assert!(false),
+            Err(s) => assert_eq!(
+                s,
+                "[Type Error on '__main__()'] if expression must be boolean."
+            ),
+        }
+    }
+
+    #[test]
+    fn check_while_error() {
+        let env: Environment<Type> = Environment::new();
+
+        let assignment1 = Assignment("a".to_string(), Box::new(CInt(3)), Some(TInteger));
+        let assignment2 = Assignment("b".to_string(), Box::new(CInt(0)), Some(TInteger));
+        let while_stmt = While(
+            Box::new(CInt(1)),
+            Box::new(Assignment(
+                "b".to_string(),
+                Box::new(Add(Box::new(Var("b".to_string())), Box::new(CInt(1)))),
+                None,
+            )),
+        );
+        let program = Sequence(
+            Box::new(assignment1),
+            Box::new(Sequence(Box::new(assignment2), Box::new(while_stmt))),
+        );
+
+        match check_stmt(program, &env) {
+            Ok(_) => assert!(false),
+            Err(s) => assert_eq!(
+                s,
+                "[Type Error on '__main__()'] while expression must be boolean."
+            ),
+        }
+    }
+
+    #[test]
+    fn check_func_def() {
+        let env: Environment<Type> = Environment::new();
+
+        let func = FuncDef(Function {
+            name: "add".to_string(),
+            type_params: vec![],
+            kind: Some(TInteger),
+            params: Some(vec![
+                ("a".to_string(), TInteger),
+                ("b".to_string(), TInteger),
+            ]),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(Return(Box::new(Add(
+                Box::new(Var("a".to_string())),
+                Box::new(Var("b".to_string())),
+            ))))),
+        });
+
+        match check_stmt(func, &env) {
+            Ok(ControlFlow::Continue(new_env)) => {
+                assert_eq!(
+                    new_env.search_frame("add".to_string()),
+                    Some(TFunction(
+                        Box::new(Some(TInteger)),
+                        vec![TInteger, TInteger]
+                    ))
+                    .as_ref()
+                );
+            }
+            Ok(_) => assert!(false),
+            Err(s) => assert!(false, "{}", s),
+        }
+    }
+
+    #[test]
+    fn check_func_def_error() {
+        let env: Environment<Type> = Environment::new();
+
+        let func = FuncDef(Function {
+            name: "add".to_string(),
+            type_params: vec![],
+            kind: Some(TInteger),
+            params: Some(vec![
+                ("a".to_string(), TInteger),
+                ("b".to_string(), TInteger),
+            ]),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(Return(Box::new(CTrue)))),
+        });
+
+        match check_stmt(func, &env) {
+            Ok(_) => assert!(false),
+            Err(s) => assert_eq!(
+                s,
+                "[Type Error] 'add()' has mismatched types: expected 'TInteger', found 'TBool'."
+            ),
+        }
+    }
+
+    #[test]
+    fn check_return_outside_function() {
+        let env: Environment<Type> = Environment::new();
+
+        let retrn = Return(Box::new(CInt(1)));
+
+        match check_stmt(retrn, &env) {
+            Ok(_) => assert!(false),
+            Err(s) => assert_eq!(s, "[Syntax Error] return statement outside function."),
+        }
+    }
+
+    #[test]
+    fn check_function_call_wrong_args() {
+        let env: Environment<Type> = Environment::new();
+
+        let func = FuncDef(Function {
+            name: "add".to_string(),
+            type_params: vec![],
+            kind: Some(TInteger),
+            params: Some(vec![
+                ("a".to_string(), TInteger),
+                ("b".to_string(), TInteger),
+            ]),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(Sequence(
+                Box::new(Assignment(
+                    "c".to_string(),
+                    Box::new(Add(
+                        Box::new(Var("a".to_string())),
+                        Box::new(Var("b".to_string())),
+                    )),
+                    Some(TInteger),
+                )),
+                Box::new(Return(Box::new(Var("c".to_string())))),
+            ))),
+        });
+        let program1 = Sequence(
+            Box::new(func.clone()),
+            Box::new(Assignment(
+                "var".to_string(),
+                Box::new(FuncCall("add".to_string(), vec![CInt(1)])),
+                Some(TInteger),
+            )),
+        );
+        let program2 = Sequence(
+            Box::new(func),
+            Box::new(Assignment(
+                "var".to_string(),
+                Box::new(FuncCall("add".to_string(), vec![CInt(1), CInt(2), CInt(3)])),
+                Some(TInteger),
+            )),
+        );
+
+        match check_stmt(program1, &env.clone()) {
+            Ok(_) => assert!(false),
+            Err(s) => assert_eq!(
+                s,
+                "[Type Error on '__main__()'] 'add()' expected 2 arguments, found 1."
+            ),
+        }
+        match check_stmt(program2, &env) {
+            Ok(_) => assert!(false),
+            Err(s) => assert_eq!(
+                s,
+                "[Type Error on '__main__()'] 'add()' expected 2 arguments, found 3."
+            ),
+        }
+    }
+
+    #[test]
+    fn check_function_call_wrong_type() {
+        let env: Environment<Type> = Environment::new();
+
+        let func = FuncDef(Function {
+            name: "add".to_string(),
+            type_params: vec![],
+            kind: Some(TInteger),
+            params: Some(vec![
+                ("a".to_string(), TInteger),
+                ("b".to_string(), TInteger),
+            ]),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(Sequence(
+                Box::new(Assignment(
+                    "c".to_string(),
+                    Box::new(Add(
+                        Box::new(Var("a".to_string())),
+                        Box::new(Var("b".to_string())),
+                    )),
+                    Some(TInteger),
+                )),
+                Box::new(Return(Box::new(Var("c".to_string())))),
+            ))),
+        });
+        let program = Sequence(
+            Box::new(func.clone()),
+            Box::new(Assignment(
+                "var".to_string(),
+                Box::new(FuncCall("add".to_string(), vec![CInt(1), CTrue])),
+                Some(TInteger),
+            )),
+        );
+
+        match check_stmt(program, &env.clone()) {
+            Ok(_) => assert!(false),
+            Err(s) => assert_eq!(s, "[Type Error on '__main__()'] 'add()' has mismatched arguments: expected 'TInteger', found 'TBool'."),
+        }
+    }
+
+    #[test]
+    fn check_function_call_non_function() {
+        let env: Environment<Type> = Environment::new();
+
+        let program = Sequence(
+            Box::new(Assignment(
+                "a".to_string(),
+                Box::new(CInt(1)),
+                Some(TInteger),
+            )),
+            Box::new(Assignment(
+                "b".to_string(),
+                Box::new(FuncCall("a".to_string(), vec![])),
+                Some(TInteger),
+            )),
+        );
+
+        match check_stmt(program, &env.clone()) {
+            Ok(_) => assert!(false),
+            Err(s) => assert_eq!(s, "[Name Error on '__main__()'] 'a()' is not defined."),
+        }
+    }
+
+    #[test]
+    fn check_function_call_undefined() {
+        let env: Environment<Type> = Environment::new();
+
+        let program = Assignment(
+            "a".to_string(),
+            Box::new(FuncCall("func".to_string(), vec![])),
+            Some(TInteger),
+        );
+
+        match check_stmt(program, &env.clone()) {
+            Ok(_) => assert!(false),
+            Err(s) => assert_eq!(s, "[Name Error on '__main__()'] 'func()' is not defined."),
+        }
+    }
+    #[test]
+    fn check_recursive_function() {
+        let env: Environment<Type> = Environment::new();
+
+        // Definição de função fatorial recursiva
+        let factorial = FuncDef(Function {
+            name: "factorial".to_string(),
+            type_params: vec![],
+            kind: Some(TInteger),
+            params: Some(vec![("n".to_string(), TInteger)]),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(IfThenElse(
+                Box::new(EQ(Box::new(Var("n".to_string())), Box::new(CInt(0)))),
+                Box::new(Return(Box::new(CInt(1)))),
+                Some(Box::new(Return(Box::new(Mul(
+                    Box::new(Var("n".to_string())),
+                    Box::new(FuncCall(
+                        "factorial".to_string(),
+                        vec![Sub(Box::new(Var("n".to_string())), Box::new(CInt(1)))],
+                    )),
+                ))))),
+            ))),
+        });
+
+        match check_stmt(factorial, &env) {
+            Ok(ControlFlow::Continue(new_env)) => {
+                assert_eq!(
+                    new_env.search_frame("factorial".to_string()),
+                    Some(TFunction(Box::new(Some(TInteger)), vec![TInteger])).as_ref()
+                );
+            }
+            _ => assert!(false, "Recursive function definition failed"),
+        }
+    }
+
+    #[test]
+    fn check_function_multiple_return_paths() {
+        let env: Environment<Type> = Environment::new();
+
+        // Função com múltiplos caminhos de retorno
+        let func = FuncDef(Function {
+            name: "max".to_string(),
+            type_params: vec![],
+            kind: Some(TInteger),
+            params: Some(vec![
+                ("a".to_string(), TInteger),
+                ("b".to_string(), TInteger),
+            ]),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(IfThenElse(
+                Box::new(GT(
+                    Box::new(Var("a".to_string())),
+                    Box::new(Var("b".to_string())),
+                )),
+                Box::new(Return(Box::new(Var("a".to_string())))),
+                Some(Box::new(Return(Box::new(Var("b".to_string()))))),
+            ))),
+        });
+
+        match check_stmt(func, &env) {
+            Ok(ControlFlow::Continue(_)) => assert!(true),
+            _ => assert!(false, "Multiple return paths function failed"),
+        }
+    }
+
+    #[test]
+    fn test_function_wrong_return_type() {
+        let env: Environment<Type> = Environment::new();
+
+        let func = FuncDef(Function {
+            name: "wrong_return".to_string(),
+            type_params: vec![],
+            kind: Some(TInteger),
+            params: None,
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(Return(Box::new(CReal(1.0))))),
+        });
+
+        match check_stmt(func, &env) {
+            Ok(_) => assert!(false, "Should fail due to wrong return type"),
+            Err(msg) => assert_eq!(
+                msg,
+                "[Type Error] 'wrong_return()' has mismatched types: expected 'TInteger', found 'TReal'."
+            ),
+        }
+    }
+
+    #[test]
+    fn test_function_parameter_shadowing() {
+        let env: Environment<Type> = Environment::new();
+
+        let func = FuncDef(Function {
+            name: "shadow_test".to_string(),
+            type_params: vec![],
+            kind: Some(TInteger),
+            params: Some(vec![
+                ("x".to_string(), TInteger),
+                ("x".to_string(), TInteger), // Mesmo nome de parâmetro
+            ]),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(Return(Box::new(Var("x".to_string()))))),
+        });
+
+        match check_stmt(func, &env) {
+            Ok(_) => panic!("Should not accept duplicate parameter names"),
+            Err(msg) => assert_eq!(msg, "[Parameter Error] Duplicate parameter name 'x'"),
+        }
+    }
+
+    #[test]
+    fn check_typed_declaration() {
+        let env: Environment<Type> = Environment::new();
+
+        let declaration = Sequence(
+            Box::new(ValDeclaration("a".to_string(), None)),
+            Box::new(Assignment("a".to_string(), Box::new(CInt(1)), Some(TReal))),
+        );
+
+        match check_stmt(declaration, &env) {
+            Ok(_) => assert!(false),
+            Err(s) => assert_eq!(
+                s,
+                "[Type Error on '__main__()'] 'a' has mismatched types: expected 'TReal', found 'TInteger'."
+            ),
+        }
+    }
+
+    #[test]
+    fn check_declaration_without_initializer() {
+        let env: Environment<Type> = Environment::new();
+        let maybe_int = TMaybe(Box::new(TInteger));
+
+        // val m: TMaybe[TInteger]
+        // m = Nothing
+        let program = Sequence(
+            Box::new(ValDeclaration("m".to_string(), Some(maybe_int.clone()))),
+            Box::new(Assignment("m".to_string(), Box::new(CNothing), None)),
+        );
+        match check_stmt(program, &env) {
+            Ok(ControlFlow::Continue(new_env)) => {
+                assert_eq!(check_exp(Var("m".to_string()), &new_env), Ok(maybe_int))
+            }
+            _ => panic!("declaration should type check"),
+        }
+
+        // var x: TReal
+        // x = 1
+        let program = Sequence(
+            Box::new(VarDeclaration("x".to_string(), Some(TReal))),
+            Box::new(Assignment(
+                "x".to_string(),
+                Box::new(CInt(1)),
+                Some(TInteger),
+            )),
+        );
+        assert_eq!(
+            check_stmt(program, &env).err(),
+            Some(String::from("[Type Error on '__main__()'] 'x' has mismatched types: expected 'TReal', found 'TInteger'."))
+        );
+        assert_eq!(
+            check_stmt(
+                VarDeclaration("p".to_string(), Some(TUser("Point".to_string()))),
+                &env
+            )
+            .err(),
+            Some(String::from(
+                "[Type Error on '__main__()'] type 'Point' is not defined."
+            ))
+        );
+    }
+
+    #[test]
+    fn check_duplicate_declaration() {
+        let env: Environment<Type> = Environment::new();
+
+        let program = Sequence(
+            Box::new(Assignment(
+                "a".to_string(),
+                Box::new(CInt(1)),
+                Some(TInteger),
+            )),
+            Box::new(VarDeclaration("a".to_string(), None)),
+        );
+
+        match check_stmt(program, &env) {
+            Ok(_) => assert!(false),
+            Err(s) => assert_eq!(s, "[Name Error on '__main__()'] 'a' is already declared."),
+        }
+    }
+
+    #[test]
+    fn check_val_reassignment() {
+        let env: Environment<Type> = Environment::new();
+
+        let declaration = Sequence(
+            Box::new(ValDeclaration("a".to_string(), None)),
+            Box::new(Assignment(
+                "a".to_string(),
+                Box::new(CInt(1)),
+                Some(TInteger),
+            )),
+        );
+        let program = Sequence(
+            Box::new(declaration),
+            Box::new(Assignment(
+                "a".to_string(),
+                Box::new(CInt(2)),
+                Some(TInteger),
+            )),
+        );
+
+        match check_stmt(program, &env) {
+            Ok(_) => assert!(false),
+            Err(s) => assert_eq!(
+                s,
+                "[Type Error on '__main__()'] cannot reassign 'a', declared with 'val' in '__main__()'."
+            ),
+        }
+    }
+
+    #[test]
+    fn check_var_reassignment() {
+        let env: Environment<Type> = Environment::new();
+
+        let declaration = Sequence(
+            Box::new(VarDeclaration("a".to_string(), None)),
+            Box::new(Assignment(
+                "a".to_string(),
+                Box::new(CInt(1)),
+                Some(TInteger),
+            )),
+        );
+        let program = Sequence(
+            Box::new(declaration),
+            Box::new(Assignment(
+                "a".to_string(),
+                Box::new(CInt(2)),
+                Some(TInteger),
+            )),
+        );
+
+        assert!(check_stmt(program, &env).is_ok());
+    }
+
+    #[test]
+    fn check_fresh_assignment_inference() {
+        let env: Environment<Type> = Environment::new();
+
+        let assignment1 = Assignment("x".to_string(), Box::new(CReal(1.5)), Some(TReal));
+        let assignment2 = Assignment(
+            "y".to_string(),
+            Box::new(Add(Box::new(Var("x".to_string())), Box::new(CInt(1)))),
+            None,
+        );
+        let program = Sequence(Box::new(assignment1), Box::new(assignment2));
+
+        match check_stmt(program, &env) {
+            Ok(ControlFlow::Continue(new_env)) => {
+                assert_eq!(new_env.search_frame("y".to_string()), Some(TReal).as_ref());
+            }
+            Ok(_) => assert!(false),
+            Err(s) => assert!(false, "{}", s),
+        }
+    }
+
+    #[test]
+    fn check_reassignment_keeps_inferred_type() {
+        let env: Environment<Type> = Environment::new();
+
+        let assignment1 = Assignment(
+            "y".to_string(),
+            Box::new(Add(Box::new(CInt(1)), Box::new(CInt(2)))),
+            None,
+        );
+        let assignment2 = Assignment(
+            "y".to_string(),
+            Box::new(CString("three".to_string())),
+            Some(TString),
+        );
+        let program = Sequence(Box::new(assignment1), Box::new(assignment2));
+
+        match check_stmt(program, &env) {
+            Ok(_) => assert!(false),
+            Err(s) => assert_eq!(
+                s,
+                "[Type Error on '__main__()'] 'y' has mismatched types: expected 'TInteger', found 'TString'."
+            ),
+        }
+    }
+
+    #[test]
+    fn check_nothing_resolved_by_reassignment() {
+        let env: Environment<Type> = Environment::new();
+
+        let assignment1 = Assignment("x".to_string(), Box::new(CNothing), None);
+        let assignment2 = Assignment("x".to_string(), Box::new(CJust(Box::new(CInt(3)))), None);
+        let program = Sequence(Box::new(assignment1), Box::new(assignment2));
+
+        match check_stmt(program, &env) {
+            Ok(ControlFlow::Continue(new_env)) => {
+                assert_eq!(
+                    new_env.search_frame("x".to_string()),
+                    Some(TMaybe(Box::new(TInteger))).as_ref()
+                );
+            }
+            Ok(_) => assert!(false),
+            Err(s) => assert!(false, "{}", s),
+        }
+    }
+
+    #[test]
+    fn check_nothing_then_mismatched_reassignment() {
+        let env: Environment<Type> = Environment::new();
+
+        let program = Sequence(
+            Box::new(Sequence(
+                Box::new(Assignment("x".to_string(), Box::new(CNothing), None)),
+                Box::new(Assignment(
+                    "x".to_string(),
+                    Box::new(CJust(Box::new(CInt(3)))),
+                    None,
+                )),
+            )),
+            Box::new(Assignment(
+                "x".to_string(),
+                Box::new(CJust(Box::new(CTrue))),
+                None,
+            )),
+        );
+
+        match check_stmt(program, &env) {
+            Ok(_) => assert!(false),
+            Err(s) => assert_eq!(
+                s,
+                "[Type Error on '__main__()'] 'x' has mismatched types: expected 'TMaybe(TInteger)', found 'TMaybe(TBool)'."
+            ),
+        }
+    }
+
+    #[test]
+    fn check_maybe_comparison() {
+        let env: Environment<Type> = Environment::new();
+
+        let not_equal = Not(Box::new(EQ(
+            Box::new(CJust(Box::new(CInt(3)))),
+            Box::new(CNothing),
+        )));
+        let mismatched = EQ(
+            Box::new(CJust(Box::new(CInt(3)))),
+            Box::new(CJust(Box::new(CString("3".to_string())))),
+        );
+
+        assert_eq!(check_exp(not_equal, &env), Ok(TBool));
+        assert_eq!(
+            check_exp(mismatched, &env),
+            Err(String::from(
+                "[Type Error] cannot compare 'TMaybe(TInteger)' with 'TMaybe(TString)'."
+            ))
+        );
+
+        // Functions have no equality at runtime
+        let mut env = env;
+        let int_to_int = TFunction(Box::new(Some(TInteger)), vec![TInteger]);
+        env.insert_variable("f".to_string(), int_to_int);
+        assert_eq!(
+            check_exp(EQ(Box::new(Var("f".to_string())), Box::new(Var("f".to_string()))), &env),
+            Err(String::from(
+                "[Type Error] cannot compare 'TFunction(Some(TInteger), [TInteger])' with 'TFunction(Some(TInteger), [TInteger])', functions have no equality."
+            ))
+        );
+    }
 
-        assert_eq!(check_exp(just, &env), Ok(TMaybe(Box::new(TInteger))))
+    #[test]
+    fn check_return_unifies_with_declared_result() {
+        let env: Environment<Type> = Environment::new();
+
+        let func = FuncDef(Function {
+            name: "safe".to_string(),
+            type_params: vec![],
+            kind: Some(TResult(Box::new(TInteger), Box::new(TString))),
+            params: Some(vec![("a".to_string(), TInteger)]),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(IfThenElse(
+                Box::new(GT(Box::new(Var("a".to_string())), Box::new(CInt(0)))),
+                Box::new(Return(Box::new(COk(Box::new(CInt(1)))))),
+                Some(Box::new(Return(Box::new(CErr(Box::new(CString(
+                    "negative".to_string(),
+                ))))))),
+            ))),
+        });
+
+        assert!(check_stmt(func, &env).is_ok());
     }
 
     #[test]
-    fn check_is_error_result_positive() {
-        let env = Environment::new();
-        let bool = CTrue;
-        let ok = COk(Box::new(bool));
-        let ie = IsError(Box::new(ok));
+    fn check_unify_occurs_check() {
+        let var = fresh_type_var();
+        let mut subst = Substitution::new();
 
-        assert_eq!(check_exp(ie, &env), Ok(TBool));
+        assert!(!unify(&var, &TList(Box::new(var.clone())), &mut subst));
+        assert!(unify(&var, &TList(Box::new(TInteger)), &mut subst));
+        assert_eq!(apply(&subst, &var), TList(Box::new(TInteger)));
+    }
+
+    fn identity() -> Statement {
+        FuncDef(Function {
+            name: "identity".to_string(),
+            type_params: vec!["T".to_string()],
+            kind: Some(TVar("T".to_string())),
+            params: Some(vec![("x".to_string(), TVar("T".to_string()))]),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(Return(Box::new(Var("x".to_string()))))),
+        })
     }
 
     #[test]
-    fn check_is_error_result_error() {
-        let env = Environment::new();
-        let bool = CTrue;
-        let ie = IsError(Box::new(bool));
+    fn check_generic_function_instantiation() {
+        let env: Environment<Type> = Environment::new();
+
+        let program = Sequence(
+            Box::new(identity()),
+            Box::new(Sequence(
+                Box::new(Assignment(
+                    "a".to_string(),
+                    Box::new(FuncCall("identity".to_string(), vec![CInt(1)])),
+                    None,
+                )),
+                Box::new(Assignment(
+                    "b".to_string(),
+                    Box::new(FuncCall(
+                        "identity".to_string(),
+                        vec![CJust(Box::new(CString("b".to_string())))],
+                    )),
+                    None,
+                )),
+            )),
+        );
+
+        match check_stmt(program, &env) {
+            Ok(ControlFlow::Continue(new_env)) => {
+                assert_eq!(new_env.search_frame("a".to_string()), Some(&TInteger));
+                assert_eq!(
+                    new_env.search_frame("b".to_string()),
+                    Some(&TMaybe(Box::new(TString)))
+                );
+            }
+            Ok(_) => assert!(false),
+            Err(s) => assert!(false, "{}", s),
+        }
+    }
+
+    #[test]
+    fn check_type_parameters_are_rigid_in_the_body() {
+        let env: Environment<Type> = Environment::new();
+        let t = || TVar("T".to_string());
+        let generic = |arg: Expression| {
+            FuncDef(Function {
+                name: "f".to_string(),
+                type_params: vec!["T".to_string()],
+                kind: Some(TInteger),
+                params: Some(vec![
+                    (
+                        "g".to_string(),
+                        TFunction(Box::new(Some(TInteger)), vec![t()]),
+                    ),
+                    ("x".to_string(), t()),
+                ]),
+                variadic: false,
+                defaults: vec![],
+                body: Some(Box::new(Return(Box::new(FuncCall(
+                    "g".to_string(),
+                    vec![arg],
+                ))))),
+            })
+        };
 
+        // def f[T](g: TFunction[[T], TInteger], x: T) -> TInteger: return g(1)
         assert_eq!(
-            check_exp(ie, &env),
-            Err(String::from("[Type Error] expecting a result type value."))
+            check_program(vec![generic(CInt(1))], &env)
+                .into_iter()
+                .map(|d| d.message)
+                .collect::<Vec<String>>(),
+            vec![String::from("[Type Error on 'f()'] 'g()' has mismatched arguments: expected 'TVar(\"T\")', found 'TInteger'.")]
         );
+        // ... return g(x)
+        assert!(check_stmt(generic(Var("x".to_string())), &env).is_ok());
     }
 
     #[test]
-    fn check_nothing() {
-        let env = Environment::new();
+    fn check_generic_arguments_must_agree() {
+        let env: Environment<Type> = Environment::new();
+
+        let same = FuncDef(Function {
+            name: "same".to_string(),
+            type_params: vec!["T".to_string()],
+            kind: Some(TBool),
+            params: Some(vec![
+                ("a".to_string(), TVar("T".to_string())),
+                ("b".to_string(), TVar("T".to_string())),
+            ]),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(Return(Box::new(EQ(
+                Box::new(Var("a".to_string())),
+                Box::new(Var("b".to_string())),
+            ))))),
+        });
+        let program = Sequence(
+            Box::new(same),
+            Box::new(Assignment(
+                "c".to_string(),
+                Box::new(FuncCall(
+                    "same".to_string(),
+                    vec![CTrue, CString("true".to_string())],
+                )),
+                None,
+            )),
+        );
+
+        match check_stmt(program, &env) {
+            Ok(_) => assert!(false),
+            Err(s) => assert_eq!(s, "[Type Error on '__main__()'] 'same()' has mismatched arguments: expected 'TBool', found 'TString'."),
+        }
+    }
 
-        assert_eq!(check_exp(CNothing, &env), Ok(TMaybe(Box::new(TAny))));
+    fn point() -> Statement {
+        StructDef(
+            "Point".to_string(),
+            vec![("x".to_string(), TReal), ("y".to_string(), TReal)],
+        )
     }
 
     #[test]
-    fn check_is_nothing_on_maybe() {
-        let env = Environment::new();
-        let c5 = CInt(5);
-        let just = CJust(Box::new(c5));
-        let is_nothing = IsNothing(Box::new(just));
+    fn check_struct_construction_and_field_access() {
+        let env: Environment<Type> = Environment::new();
 
-        assert_eq!(check_exp(is_nothing, &env), Ok(TBool));
+        let program = Sequence(
+            Box::new(point()),
+            Box::new(Sequence(
+                Box::new(Assignment(
+                    "p".to_string(),
+                    Box::new(FuncCall("Point".to_string(), vec![CReal(1.0), CReal(2.0)])),
+                    None,
+                )),
+                Box::new(Assignment(
+                    "x".to_string(),
+                    Box::new(FieldAccess(Box::new(Var("p".to_string())), "x".to_string())),
+                    None,
+                )),
+            )),
+        );
+
+        match check_stmt(program, &env) {
+            Ok(ControlFlow::Continue(new_env)) => {
+                assert_eq!(
+                    new_env.search_frame("p".to_string()),
+                    Some(&TUser("Point".to_string()))
+                );
+                assert_eq!(new_env.search_frame("x".to_string()), Some(&TReal));
+            }
+            Ok(_) => assert!(false),
+            Err(s) => assert!(false, "{}", s),
+        }
     }
 
     #[test]
-    fn check_is_nothing_type_error() {
-        let env = Environment::new();
-        let c5 = CInt(5);
-        let is_nothing = IsNothing(Box::new(c5));
+    fn check_struct_errors() {
+        let env: Environment<Type> = Environment::new();
+
+        let construct = Assignment(
+            "p".to_string(),
+            Box::new(FuncCall("Point".to_string(), vec![CReal(1.0), CTrue])),
+            None,
+        );
+        let unknown_field = Sequence(
+            Box::new(Assignment(
+                "p".to_string(),
+                Box::new(FuncCall("Point".to_string(), vec![CReal(1.0), CReal(2.0)])),
+                None,
+            )),
+            Box::new(FieldAssignment(
+                Box::new(Var("p".to_string())),
+                "z".to_string(),
+                Box::new(CReal(3.0)),
+            )),
+        );
+        let mismatched_field = Sequence(
+            Box::new(Assignment(
+                "p".to_string(),
+                Box::new(FuncCall("Point".to_string(), vec![CReal(1.0), CReal(2.0)])),
+                None,
+            )),
+            Box::new(FieldAssignment(
+                Box::new(Var("p".to_string())),
+                "x".to_string(),
+                Box::new(CString("one".to_string())),
+            )),
+        );
+        let temporary_record = FieldAssignment(
+            Box::new(FuncCall("Point".to_string(), vec![CReal(1.0), CReal(2.0)])),
+            "x".to_string(),
+            Box::new(CReal(3.0)),
+        );
+
+        let errors: Vec<String> =
+            vec![construct, unknown_field, mismatched_field, temporary_record]
+                .into_iter()
+                .map(
+                    |stmt| match check_stmt(Sequence(Box::new(point()), Box::new(stmt)), &env) {
+                        Ok(_) => String::new(),
+                        Err(s) => s,
+                    },
+                )
+                .collect();
 
         assert_eq!(
-            check_exp(is_nothing, &env),
-            Err(String::from("[Type Error] expecting a maybe type value."))
+            errors,
+            vec![
+                "[Type Error on '__main__()'] 'Point()' has mismatched arguments: expected 'TReal', found 'TBool'.",
+                "[Type Error on '__main__()'] 'Point' has no field 'z'.",
+                "[Type Error on '__main__()'] field 'x' has mismatched types: expected 'TReal', found 'TString'.",
+                "[Type Error on '__main__()'] can only assign to fields of variables.",
+            ]
         );
     }
 
     #[test]
-    fn check_unwrap_maybe() {
-        let env = Environment::new();
-        let c5 = CInt(5);
-        let some = CJust(Box::new(c5));
-        let u = Unwrap(Box::new(some));
+    fn check_undefined_user_type() {
+        let env: Environment<Type> = Environment::new();
 
-        assert_eq!(check_exp(u, &env), Ok(TInteger));
+        let func = FuncDef(Function {
+            name: "norm".to_string(),
+            type_params: vec![],
+            kind: Some(TReal),
+            params: Some(vec![("p".to_string(), TUser("Vector".to_string()))]),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(Return(Box::new(CReal(0.0))))),
+        });
+
+        match check_stmt(func, &env) {
+            Ok(_) => assert!(false),
+            Err(s) => assert_eq!(s, "[Type Error on 'norm()'] type 'Vector' is not defined."),
+        }
     }
 
-    #[test]
-    fn check_unwrap_maybe_type_error() {
-        let env = Environment::new();
-        let c5 = CInt(5);
-        let u = Unwrap(Box::new(c5));
+    fn shape() -> Statement {
+        EnumDef(
+            "Shape".to_string(),
+            vec![
+                ("Circle".to_string(), vec![TReal]),
+                ("Rect".to_string(), vec![TReal, TReal]),
+                ("Empty".to_string(), vec![]),
+            ],
+        )
+    }
 
-        assert_eq!(
-            check_exp(u, &env),
-            Err(String::from(
-                "[Type Error] expecting a maybe or result type value."
-            ))
-        );
+    fn area(arms: Vec<(Pattern, Statement)>) -> Statement {
+        Sequence(
+            Box::new(shape()),
+            Box::new(FuncDef(Function {
+                name: "area".to_string(),
+                type_params: vec![],
+                kind: Some(TReal),
+                params: Some(vec![(
+                    "s".to_string(),
+                    TMaybe(Box::new(TUser("Shape".to_string()))),
+                )]),
+                variadic: false,
+                defaults: vec![],
+                body: Some(Box::new(Statement::Match(
+                    Box::new(Var("s".to_string())),
+                    arms,
+                ))),
+            })),
+        )
+    }
+
+    fn case(name: &str, args: Vec<Pattern>) -> Pattern {
+        Pattern::Constructor(name.to_string(), args)
+    }
+
+    fn binding(name: &str) -> Pattern {
+        Pattern::Binding(name.to_string())
+    }
+
+    fn returns(exp: Expression) -> Statement {
+        Return(Box::new(exp))
     }
 
     #[test]
-    fn check_unwrap_result() {
-        let env = Environment::new();
-        let bool = CTrue;
-        let ok = COk(Box::new(bool));
-        let u = Unwrap(Box::new(ok));
+    fn check_exhaustive_match() {
+        let env: Environment<Type> = Environment::new();
 
-        assert_eq!(check_exp(u, &env), Ok(TBool));
+        let program = area(vec![
+            (
+                case("Just", vec![case("Circle", vec![binding("r")])]),
+                returns(Mul(
+                    Box::new(Var("r".to_string())),
+                    Box::new(Var("r".to_string())),
+                )),
+            ),
+            (
+                case("Just", vec![case("Rect", vec![binding("w"), binding("h")])]),
+                returns(Mul(
+                    Box::new(Var("w".to_string())),
+                    Box::new(Var("h".to_string())),
+                )),
+            ),
+            (case("Just", vec![Pattern::Wildcard]), returns(CReal(0.0))),
+            (case("Nothing", vec![]), returns(CReal(-1.0))),
+        ]);
+
+        assert!(check_stmt(program, &env).is_ok());
     }
 
     #[test]
-    fn check_propagate_maybe() {
-        let env = Environment::new();
-        let c5 = CInt(5);
-        let some = CJust(Box::new(c5));
-        let u = Propagate(Box::new(some));
+    fn check_non_exhaustive_match() {
+        let env: Environment<Type> = Environment::new();
 
-        assert_eq!(check_exp(u, &env), Ok(TInteger));
+        let program = area(vec![
+            (
+                case("Just", vec![case("Circle", vec![binding("r")])]),
+                returns(Var("r".to_string())),
+            ),
+            (
+                case("Just", vec![case("Empty", vec![])]),
+                returns(CReal(0.0)),
+            ),
+            (case("Nothing", vec![]), returns(CReal(-1.0))),
+        ]);
+
+        match check_stmt(program, &env) {
+            Ok(_) => assert!(false),
+            Err(s) => assert_eq!(
+                s,
+                "[Type Error on 'area()'] match is not exhaustive: case 'Just(Rect(_, _))' is not covered."
+            ),
+        }
     }
 
     #[test]
-    fn check_propagate_maybe_type_error() {
-        let env = Environment::new();
-        let c5 = CInt(5);
-        let u = Propagate(Box::new(c5));
+    fn check_unreachable_case() {
+        let env: Environment<Type> = Environment::new();
 
-        assert_eq!(
-            check_exp(u, &env),
-            Err(String::from(
-                "[Type Error] expecting a maybe or result type value."
-            ))
-        );
+        let program = area(vec![
+            (binding("other"), returns(CReal(0.0))),
+            (case("Nothing", vec![]), returns(CReal(-1.0))),
+        ]);
+
+        match check_stmt(program, &env) {
+            Ok(_) => assert!(false),
+            Err(s) => assert_eq!(s, "[Type Error on 'area()'] unreachable case 'Nothing'."),
+        }
     }
 
     #[test]
-    fn check_propagate_result() {
-        let env = Environment::new();
-        let bool = CTrue;
-        let ok = COk(Box::new(bool));
-        let u = Propagate(Box::new(ok));
+    fn check_pattern_errors() {
+        let env: Environment<Type> = Environment::new();
 
-        assert_eq!(check_exp(u, &env), Ok(TBool));
+        let wrong_constructor = area(vec![
+            (case("Ok", vec![Pattern::Wildcard]), returns(CReal(0.0))),
+            (Pattern::Wildcard, returns(CReal(0.0))),
+        ]);
+        let wrong_arity = area(vec![
+            (
+                case("Just", vec![case("Circle", vec![])]),
+                returns(CReal(0.0)),
+            ),
+            (Pattern::Wildcard, returns(CReal(0.0))),
+        ]);
+        let wrong_binding_type = area(vec![
+            (
+                case("Just", vec![case("Circle", vec![binding("r")])]),
+                returns(IsNothing(Box::new(Var("r".to_string())))),
+            ),
+            (Pattern::Wildcard, returns(CReal(0.0))),
+        ]);
+
+        let errors: Vec<String> = vec![wrong_constructor, wrong_arity, wrong_binding_type]
+            .into_iter()
+            .map(|program| match check_stmt(program, &env) {
+                Ok(_) => String::new(),
+                Err(s) => s,
+            })
+            .collect();
+
+        assert_eq!(
+            errors,
+            vec![
+                "[Type Error on 'area()'] 'Ok' is not a constructor of 'TMaybe(TUser(\"Shape\"))'.",
+                "[Type Error on 'area()'] 'Circle' expects 1 fields, found 0.",
+                "[Type Error] expecting a maybe type value.",
+            ]
+        );
     }
 
     #[test]
-    fn check_assignment() {
+    fn check_enum_constructors() {
         let env: Environment<Type> = Environment::new();
 
-        let assignment = Assignment("a".to_string(), Box::new(CTrue), Some(TBool));
+        let program = Sequence(
+            Box::new(shape()),
+            Box::new(Sequence(
+                Box::new(Assignment(
+                    "a".to_string(),
+                    Box::new(FuncCall("Rect".to_string(), vec![CReal(1.0), CReal(2.0)])),
+                    None,
+                )),
+                Box::new(Assignment(
+                    "a".to_string(),
+                    Box::new(Var("Empty".to_string())),
+                    None,
+                )),
+            )),
+        );
 
-        match check_stmt(assignment, &env) {
-            Ok(ControlFlow::Continue(new_env)) => {
-                assert_eq!(new_env.search_frame("a".to_string()), Some(TBool).as_ref());
-            }
+        match check_stmt(program, &env) {
+            Ok(ControlFlow::Continue(new_env)) => assert_eq!(
+                new_env.search_frame("a".to_string()),
+                Some(&TUser("Shape".to_string()))
+            ),
             Ok(_) => assert!(false),
             Err(s) => assert!(false, "{}", s),
         }
     }
 
+    fn divide() -> Statement {
+        FuncDef(Function {
+            name: "divide".to_string(),
+            type_params: vec![],
+            kind: Some(TResult(Box::new(TInteger), Box::new(TString))),
+            params: Some(vec![
+                ("a".to_string(), TInteger),
+                ("b".to_string(), TInteger),
+            ]),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(IfThenElse(
+                Box::new(EQ(Box::new(Var("b".to_string())), Box::new(CInt(0)))),
+                Box::new(Return(Box::new(CErr(Box::new(CString(
+                    "division by zero".to_string(),
+                )))))),
+                Some(Box::new(Return(Box::new(COk(Box::new(Div(
+                    Box::new(Var("a".to_string())),
+                    Box::new(Var("b".to_string())),
+                ))))))),
+            ))),
+        })
+    }
+
     #[test]
-    fn check_assignment_error1() {
+    fn check_match_on_result_binds_payload_types() {
         let env: Environment<Type> = Environment::new();
 
-        let assignment = Assignment("a".to_string(), Box::new(CTrue), Some(TInteger));
+        let program = Sequence(
+            Box::new(divide()),
+            Box::new(Statement::Match(
+                Box::new(FuncCall("divide".to_string(), vec![CInt(6), CInt(3)])),
+                vec![
+                    (
+                        case("Ok", vec![binding("v")]),
+                        Block(vec![Assignment(
+                            "half".to_string(),
+                            Box::new(Div(Box::new(Var("v".to_string())), Box::new(CInt(2)))),
+                            None,
+                        )]),
+                    ),
+                    (
+                        case("Err", vec![binding("message")]),
+                        Block(vec![Assignment(
+                            "half".to_string(),
+                            Box::new(Div(Box::new(Var("message".to_string())), Box::new(CInt(2)))),
+                            None,
+                        )]),
+                    ),
+                ],
+            )),
+        );
 
-        match check_stmt(assignment, &env) {
+        match check_stmt(program, &env) {
             Ok(_) => assert!(false),
-            Err(s) => assert_eq!(
-                s,
-                "[Type Error on '__main__()'] 'a' has mismatched types: expected 'TInteger', found 'TBool'."
-            ),
+            Err(s) => assert_eq!(s, "[Type Error] expecting numeric type values."),
         }
     }
 
     #[test]
-    fn check_assignment_error2() {
+    fn check_match_on_maybe_exhaustiveness() {
+        let env: Environment<Type> = Environment::new();
+
+        let just_only = Sequence(
+            Box::new(Assignment(
+                "m".to_string(),
+                Box::new(CJust(Box::new(CInt(1)))),
+                None,
+            )),
+            Box::new(Statement::Match(
+                Box::new(Var("m".to_string())),
+                vec![(case("Just", vec![binding("x")]), Block(vec![]))],
+            )),
+        );
+        let err_only = Statement::Match(
+            Box::new(COk(Box::new(CTrue))),
+            vec![(case("Err", vec![Pattern::Wildcard]), Block(vec![]))],
+        );
+
+        assert_eq!(
+            check_stmt(just_only, &env).err(),
+            Some(String::from(
+                "[Type Error on '__main__()'] match is not exhaustive: case 'Nothing' is not covered."
+            ))
+        );
+        assert_eq!(
+            check_stmt(err_only, &env).err(),
+            Some(String::from(
+                "[Type Error on '__main__()'] match is not exhaustive: case 'Ok(_)' is not covered."
+            ))
+        );
+    }
+
+    #[test]
+    fn check_match_expression_unifies_arms() {
         let env: Environment<Type> = Environment::new();
 
-        let assignment1 = Assignment("a".to_string(), Box::new(CTrue), Some(TBool));
-        let assignment2 = Assignment("a".to_string(), Box::new(CInt(1)), None);
-        let program = Sequence(Box::new(assignment1), Box::new(assignment2));
+        let program = |nothing: Expression| {
+            Sequence(
+                Box::new(Assignment(
+                    "m".to_string(),
+                    Box::new(CJust(Box::new(CInt(1)))),
+                    None,
+                )),
+                Box::new(Assignment(
+                    "y".to_string(),
+                    Box::new(Expression::Match(
+                        Box::new(Var("m".to_string())),
+                        vec![
+                            (case("Just", vec![binding("x")]), Var("x".to_string())),
+                            (case("Nothing", vec![]), nothing),
+                        ],
+                    )),
+                    None,
+                )),
+            )
+        };
 
-        match check_stmt(program, &env) {
-            Ok(_) => assert!(false),
-            Err(s) => assert_eq!(
-                s,
-                "[Type Error on '__main__()'] 'a' has mismatched types: expected 'TBool', found 'TInteger'."
-            ),
+        match check_stmt(program(CInt(0)), &env) {
+            Ok(ControlFlow::Continue(new_env)) => {
+                assert_eq!(new_env.search_frame("y".to_string()), Some(&TInteger))
+            }
+            _ => assert!(false),
         }
+        assert_eq!(
+            check_stmt(program(CTrue), &env).err(),
+            Some(String::from(
+                "[Type Error on '__main__()'] match arms have mismatched types: expected 'TInteger', found 'TBool'."
+            ))
+        );
     }
 
     #[test]
-    fn check_if_then_else_error() {
+    fn check_match_keeps_variables_bound_by_every_arm() {
         let env: Environment<Type> = Environment::new();
-
-        let ifthenelse = IfThenElse(
-            Box::new(CInt(1)),
-            Box::new(Assignment(
-                "a".to_string(),
-                Box::new(CInt(1)),
-                Some(TInteger),
-            )),
-            Some(Box::new(Assignment(
-                "b".to_string(),
-                Box::new(CReal(2.0)),
-                Some(TReal),
-            ))),
+        let var = |name: &str| Box::new(Var(name.to_string()));
+        let assign =
+            |name: &str, exp: Expression| Assignment(name.to_string(), Box::new(exp), None);
+
+        // s = Circle(1.0)
+        // match s:
+        //     case Circle(r): area = r * r
+        //     case Rect(w, h): area = w * h
+        //     case Empty: <empty>
+        // total = area
+        let program = |empty: Statement| {
+            Block(vec![
+                shape(),
+                assign("s", FuncCall("Circle".to_string(), vec![CReal(1.0)])),
+                Statement::Match(
+                    var("s"),
+                    vec![
+                        (
+                            case("Circle", vec![binding("r")]),
+                            assign("area", Mul(var("r"), var("r"))),
+                        ),
+                        (
+                            case("Rect", vec![binding("w"), binding("h")]),
+                            assign("area", Mul(var("w"), var("h"))),
+                        ),
+                        (case("Empty", vec![]), empty),
+                    ],
+                ),
+                assign("total", Var("area".to_string())),
+            ])
+        };
+
+        match check_stmt(program(assign("area", CReal(0.0))), &env) {
+            Ok(ControlFlow::Continue(new_env)) => {
+                assert_eq!(new_env.search_frame("total".to_string()), Some(&TReal))
+            }
+            _ => assert!(false),
+        }
+        assert_eq!(
+            check_stmt(program(assign("empty", CTrue)), &env).err(),
+            Some(String::from(
+                "[Name Error on '__main__'] 'area' is not defined."
+            ))
         );
+    }
 
-        match check_stmt(ifthenelse, &env) {
-            Ok(_) => assert!(false),
-            Err(s) => assert_eq!(
-                s,
-                "[Type Error on '__main__()'] if expression must be boolean."
-            ),
-        }
+    fn double() -> Statement {
+        FuncDef(Function {
+            name: "double".to_string(),
+            type_params: vec![],
+            kind: Some(TInteger),
+            params: Some(vec![("x".to_string(), TInteger)]),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(Return(Box::new(Mul(
+                Box::new(Var("x".to_string())),
+                Box::new(CInt(2)),
+            ))))),
+        })
     }
 
     #[test]
-    fn check_while_error() {
+    fn check_combinators_on_maybe_and_result() {
         let env: Environment<Type> = Environment::new();
+        let mut env = match check_stmt(Sequence(Box::new(double()), Box::new(divide())), &env) {
+            Ok(ControlFlow::Continue(new_env)) => new_env,
+            _ => panic!("definitions should type check"),
+        };
+        env.insert_variable("m".to_string(), TMaybe(Box::new(TInteger)));
+        env.insert_variable(
+            "positive".to_string(),
+            TFunction(Box::new(Some(TMaybe(Box::new(TInteger)))), vec![TInteger]),
+        );
+        env.insert_variable(
+            "recover".to_string(),
+            TFunction(
+                Box::new(Some(TResult(Box::new(TInteger), Box::new(TBool)))),
+                vec![TString],
+            ),
+        );
+        let m = || Box::new(Var("m".to_string()));
+        let function = |name: &str| Box::new(Var(name.to_string()));
+        let division = || Box::new(FuncCall("divide".to_string(), vec![CInt(6), CInt(3)]));
 
-        let assignment1 = Assignment("a".to_string(), Box::new(CInt(3)), Some(TInteger));
-        let assignment2 = Assignment("b".to_string(), Box::new(CInt(0)), Some(TInteger));
-        let while_stmt = While(
-            Box::new(CInt(1)),
-            Box::new(Assignment(
-                "b".to_string(),
-                Box::new(Add(Box::new(Var("b".to_string())), Box::new(CInt(1)))),
-                None,
-            )),
+        assert_eq!(
+            check_exp(Map(m(), function("double")), &env),
+            Ok(TMaybe(Box::new(TInteger)))
         );
-        let program = Sequence(
-            Box::new(assignment1),
-            Box::new(Sequence(Box::new(assignment2), Box::new(while_stmt))),
+        assert_eq!(
+            check_exp(Map(division(), function("double")), &env),
+            Ok(TResult(Box::new(TInteger), Box::new(TString)))
+        );
+        assert_eq!(
+            check_exp(AndThen(m(), function("positive")), &env),
+            Ok(TMaybe(Box::new(TInteger)))
+        );
+        assert_eq!(
+            check_exp(OrElse(division(), function("recover")), &env),
+            Ok(TResult(Box::new(TInteger), Box::new(TBool)))
         );
+        assert_eq!(
+            check_exp(UnwrapOr(division(), Box::new(CInt(0))), &env),
+            Ok(TInteger)
+        );
+        assert_eq!(
+            check_exp(OkOr(m(), Box::new(CString("missing".to_string()))), &env),
+            Ok(TResult(Box::new(TInteger), Box::new(TString)))
+        );
+    }
 
-        match check_stmt(program, &env) {
-            Ok(_) => assert!(false),
-            Err(s) => assert_eq!(
-                s,
-                "[Type Error on '__main__()'] while expression must be boolean."
+    #[test]
+    fn check_combinator_errors() {
+        let env: Environment<Type> = Environment::new();
+        let mut env = match check_stmt(Sequence(Box::new(double()), Box::new(divide())), &env) {
+            Ok(ControlFlow::Continue(new_env)) => new_env,
+            _ => panic!("definitions should type check"),
+        };
+        env.insert_variable("m".to_string(), TMaybe(Box::new(TString)));
+        let division = || Box::new(FuncCall("divide".to_string(), vec![CInt(6), CInt(3)]));
+
+        assert_eq!(
+            check_exp(
+                Map(Box::new(CInt(1)), Box::new(Var("double".to_string()))),
+                &env
             ),
-        }
+            Err(String::from(
+                "[Type Error] 'map' is only defined for maybe and result values."
+            ))
+        );
+        assert_eq!(
+            check_exp(UnwrapOr(division(), Box::new(CString("zero".to_string()))), &env),
+            Err(String::from(
+                "[Type Error] 'unwrap_or' has mismatched arguments: expected 'TInteger', found 'TString'."
+            ))
+        );
+        assert_eq!(
+            check_exp(
+                OkOr(division(), Box::new(CString("missing".to_string()))),
+                &env
+            ),
+            Err(String::from(
+                "[Type Error] 'ok_or' is only defined for maybe values."
+            ))
+        );
+        assert!(check_exp(
+            Map(
+                Box::new(Var("m".to_string())),
+                Box::new(Var("double".to_string()))
+            ),
+            &env
+        )
+        .is_err());
     }
 
     #[test]
-    fn check_func_def() {
+    fn check_first_class_functions() {
         let env: Environment<Type> = Environment::new();
-
-        let func = FuncDef(Function {
-            name: "add".to_string(),
+        let int_to_int = TFunction(Box::new(Some(TInteger)), vec![TInteger]);
+        let lambda = |body: Expression| {
+            Lambda(Box::new(Function {
+                name: "lambda".to_string(),
+                type_params: vec![],
+                kind: Some(TInteger),
+                params: Some(vec![("y".to_string(), TInteger)]),
+                variadic: false,
+                defaults: vec![],
+                body: Some(Box::new(Return(Box::new(body)))),
+            }))
+        };
+
+        // def apply(f: TFunction[[TInteger], TInteger], x: TInteger) -> TInteger: return f(x)
+        let apply_def = FuncDef(Function {
+            name: "apply".to_string(),
+            type_params: vec![],
             kind: Some(TInteger),
             params: Some(vec![
-                ("a".to_string(), TInteger),
-                ("b".to_string(), TInteger),
+                ("f".to_string(), int_to_int.clone()),
+                ("x".to_string(), TInteger),
             ]),
-            body: Some(Box::new(Return(Box::new(Add(
-                Box::new(Var("a".to_string())),
-                Box::new(Var("b".to_string())),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(Return(Box::new(FuncCall(
+                "f".to_string(),
+                vec![Var("x".to_string())],
             ))))),
         });
+        // def adder(x: TInteger) -> TFunction[[TInteger], TInteger]: return lambda(y: TInteger) -> TInteger: x + y
+        let adder_def = FuncDef(Function {
+            name: "adder".to_string(),
+            type_params: vec![],
+            kind: Some(int_to_int.clone()),
+            params: Some(vec![("x".to_string(), TInteger)]),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(Return(Box::new(lambda(Add(
+                Box::new(Var("x".to_string())),
+                Box::new(Var("y".to_string())),
+            )))))),
+        });
 
-        match check_stmt(func, &env) {
+        let env = match check_stmt(Sequence(Box::new(apply_def), Box::new(adder_def)), &env) {
+            Ok(ControlFlow::Continue(new_env)) => new_env,
+            _ => panic!("definitions should type check"),
+        };
+
+        assert_eq!(
+            check_exp(lambda(Var("y".to_string())), &env),
+            Ok(int_to_int.clone())
+        );
+        assert_eq!(
+            check_exp(
+                FuncCall(
+                    "apply".to_string(),
+                    vec![
+                        lambda(Mul(Box::new(Var("y".to_string())), Box::new(CInt(2)))),
+                        CInt(3)
+                    ]
+                ),
+                &env
+            ),
+            Ok(TInteger)
+        );
+        assert_eq!(
+            check_exp(
+                Call(
+                    Box::new(FuncCall("adder".to_string(), vec![CInt(1)])),
+                    vec![CInt(2)]
+                ),
+                &env
+            ),
+            Ok(TInteger)
+        );
+        assert_eq!(
+            check_exp(
+                FuncCall("apply".to_string(), vec![Var("adder".to_string()), CInt(3)]),
+                &env
+            ),
+            Err(String::from("[Type Error on '__main__()'] 'apply()' has mismatched arguments: expected 'TFunction(Some(TInteger), [TInteger])', found 'TFunction(Some(TFunction(Some(TInteger), [TInteger])), [TInteger])'."))
+        );
+        assert_eq!(
+            check_exp(Call(Box::new(CInt(1)), vec![CInt(2)]), &env),
+            Err(String::from(
+                "[Type Error on '__main__()'] 'TInteger' is not a function."
+            ))
+        );
+    }
+
+    // def bump() -> TInteger:
+    //     global count
+    //     count = <value>
+    //     return count
+    fn bump(value: Expression) -> Statement {
+        FuncDef(Function {
+            name: "bump".to_string(),
+            type_params: vec![],
+            kind: Some(TInteger),
+            params: Some(vec![]),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(Sequence(
+                Box::new(Global(vec!["count".to_string()])),
+                Box::new(Sequence(
+                    Box::new(Assignment("count".to_string(), Box::new(value), None)),
+                    Box::new(Return(Box::new(Var("count".to_string())))),
+                )),
+            ))),
+        })
+    }
+
+    #[test]
+    fn check_global_declarations() {
+        let env: Environment<Type> = Environment::new();
+        let count = Assignment("count".to_string(), Box::new(CInt(0)), None);
+
+        let increment = Add(Box::new(Var("count".to_string())), Box::new(CInt(1)));
+        match check_stmt(
+            Sequence(Box::new(count.clone()), Box::new(bump(increment.clone()))),
+            &env,
+        ) {
             Ok(ControlFlow::Continue(new_env)) => {
-                assert_eq!(
-                    new_env.search_frame("add".to_string()),
-                    Some(TFunction(
-                        Box::new(Some(TInteger)),
-                        vec![TInteger, TInteger]
-                    ))
-                    .as_ref()
-                );
+                assert_eq!(new_env.search_frame("count".to_string()), Some(&TInteger))
             }
-            Ok(_) => assert!(false),
-            Err(s) => assert!(false, "{}", s),
+            _ => assert!(false),
         }
+
+        let rename = CString("one".to_string());
+        assert_eq!(
+            check_stmt(Sequence(Box::new(count), Box::new(bump(rename))), &env).err(),
+            Some(String::from("[Type Error on 'bump()'] 'count' has mismatched types: expected 'TInteger', found 'TString'."))
+        );
+        // A function may assign a global the module has not bound yet, but not read it
+        assert!(check_stmt(bump(CInt(1)), &env).is_ok());
+        assert_eq!(
+            check_stmt(bump(increment.clone()), &env).err(),
+            Some(String::from(
+                "[Name Error on 'bump'] 'count' is not defined."
+            ))
+        );
+
+        // def bump() -> TInteger: global count; count = count + 1; return count
+        // count = 0
+        let later = || Assignment("count".to_string(), Box::new(CInt(0)), None);
+        assert!(check_stmt(Sequence(Box::new(bump(increment)), Box::new(later())), &env).is_ok());
+        let rename = CString("one".to_string());
+        assert_eq!(
+            check_stmt(Sequence(Box::new(bump(rename)), Box::new(later())), &env).err(),
+            Some(String::from("[Type Error on 'bump()'] 'count' has mismatched types: expected 'TInteger', found 'TString'."))
+        );
     }
 
     #[test]
-    fn check_func_def_error() {
+    fn check_functions_read_later_globals() {
         let env: Environment<Type> = Environment::new();
-
-        let func = FuncDef(Function {
-            name: "add".to_string(),
-            kind: Some(TInteger),
-            params: Some(vec![
-                ("a".to_string(), TInteger),
-                ("b".to_string(), TInteger),
-            ]),
-            body: Some(Box::new(Return(Box::new(CTrue)))),
-        });
-
-        match check_stmt(func, &env) {
-            Ok(_) => assert!(false),
-            Err(s) => assert_eq!(
-                s,
-                "[Type Error] 'add()' has mismatched types: expected 'TInteger', found 'TBool'."
-            ),
+        let get = |kind: Type| {
+            FuncDef(Function {
+                name: "get".to_string(),
+                type_params: vec![],
+                kind: Some(kind),
+                params: Some(vec![]),
+                variadic: false,
+                defaults: vec![],
+                body: Some(Box::new(Return(Box::new(Var("g".to_string()))))),
+            })
+        };
+        let g = || Assignment("g".to_string(), Box::new(CInt(1)), None);
+
+        // def get() -> TInteger: return g
+        // g = 1
+        // n = get()
+        let program = Sequence(
+            Box::new(get(TInteger)),
+            Box::new(Sequence(
+                Box::new(g()),
+                Box::new(Assignment(
+                    "n".to_string(),
+                    Box::new(FuncCall("get".to_string(), vec![])),
+                    None,
+                )),
+            )),
+        );
+        match check_stmt(program, &env) {
+            Ok(ControlFlow::Continue(new_env)) => {
+                assert_eq!(new_env.search_frame("n".to_string()), Some(&TInteger))
+            }
+            _ => assert!(false),
         }
+
+        // The global has the type the module assigns it
+        assert_eq!(
+            check_stmt(Sequence(Box::new(get(TString)), Box::new(g())), &env).err(),
+            Some(String::from(
+                "[Type Error] 'get()' has mismatched types: expected 'TString', found 'TInteger'."
+            ))
+        );
+        // The module itself cannot read it before the assignment
+        let read = Assignment("n".to_string(), Box::new(Var("g".to_string())), None);
+        assert_eq!(
+            check_stmt(Sequence(Box::new(read), Box::new(g())), &env).err(),
+            Some(String::from(
+                "[Name Error on '__main__'] 'g' is not defined."
+            ))
+        );
     }
 
     #[test]
-    fn check_return_outside_function() {
+    fn check_val_reassignment_through_global() {
         let env: Environment<Type> = Environment::new();
 
-        let retrn = Return(Box::new(CInt(1)));
+        // val count = 0
+        // def bump() -> TInteger: global count; count = 1; return count
+        let program = Sequence(
+            Box::new(Sequence(
+                Box::new(ValDeclaration("count".to_string(), None)),
+                Box::new(Assignment("count".to_string(), Box::new(CInt(0)), None)),
+            )),
+            Box::new(bump(CInt(1))),
+        );
 
-        match check_stmt(retrn, &env) {
-            Ok(_) => assert!(false),
-            Err(s) => assert_eq!(s, "[Syntax Error] return statement outside function."),
-        }
+        assert_eq!(
+            check_stmt(program, &env).err(),
+            Some(String::from("[Type Error on 'bump()'] cannot reassign 'count', declared with 'val' in '__main__()'."))
+        );
     }
 
     #[test]
-    fn check_function_call_wrong_args() {
+    fn check_nonlocal_declarations() {
         let env: Environment<Type> = Environment::new();
 
-        let func = FuncDef(Function {
-            name: "add".to_string(),
+        // def counter() -> TInteger:
+        //     n = 0
+        //     def step() -> TInteger:
+        //         nonlocal n
+        //         n = n + 1
+        //         return n
+        //     return step()
+        let step = FuncDef(Function {
+            name: "step".to_string(),
+            type_params: vec![],
             kind: Some(TInteger),
-            params: Some(vec![
-                ("a".to_string(), TInteger),
-                ("b".to_string(), TInteger),
-            ]),
+            params: Some(vec![]),
+            variadic: false,
+            defaults: vec![],
             body: Some(Box::new(Sequence(
-                Box::new(Assignment(
-                    "c".to_string(),
-                    Box::new(Add(
-                        Box::new(Var("a".to_string())),
-                        Box::new(Var("b".to_string())),
+                Box::new(Nonlocal(vec!["n".to_string()])),
+                Box::new(Sequence(
+                    Box::new(Assignment(
+                        "n".to_string(),
+                        Box::new(Add(Box::new(Var("n".to_string())), Box::new(CInt(1)))),
+                        None,
                     )),
-                    Some(TInteger),
+                    Box::new(Return(Box::new(Var("n".to_string())))),
                 )),
-                Box::new(Return(Box::new(Var("c".to_string())))),
             ))),
         });
-        let program1 = Sequence(
-            Box::new(func.clone()),
-            Box::new(Assignment(
-                "var".to_string(),
-                Box::new(FuncCall("add".to_string(), vec![CInt(1)])),
-                Some(TInteger),
-            )),
+        let counter = FuncDef(Function {
+            name: "counter".to_string(),
+            type_params: vec![],
+            kind: Some(TInteger),
+            params: Some(vec![]),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(Sequence(
+                Box::new(Assignment("n".to_string(), Box::new(CInt(0)), None)),
+                Box::new(Sequence(
+                    Box::new(step.clone()),
+                    Box::new(Return(Box::new(FuncCall("step".to_string(), vec![])))),
+                )),
+            ))),
+        });
+
+        assert!(check_stmt(counter, &env).is_ok());
+        assert_eq!(
+            check_stmt(step, &env).err(),
+            Some(String::from(
+                "[Name Error on 'step()'] no binding for nonlocal 'n' found."
+            ))
+        );
+        assert_eq!(
+            check_stmt(Nonlocal(vec!["n".to_string()]), &env).err(),
+            Some(String::from(
+                "[Syntax Error] nonlocal declaration not allowed at module level."
+            ))
+        );
+    }
+
+    // def scale(x: TInteger, by: TInteger = 2) -> TInteger: return x * by
+    fn scale(default: Expression) -> Statement {
+        FuncDef(Function {
+            name: "scale".to_string(),
+            type_params: vec![],
+            kind: Some(TInteger),
+            params: Some(vec![
+                ("x".to_string(), TInteger),
+                ("by".to_string(), TInteger),
+            ]),
+            variadic: false,
+            defaults: vec![("by".to_string(), default)],
+            body: Some(Box::new(Return(Box::new(Mul(
+                Box::new(Var("x".to_string())),
+                Box::new(Var("by".to_string())),
+            ))))),
+        })
+    }
+
+    #[test]
+    fn check_default_and_keyword_arguments() {
+        let env: Environment<Type> = Environment::new();
+        let env = match check_stmt(scale(CInt(2)), &env) {
+            Ok(ControlFlow::Continue(new_env)) => new_env,
+            _ => panic!("definitions should type check"),
+        };
+        let call = |args: Vec<Expression>| check_exp(FuncCall("scale".to_string(), args), &env);
+        let keyword = |name: &str, exp: Expression| KeywordArg(name.to_string(), Box::new(exp));
+
+        assert_eq!(call(vec![CInt(1)]), Ok(TInteger));
+        assert_eq!(
+            call(vec![keyword("by", CInt(3)), keyword("x", CInt(1))]),
+            Ok(TInteger)
+        );
+        assert_eq!(call(vec![CInt(1), keyword("by", CInt(3))]), Ok(TInteger));
+        assert_eq!(
+            call(vec![CInt(1), keyword("step", CInt(3))]),
+            Err(String::from(
+                "[Type Error on '__main__()'] 'scale()' got an unexpected keyword argument 'step'."
+            ))
+        );
+        assert_eq!(
+            call(vec![CInt(1), keyword("x", CInt(3))]),
+            Err(String::from(
+                "[Type Error on '__main__()'] 'scale()' got multiple values for argument 'x'."
+            ))
+        );
+        assert_eq!(
+            call(vec![keyword("by", CInt(3))]),
+            Err(String::from(
+                "[Type Error on '__main__()'] 'scale()' missing argument 'x'."
+            ))
         );
-        let program2 = Sequence(
-            Box::new(func),
-            Box::new(Assignment(
-                "var".to_string(),
-                Box::new(FuncCall("add".to_string(), vec![CInt(1), CInt(2), CInt(3)])),
-                Some(TInteger),
-            )),
+        assert_eq!(
+            call(vec![keyword("by", CString("two".to_string())), CInt(1)]),
+            Err(String::from("[Type Error on '__main__()'] 'scale()' has a positional argument after keyword arguments."))
+        );
+        assert_eq!(
+            call(vec![CInt(1), keyword("by", CString("two".to_string()))]),
+            Err(String::from("[Type Error on '__main__()'] 'scale()' has mismatched arguments: expected 'TInteger', found 'TString'."))
         );
-
-        match check_stmt(program1, &env.clone()) {
-            Ok(_) => assert!(false),
-            Err(s) => assert_eq!(
-                s,
-                "[Type Error on '__main__()'] 'add()' expected 2 arguments, found 1."
-            ),
-        }
-        match check_stmt(program2, &env) {
-            Ok(_) => assert!(false),
-            Err(s) => assert_eq!(
-                s,
-                "[Type Error on '__main__()'] 'add()' expected 2 arguments, found 3."
-            ),
-        }
     }
 
     #[test]
-    fn check_function_call_wrong_type() {
+    fn check_default_values() {
         let env: Environment<Type> = Environment::new();
 
-        let func = FuncDef(Function {
-            name: "add".to_string(),
+        assert_eq!(
+            check_stmt(scale(CTrue), &env).err(),
+            Some(String::from("[Type Error on 'scale()'] default value of 'by' has mismatched types: expected 'TInteger', found 'TBool'."))
+        );
+
+        let mut swapped = match scale(CInt(2)) {
+            FuncDef(func) => func,
+            _ => unreachable!(),
+        };
+        swapped.defaults = vec![("x".to_string(), CInt(1))];
+        assert_eq!(
+            check_stmt(FuncDef(swapped), &env).err(),
+            Some(String::from(
+                "[Parameter Error] Parameter 'by' without a default follows a parameter with one"
+            ))
+        );
+    }
+
+    #[test]
+    fn check_variadic_parameters_and_unpacking() {
+        // def count(first: TInteger, *rest: TInteger) -> TInteger: return first
+        let count = FuncDef(Function {
+            name: "count".to_string(),
+            type_params: vec![],
             kind: Some(TInteger),
             params: Some(vec![
-                ("a".to_string(), TInteger),
-                ("b".to_string(), TInteger),
+                ("first".to_string(), TInteger),
+                ("rest".to_string(), TList(Box::new(TInteger))),
             ]),
-            body: Some(Box::new(Sequence(
-                Box::new(Assignment(
-                    "c".to_string(),
-                    Box::new(Add(
-                        Box::new(Var("a".to_string())),
-                        Box::new(Var("b".to_string())),
-                    )),
-                    Some(TInteger),
-                )),
-                Box::new(Return(Box::new(Var("c".to_string())))),
-            ))),
+            variadic: true,
+            defaults: vec![],
+            body: Some(Box::new(Return(Box::new(Var("first".to_string()))))),
         });
-        let program = Sequence(
-            Box::new(func.clone()),
-            Box::new(Assignment(
-                "var".to_string(),
-                Box::new(FuncCall("add".to_string(), vec![CInt(1), CTrue])),
-                Some(TInteger),
-            )),
+        let mut env: Environment<Type> = Environment::new();
+        env.insert_variable("xs".to_string(), TList(Box::new(TInteger)));
+        env.insert_variable("pair".to_string(), TTuple(vec![TInteger, TInteger]));
+        let env = match check_stmt(Sequence(Box::new(count), Box::new(scale(CInt(2)))), &env) {
+            Ok(ControlFlow::Continue(new_env)) => new_env,
+            _ => panic!("definitions should type check"),
+        };
+        let call =
+            |name: &str, args: Vec<Expression>| check_exp(FuncCall(name.to_string(), args), &env);
+        let unpack = |name: &str| Unpack(Box::new(Var(name.to_string())));
+
+        assert_eq!(call("count", vec![CInt(1)]), Ok(TInteger));
+        assert_eq!(call("count", vec![CInt(1), CInt(2), CInt(3)]), Ok(TInteger));
+        assert_eq!(call("count", vec![CInt(1), unpack("xs")]), Ok(TInteger));
+        // A list has no static length, so it cannot fill 'first'
+        assert_eq!(
+            call("count", vec![unpack("xs")]),
+            Err(String::from("[Type Error on '__main__()'] 'count()' can only take an unpacked list as its variadic parameter."))
         );
-
-        match check_stmt(program, &env.clone()) {
-            Ok(_) => assert!(false),
-            Err(s) => assert_eq!(s, "[Type Error on '__main__()'] 'add()' has mismatched arguments: expected 'TInteger', found 'TBool'."),
-        }
+        assert_eq!(
+            call("count", vec![CInt(1), CInt(2), CTrue]),
+            Err(String::from("[Type Error on '__main__()'] 'count()' has mismatched arguments: expected 'TInteger', found 'TBool'."))
+        );
+        assert_eq!(
+            call("count", vec![]),
+            Err(String::from(
+                "[Type Error on '__main__()'] 'count()' expected at least 1 argument, found 0."
+            ))
+        );
+        // Arguments after an unpacked list also go to the variadic parameter
+        assert_eq!(
+            call("count", vec![CInt(1), unpack("xs"), CInt(2), unpack("xs")]),
+            Ok(TInteger)
+        );
+        assert_eq!(
+            call("count", vec![CInt(1), unpack("xs"), CTrue]),
+            Err(String::from("[Type Error on '__main__()'] 'count()' has mismatched arguments: expected 'TInteger', found 'TBool'."))
+        );
+        assert_eq!(
+            call("count", vec![CInt(1), unpack("pair")]),
+            Err(String::from("[Type Error on '__main__()'] cannot unpack 'TTuple([TInteger, TInteger])', expected a list."))
+        );
+        assert!(call("scale", vec![unpack("xs")]).is_err());
+        assert!(call("count", vec![unpack("xs"), CInt(1)]).is_err());
+        assert!(check_exp(unpack("xs"), &env).is_err());
     }
 
     #[test]
-    fn check_function_call_non_function() {
+    fn check_void_functions() {
         let env: Environment<Type> = Environment::new();
+        let func = |kind: Option<Type>, body: Statement| {
+            FuncDef(Function {
+                name: "reset".to_string(),
+                type_params: vec![],
+                kind,
+                params: Some(vec![]),
+                variadic: false,
+                defaults: vec![],
+                body: Some(Box::new(body)),
+            })
+        };
+        let assign = Assignment("x".to_string(), Box::new(CInt(0)), Some(TInteger));
+
+        // Void functions may fall off the end or use a bare 'return'
+        assert!(check_stmt(func(Some(TVoid), assign.clone()), &env).is_ok());
+        assert!(check_stmt(func(None, assign.clone()), &env).is_ok());
+        assert!(check_stmt(func(Some(TVoid), Return(Box::new(CVoid))), &env).is_ok());
+        assert_eq!(
+            check_stmt(func(Some(TInteger), assign), &env).err(),
+            Some(String::from(
+                "[Syntax Error] 'reset()' does not have a return statement."
+            ))
+        );
+        assert_eq!(
+            check_stmt(func(Some(TInteger), Return(Box::new(CVoid))), &env).err(),
+            Some(String::from(
+                "[Type Error] 'reset()' has mismatched types: expected 'TInteger', found 'TVoid'."
+            ))
+        );
+        assert_eq!(
+            check_stmt(func(Some(TVoid), Return(Box::new(CInt(1)))), &env).err(),
+            Some(String::from(
+                "[Type Error] 'reset()' has mismatched types: expected 'TVoid', found 'TInteger'."
+            ))
+        );
 
-        let program = Sequence(
-            Box::new(Assignment(
-                "a".to_string(),
-                Box::new(CInt(1)),
-                Some(TInteger),
-            )),
-            Box::new(Assignment(
-                "b".to_string(),
-                Box::new(FuncCall("a".to_string(), vec![])),
-                Some(TInteger),
-            )),
+        let env = match check_stmt(func(Some(TVoid), Return(Box::new(CVoid))), &env) {
+            Ok(ControlFlow::Continue(new_env)) => new_env,
+            _ => panic!("definitions should type check"),
+        };
+        assert_eq!(
+            check_exp(FuncCall("reset".to_string(), vec![]), &env),
+            Ok(TVoid)
         );
+    }
 
-        match check_stmt(program, &env.clone()) {
-            Ok(_) => assert!(false),
-            Err(s) => assert_eq!(s, "[Name Error on '__main__()'] 'a()' is not defined."),
-        }
+    #[test]
+    fn check_all_paths_return() {
+        let env: Environment<Type> = Environment::new();
+        let func = |body: Statement| {
+            FuncDef(Function {
+                name: "pick".to_string(),
+                type_params: vec![],
+                kind: Some(TInteger),
+                params: Some(vec![("c".to_string(), TBool)]),
+                variadic: false,
+                defaults: vec![],
+                body: Some(Box::new(body)),
+            })
+        };
+        let ret = |n: i32| Return(Box::new(CInt(n)));
+        let cond = || Box::new(Var("c".to_string()));
+        let missing = Some(String::from(
+            "[Type Error on 'pick()'] not every path returns a value of type 'TInteger'.",
+        ));
+
+        // if c: return 1
+        let if_only = IfThenElse(cond(), Box::new(ret(1)), None);
+        assert_eq!(check_stmt(func(if_only.clone()), &env).err(), missing);
+
+        // if c: return 1 / return 2
+        let if_then_return = Sequence(Box::new(if_only), Box::new(ret(2)));
+        assert!(check_stmt(func(if_then_return), &env).is_ok());
+
+        // if c: return 1 else: x = 1
+        let assign = Assignment("x".to_string(), Box::new(CInt(1)), Some(TInteger));
+        let if_else = IfThenElse(cond(), Box::new(ret(1)), Some(Box::new(assign)));
+        assert_eq!(check_stmt(func(if_else), &env).err(), missing);
+
+        // while c: return 1
+        let while_only = While(cond(), Box::new(Block(vec![ret(1)])));
+        assert_eq!(check_stmt(func(while_only.clone()), &env).err(), missing);
+
+        // while c: return 1 / return 2
+        let while_then_return = Block(vec![while_only, ret(2)]);
+        assert!(check_stmt(func(while_then_return), &env).is_ok());
+
+        // Both branches return through nested blocks
+        let nested = Block(vec![IfThenElse(
+            cond(),
+            Box::new(Block(vec![ret(1)])),
+            Some(Box::new(Block(vec![Block(vec![ret(2)])]))),
+        )]);
+        assert!(check_stmt(func(nested), &env).is_ok());
     }
 
     #[test]
-    fn check_function_call_undefined() {
+    fn check_program_reports_every_error() {
         let env: Environment<Type> = Environment::new();
+        let var = |name: &str| Box::new(Var(name.to_string()));
+        let assign =
+            |name: &str, exp: Expression| Assignment(name.to_string(), Box::new(exp), None);
+
+        // def f(a: TInteger) -> TInteger:
+        //     c = a + True
+        //     return a
+        //     a = 2
+        let f = FuncDef(Function {
+            name: "f".to_string(),
+            type_params: vec![],
+            kind: Some(TInteger),
+            params: Some(vec![("a".to_string(), TInteger)]),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(Block(vec![
+                assign("c", Add(var("a"), Box::new(CTrue))),
+                Return(var("a")),
+                assign("a", CInt(2)),
+            ]))),
+        });
+        let program = vec![
+            assign("x", Add(Box::new(CInt(1)), Box::new(CTrue))),
+            // Uses of 'x' are not reported again
+            assign("y", Mul(var("x"), Box::new(CInt(2)))),
+            assign("z", Var("w".to_string())),
+            IfThenElse(
+                Box::new(CInt(1)),
+                Box::new(Block(vec![assign("b", Not(Box::new(CInt(1))))])),
+                None,
+            ),
+            f,
+            assign("g", FuncCall("f".to_string(), vec![CTrue])),
+            Return(Box::new(CInt(0))),
+        ];
 
-        let program = Assignment(
-            "a".to_string(),
-            Box::new(FuncCall("func".to_string(), vec![])),
-            Some(TInteger),
-        );
+        let diagnostics = check_program(program, &env);
+        let found: Vec<(Severity, &str, &str)> = diagnostics
+            .iter()
+            .map(|d| (d.severity.clone(), d.code.as_str(), d.message.as_str()))
+            .collect();
 
-        match check_stmt(program, &env.clone()) {
-            Ok(_) => assert!(false),
-            Err(s) => assert_eq!(s, "[Name Error on '__main__()'] 'func()' is not defined."),
-        }
+        assert_eq!(
+            found,
+            vec![
+                (Severity::Error, "TypeError", "[Type Error] expecting numeric type values."),
+                (Severity::Error, "NameError", "[Name Error on '__main__'] 'w' is not defined."),
+                (Severity::Error, "TypeError", "[Type Error on '__main__()'] if expression must be boolean."),
+                (Severity::Error, "TypeError", "[Type Error] expecting a boolean type value."),
+                (Severity::Error, "TypeError", "[Type Error] expecting numeric type values."),
+                (Severity::Warning, "UnreachableCode", "[Warning on 'f()'] code after a return statement is never run."),
+                (Severity::Error, "TypeError", "[Type Error on '__main__()'] 'f()' has mismatched arguments: expected 'TInteger', found 'TBool'."),
+                (Severity::Error, "SyntaxError", "[Syntax Error] return statement outside function."),
+            ]
+        );
+        assert!(diagnostics.iter().all(|d| d.span.is_none()));
     }
+
     #[test]
-    fn check_recursive_function() {
+    fn check_program_reports_errors_in_each_subexpression() {
         let env: Environment<Type> = Environment::new();
 
-        // Definição de função fatorial recursiva
-        let factorial = FuncDef(Function {
-            name: "factorial".to_string(),
-            kind: Some(TInteger),
-            params: Some(vec![("n".to_string(), TInteger)]),
-            body: Some(Box::new(IfThenElse(
-                Box::new(EQ(Box::new(Var("n".to_string())), Box::new(CInt(0)))),
-                Box::new(Return(Box::new(CInt(1)))),
-                Some(Box::new(Return(Box::new(Mul(
-                    Box::new(Var("n".to_string())),
-                    Box::new(FuncCall(
-                        "factorial".to_string(),
-                        vec![Sub(Box::new(Var("n".to_string())), Box::new(CInt(1)))],
-                    )),
-                ))))),
-            ))),
-        });
+        // x = (1 + True) + (False * 2)
+        // match missing:
+        //     case Some(v): y = v
+        let program = vec![
+            Assignment(
+                "x".to_string(),
+                Box::new(Add(
+                    Box::new(Add(Box::new(CInt(1)), Box::new(CTrue))),
+                    Box::new(Mul(Box::new(CFalse), Box::new(CInt(2)))),
+                )),
+                None,
+            ),
+            Statement::Match(
+                Box::new(Var("missing".to_string())),
+                vec![(
+                    Pattern::Constructor(
+                        "Some".to_string(),
+                        vec![Pattern::Binding("v".to_string())],
+                    ),
+                    Assignment("y".to_string(), Box::new(Var("v".to_string())), None),
+                )],
+            ),
+        ];
 
-        match check_stmt(factorial, &env) {
-            Ok(ControlFlow::Continue(new_env)) => {
-                assert_eq!(
-                    new_env.search_frame("factorial".to_string()),
-                    Some(TFunction(Box::new(Some(TInteger)), vec![TInteger])).as_ref()
-                );
-            }
-            _ => assert!(false, "Recursive function definition failed"),
-        }
+        let found: Vec<(String, String)> = check_program(program, &env)
+            .into_iter()
+            .map(|d| (d.code, d.message))
+            .collect();
+
+        assert_eq!(
+            found,
+            vec![
+                (
+                    "TypeError".to_string(),
+                    "[Type Error] expecting numeric type values.".to_string()
+                ),
+                (
+                    "TypeError".to_string(),
+                    "[Type Error] expecting numeric type values.".to_string()
+                ),
+                (
+                    "NameError".to_string(),
+                    "[Name Error on '__main__'] 'missing' is not defined.".to_string()
+                ),
+            ]
+        );
     }
 
     #[test]
-    fn check_function_multiple_return_paths() {
+    fn check_program_recovers_inside_functions() {
         let env: Environment<Type> = Environment::new();
 
-        // Função com múltiplos caminhos de retorno
-        let func = FuncDef(Function {
-            name: "max".to_string(),
-            kind: Some(TInteger),
-            params: Some(vec![
-                ("a".to_string(), TInteger),
-                ("b".to_string(), TInteger),
-            ]),
-            body: Some(Box::new(IfThenElse(
-                Box::new(GT(
-                    Box::new(Var("a".to_string())),
-                    Box::new(Var("b".to_string())),
+        // def f() -> TInteger: return True
+        // y = f() + 1
+        let program = vec![
+            FuncDef(Function {
+                name: "f".to_string(),
+                type_params: vec![],
+                kind: Some(TInteger),
+                params: Some(vec![]),
+                variadic: false,
+                defaults: vec![],
+                body: Some(Box::new(Return(Box::new(CTrue)))),
+            }),
+            Assignment(
+                "y".to_string(),
+                Box::new(Add(
+                    Box::new(FuncCall("f".to_string(), vec![])),
+                    Box::new(CInt(1)),
                 )),
-                Box::new(Return(Box::new(Var("a".to_string())))),
-                Some(Box::new(Return(Box::new(Var("b".to_string()))))),
-            ))),
-        });
+                None,
+            ),
+        ];
 
-        match check_stmt(func, &env) {
-            Ok(ControlFlow::Continue(_)) => assert!(true),
-            _ => assert!(false, "Multiple return paths function failed"),
-        }
+        // The failed return still ends the function, and 'f' keeps its signature
+        assert_eq!(
+            check_program(program, &env),
+            vec![Diagnostic {
+                severity: Severity::Error,
+                code: String::from("TypeError"),
+                message: String::from(
+                    "[Type Error] 'f()' has mismatched types: expected 'TInteger', found 'TBool'."
+                ),
+                span: None,
+            }]
+        );
+        assert!(check_program(vec![], &env).is_empty());
     }
 
     #[test]
-    fn test_function_wrong_return_type() {
+    fn check_program_reports_statement_locations() {
         let env: Environment<Type> = Environment::new();
+        let at = |line: usize, column: usize, stmt: Statement| {
+            Located(Location { line, column }, Box::new(stmt))
+        };
+
+        // def f() -> TInteger:
+        //     return True
+        //     x = 1
+        // y = 1 + True
+        let program = vec![
+            at(
+                1,
+                1,
+                FuncDef(Function {
+                    name: "f".to_string(),
+                    type_params: vec![],
+                    kind: Some(TInteger),
+                    params: Some(vec![]),
+                    variadic: false,
+                    defaults: vec![],
+                    body: Some(Box::new(Block(vec![
+                        at(2, 5, Return(Box::new(CTrue))),
+                        at(3, 5, Assignment("x".to_string(), Box::new(CInt(1)), None)),
+                    ]))),
+                }),
+            ),
+            at(
+                4,
+                1,
+                Assignment(
+                    "y".to_string(),
+                    Box::new(Add(Box::new(CInt(1)), Box::new(CTrue))),
+                    None,
+                ),
+            ),
+        ];
 
-        let func = FuncDef(Function {
-            name: "wrong_return".to_string(),
+        let found: Vec<(String, Option<Location>)> = check_program(program, &env)
+            .into_iter()
+            .map(|d| (d.code, d.span))
+            .collect();
+
+        assert_eq!(
+            found,
+            vec![
+                (
+                    "TypeError".to_string(),
+                    Some(Location { line: 2, column: 5 })
+                ),
+                (
+                    "UnreachableCode".to_string(),
+                    Some(Location { line: 3, column: 5 })
+                ),
+                (
+                    "TypeError".to_string(),
+                    Some(Location { line: 4, column: 1 })
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn check_if_keeps_variables_bound_by_both_branches() {
+        let env: Environment<Type> = Environment::new();
+        let assign = |name: &str, exp: Expression| {
+            Box::new(Assignment(name.to_string(), Box::new(exp), None))
+        };
+        let var = |name: &str| Var(name.to_string());
+
+        // if True: y = 1 else: y = <value>
+        // z = y
+        let program = |stmt_else: Option<Box<Statement>>| {
+            Sequence(
+                Box::new(IfThenElse(Box::new(CTrue), assign("y", CInt(1)), stmt_else)),
+                assign("z", var("y")),
+            )
+        };
+
+        match check_stmt(program(Some(assign("y", CInt(2)))), &env) {
+            Ok(ControlFlow::Continue(new_env)) => {
+                assert_eq!(new_env.search_frame("z".to_string()), Some(&TInteger))
+            }
+            _ => assert!(false),
+        }
+        // def f() -> TInteger:
+        //     if True: return 0 else: y = 2
+        //     return y
+        let returns = FuncDef(Function {
+            name: "f".to_string(),
+            type_params: vec![],
             kind: Some(TInteger),
-            params: None,
-            body: Some(Box::new(Return(Box::new(CReal(1.0))))),
+            params: Some(vec![]),
+            variadic: false,
+            defaults: vec![],
+            body: Some(Box::new(Block(vec![
+                IfThenElse(
+                    Box::new(CTrue),
+                    Box::new(Return(Box::new(CInt(0)))),
+                    Some(assign("y", CInt(2))),
+                ),
+                Return(Box::new(var("y"))),
+            ]))),
         });
+        assert!(check_stmt(returns, &env).is_ok());
 
-        match check_stmt(func, &env) {
-            Ok(_) => assert!(false, "Should fail due to wrong return type"),
-            Err(msg) => assert_eq!(
-                msg,
-                "[Type Error] 'wrong_return()' has mismatched types: expected 'TInteger', found 'TReal'."
-            ),
-        }
+        assert_eq!(
+            check_stmt(program(None), &env).err(),
+            Some(String::from(
+                "[Name Error on '__main__'] 'y' is not defined."
+            ))
+        );
+        assert_eq!(
+            check_stmt(program(Some(assign("y", CTrue))), &env).err(),
+            Some(String::from(
+                "[Type Error on '__main__()'] 'y' has mismatched types: expected 'TInteger', found 'TBool'."
+            ))
+        );
     }
 
     #[test]
-    fn test_function_parameter_shadowing() {
+    fn check_blocks_and_assertions() {
         let env: Environment<Type> = Environment::new();
+        let message = || "failed".to_string();
 
-        let func = FuncDef(Function {
-            name: "shadow_test".to_string(),
-            kind: Some(TInteger),
-            params: Some(vec![
-                ("x".to_string(), TInteger),
-                ("x".to_string(), TInteger), // Mesmo nome de parâmetro
-            ]),
-            body: Some(Box::new(Return(Box::new(Var("x".to_string()))))),
-        });
+        // if True: x = 1 else: x = 2
+        let branches = IfThenElse(
+            Box::new(CTrue),
+            Box::new(Block(vec![Assignment(
+                "x".to_string(),
+                Box::new(CInt(1)),
+                None,
+            )])),
+            Some(Box::new(Block(vec![Assignment(
+                "x".to_string(),
+                Box::new(CInt(2)),
+                None,
+            )]))),
+        );
+        assert!(check_stmt(branches, &env).is_ok());
+
+        let env = match check_stmt(
+            Block(vec![Assignment("n".to_string(), Box::new(CInt(1)), None)]),
+            &env,
+        ) {
+            Ok(ControlFlow::Continue(new_env)) => new_env,
+            _ => panic!("block should type check"),
+        };
+        assert_eq!(check_exp(Var("n".to_string()), &env), Ok(TInteger));
+
+        let n = || Box::new(Var("n".to_string()));
+        assert!(check_stmt(
+            AssertTrue(Box::new(GT(n(), Box::new(CInt(0)))), message()),
+            &env
+        )
+        .is_ok());
+        assert!(check_stmt(AssertFalse(Box::new(CFalse), message()), &env).is_ok());
+        assert!(check_stmt(AssertEQ(n(), Box::new(CInt(1)), message()), &env).is_ok());
+        assert!(check_stmt(AssertNEQ(n(), Box::new(CReal(2.0)), message()), &env).is_ok());
+        assert!(check_stmt(AssertFails(message()), &env).is_ok());
+        assert_eq!(
+            check_stmt(AssertTrue(n(), message()), &env).err(),
+            Some(String::from(
+                "[Type Error on '__main__()'] assert expression must be boolean."
+            ))
+        );
+        assert_eq!(
+            check_stmt(
+                AssertEQ(n(), Box::new(CString("1".to_string())), message()),
+                &env
+            )
+            .err(),
+            Some(String::from(
+                "[Type Error] cannot compare 'TInteger' with 'TString'."
+            ))
+        );
+    }
 
-        match check_stmt(func, &env) {
-            Ok(_) => panic!("Should not accept duplicate parameter names"),
-            Err(msg) => assert_eq!(msg, "[Parameter Error] Duplicate parameter name 'x'"),
+    #[test]
+    fn check_test_definitions() {
+        let env: Environment<Type> = Environment::new();
+        let test = |params: Option<Vec<(Name, Type)>>, body: Statement| {
+            TestDef(Function {
+                name: "test_sum".to_string(),
+                type_params: vec![],
+                kind: Some(TVoid),
+                params,
+                variadic: false,
+                defaults: vec![],
+                body: Some(Box::new(body)),
+            })
+        };
+        let assertion = AssertEQ(
+            Box::new(Add(Box::new(CInt(1)), Box::new(CInt(1)))),
+            Box::new(CInt(2)),
+            "1 + 1 should be 2".to_string(),
+        );
+
+        match check_stmt(test(None, assertion.clone()), &env) {
+            Ok(ControlFlow::Continue(new_env)) => {
+                assert!(new_env
+                    .get_frame(("__main__".to_string(), 0))
+                    .tests
+                    .contains_key("test_sum"))
+            }
+            _ => panic!("test definition should type check"),
+        }
+        assert_eq!(
+            check_stmt(
+                test(Some(vec![("x".to_string(), TInteger)]), assertion.clone()),
+                &env
+            )
+            .err(),
+            Some(String::from(
+                "[Type Error on '__main__()'] test 'test_sum' cannot take parameters."
+            ))
+        );
+        assert_eq!(
+            check_stmt(
+                test(None, AssertTrue(Box::new(CInt(1)), "one".to_string())),
+                &env
+            )
+            .err(),
+            Some(String::from(
+                "[Type Error on 'test_sum()'] assert expression must be boolean."
+            ))
+        );
+
+        // Names defined in a test module stay inside it
+        let module = ModTestDef(
+            "tests".to_string(),
+            Box::new(Sequence(
+                Box::new(Assignment("y".to_string(), Box::new(CInt(1)), None)),
+                Box::new(test(None, assertion)),
+            )),
+        );
+        match check_stmt(module, &env) {
+            Ok(ControlFlow::Continue(new_env)) => {
+                assert!(check_exp(Var("y".to_string()), &new_env).is_err())
+            }
+            _ => panic!("test module should type check"),
         }
     }
 }